@@ -76,7 +76,7 @@
 //     }
 // }
 
-// type MyApp = DefaultPlugins<Simp, Counter, "orga-0">;
+// type MyApp = DefaultPlugins<Simp, Counter>;
 
 // fn app_client() -> TendermintClient<MyApp> {
 //     TendermintClient::new("http://localhost:26357").unwrap()
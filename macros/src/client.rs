@@ -101,6 +101,7 @@ fn create_client_struct(
     let generic_params = gen_param_input(generics, false);
     let generic_params_bracketed = gen_param_input(generics, true);
     let generic_params_bracketed_with_parent = gen_param_input(&generics_with_parent, true);
+    let generics_params_with_parent = &generics_with_parent.params;
     let where_preds = item.generics.where_clause.as_ref().map(|w| &w.predicates);
 
     let field_fields = field_adapters
@@ -527,6 +528,13 @@ fn create_client_struct(
             }
         }
 
+        // raw calls and queries are passed through to the parent, e.g. for
+        // callers which build encoded calls at runtime
+        ::orga::pass_through_client! {
+            impl[#generics_params_with_parent] Client#generic_params_bracketed_with_parent => parent: #parent_ty
+            where [#parent_ty: Clone + Send, #where_preds]
+        }
+
         #(#call_method_impls_and_adapters)*
         #(#query_method_impls_and_adapters)*
     }
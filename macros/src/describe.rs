@@ -1,4 +1,5 @@
-use super::utils::gen_param_input;
+use super::query::is_query_field;
+use super::utils::{gen_param_input, parse_parent, relevant_methods};
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, ToTokens};
@@ -21,6 +22,9 @@ pub fn derive(item: TokenStream) -> TokenStream {
     let indexes = (0..struct_fields(&item).count()).map(num_to_token);
 
     let name = &item.ident;
+    let source = parse_parent();
+    let (calls, queries) = variants(&item, &source);
+
     let mut generics = item.generics.clone();
     generics.params.iter_mut().for_each(|p| {
         if let GenericParam::Type(tp) = p {
@@ -34,6 +38,16 @@ pub fn derive(item: TokenStream) -> TokenStream {
         .predicates;
     let generic_params = gen_param_input(&generics, true);
 
+    // the structs generated by #[orga] are named `<Name>V<version>` and carry
+    // the matching `#[state(version = ...)]` attribute
+    let versioned = state_version(&item)
+        .filter(|version| {
+            let name = name.to_string();
+            let suffix = format!("V{}", version);
+            name.len() > suffix.len() && name.ends_with(suffix.as_str())
+        })
+        .map(|version| quote!(.versioned(#version)));
+
     let output = quote! {
         impl#generics ::orga::describe::Describe for #name#generic_params
         where
@@ -43,6 +57,7 @@ pub fn derive(item: TokenStream) -> TokenStream {
         {
             fn describe() -> ::orga::describe::Descriptor {
                 ::orga::describe::Builder::new::<Self>()
+                #versioned
                 #(
                     .named_child::<#types>(
                         stringify!(#names),
@@ -50,6 +65,8 @@ pub fn derive(item: TokenStream) -> TokenStream {
                         |v| ::orga::describe::Builder::access(v, |v: Self| v.#names)
                     )
                 )*
+                #(#calls)*
+                #(#queries)*
                 .build()
             }
         }
@@ -58,6 +75,113 @@ pub fn derive(item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Generates builder calls describing the variants of the `Call` and `Query`
+/// enums derived for the type, using the same indexes as those derives.
+fn variants(item: &DeriveInput, source: &File) -> (Vec<TokenStream2>, Vec<TokenStream2>) {
+    let name = &item.ident;
+    let field_name = |i: usize, field: &Field| {
+        field
+            .ident
+            .as_ref()
+            .map_or(i.to_string(), |ident| ident.to_string())
+    };
+
+    let call_fields: Vec<_> = struct_fields(item)
+        .enumerate()
+        .filter(|(_, field)| field.attrs.iter().any(|attr| attr.path.is_ident("call")))
+        .map(|(i, field)| field_name(i, field))
+        .collect();
+    let query_fields: Vec<_> = struct_fields(item)
+        .enumerate()
+        .filter(|(_, field)| is_query_field(field))
+        .map(|(i, field)| field_name(i, field))
+        .collect();
+
+    let mut calls: Vec<_> = call_fields
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let index = i as u8 + 1;
+            quote!(.call_field(#name, #index))
+        })
+        .collect();
+    calls.extend(
+        relevant_methods(name, "call", source)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (method, _))| {
+                let index = (1 + call_fields.len() + i) as u8;
+                let name = method.sig.ident.to_string();
+                let args = method_args(&method);
+                quote!(.call_method(#name, #index, &[#(#args),*]))
+            }),
+    );
+
+    let mut queries: Vec<_> = query_fields
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let index = i as u8 + 1;
+            quote!(.query_field(#name, #index))
+        })
+        .collect();
+    queries.extend(
+        relevant_methods(name, "query", source)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (method, _))| {
+                let index = (1 + query_fields.len() + i) as u8;
+                let name = method.sig.ident.to_string();
+                let args = method_args(&method);
+                quote!(.query_method(#name, #index, &[#(#args),*]))
+            }),
+    );
+
+    (calls, queries)
+}
+
+fn method_args(method: &ImplItemMethod) -> Vec<TokenStream2> {
+    method
+        .sig
+        .inputs
+        .iter()
+        .skip(1)
+        .enumerate()
+        .map(|(i, input)| {
+            let input = match input {
+                FnArg::Typed(input) => input,
+                _ => panic!("unexpected input"),
+            };
+            let name = match &*input.pat {
+                Pat::Ident(pat) => pat.ident.to_string(),
+                _ => format!("arg{}", i),
+            };
+            let ty = &input.ty;
+            quote!(::orga::describe::Builder::arg::<#ty>(#name))
+        })
+        .collect()
+}
+
+/// The version given in the type's `#[state(version = ...)]` attribute, if any.
+fn state_version(item: &DeriveInput) -> Option<u32> {
+    item.attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("state"))
+        .filter_map(|attr| attr.parse_meta().ok())
+        .find_map(|meta| match meta {
+            Meta::List(list) => list.nested.into_iter().find_map(|nested| match nested {
+                NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("version") => {
+                    match value.lit {
+                        Lit::Int(lit) => lit.base10_parse().ok(),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }),
+            _ => None,
+        })
+}
+
 fn struct_fields(item: &DeriveInput) -> impl Iterator<Item = &Field> {
     let data = match item.data {
         Data::Struct(ref data) => data,
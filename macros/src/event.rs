@@ -0,0 +1,70 @@
+use heck::SnakeCase;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::*;
+
+pub fn derive(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+
+    let name = &item.ident;
+    let event_type = event_name(&item).unwrap_or_else(|| name.to_string().to_snake_case());
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let fields: Vec<_> = match item.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(ref fields),
+            ..
+        }) => fields
+            .named
+            .iter()
+            .map(|field| field.ident.clone().unwrap())
+            .collect(),
+        Data::Struct(DataStruct {
+            fields: Fields::Unit,
+            ..
+        }) => vec![],
+        _ => panic!("#[derive(Event)] only supports structs with named fields"),
+    };
+
+    let output = quote! {
+        impl#impl_generics ::orga::events::Event for #name#ty_generics
+        #where_clause
+        {
+            const TYPE: &'static str = #event_type;
+
+            fn attributes(&self) -> Vec<(String, String)> {
+                vec![
+                    #((stringify!(#fields).to_string(), self.#fields.to_string()),)*
+                ]
+            }
+
+            fn from_attributes(attributes: &[(String, String)]) -> ::orga::Result<Self> {
+                Ok(Self {
+                    #(#fields: ::orga::events::parse_attribute(attributes, stringify!(#fields))?,)*
+                })
+            }
+        }
+    };
+
+    output.into()
+}
+
+/// Reads the event type from a `#[event(name = "...")]` attribute, if any.
+fn event_name(item: &DeriveInput) -> Option<String> {
+    item.attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("event"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => panic!("Expected #[event(name = \"...\")]"),
+        })
+        .flatten()
+        .find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(name),
+                ..
+            })) if path.is_ident("name") => Some(name.value()),
+            _ => None,
+        })
+}
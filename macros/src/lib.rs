@@ -7,6 +7,7 @@ mod client;
 mod describe;
 mod encoding;
 mod entry;
+mod event;
 mod migrate_from;
 mod next;
 mod orga;
@@ -24,7 +25,12 @@ pub fn derive_entry(item: TokenStream) -> TokenStream {
     entry::derive(item)
 }
 
-#[proc_macro_derive(Query)]
+#[proc_macro_derive(Event, attributes(event))]
+pub fn derive_event(item: TokenStream) -> TokenStream {
+    event::derive(item)
+}
+
+#[proc_macro_derive(Query, attributes(query))]
 pub fn derive_query(item: TokenStream) -> TokenStream {
     query::derive(item)
 }
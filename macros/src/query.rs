@@ -138,7 +138,7 @@ pub(crate) fn create_query_impl(
         Data::Union(_) => panic!("Unions are not supported"),
     };
     let field_query_arms: Vec<_> = fields
-        .filter(|field| matches!(field.vis, Visibility::Public(_)))
+        .filter(|field| is_query_field(field))
         .enumerate()
         .map(|(i, field)| {
             let variant_name = field.ident.as_ref().map_or(
@@ -293,7 +293,7 @@ pub(crate) fn create_query_enum(item: &DeriveInput, source: &File) -> (TokenStre
         Data::Union(_) => panic!("Unions are not supported"),
     };
     let field_variants: Vec<_> = fields
-        .filter(|field| matches!(field.vis, Visibility::Public(_)))
+        .filter(|field| is_query_field(field))
         .enumerate()
         .map(|(i, field)| {
             let name = field.ident.as_ref().map_or(
@@ -474,3 +474,9 @@ fn gen_param_input(generics: &Generics, bracketed: bool) -> TokenStream2 {
         quote!(#(#gen_params),*)
     }
 }
+
+/// Public fields are queryable, as are private fields marked with `#[query]`.
+pub(super) fn is_query_field(field: &Field) -> bool {
+    matches!(field.vis, Visibility::Public(_))
+        || field.attrs.iter().any(|attr| attr.path.is_ident("query"))
+}
@@ -1,5 +1,6 @@
 #![cfg(feature = "abci")]
 use std::clone::Clone;
+use std::collections::HashMap;
 use std::env;
 use std::net::ToSocketAddrs;
 use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
@@ -7,6 +8,7 @@ use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
 use log::info;
 
 use crate::call::Call;
+use crate::encoding::{Decode, Encode};
 use crate::merk::MerkStore;
 use crate::query::Query;
 use crate::state::State;
@@ -36,8 +38,13 @@ pub struct ABCIStateMachine<A: Application> {
     consensus_state: Option<BufStoreMap>,
     height: u64,
     skip_init_chain: bool,
+    pending_txs: HashMap<String, usize>,
 }
 
+/// The maximum number of transactions from one sender which are accepted into
+/// the mempool between blocks.
+pub const MAX_PENDING_TXS_PER_SENDER: usize = 64;
+
 impl<A: Application> ABCIStateMachine<A> {
     /// Constructs an `ABCIStateMachine` from the given app (a set of handlers
     /// for transactions and blocks), and store (a key/value store to persist
@@ -53,7 +60,24 @@ impl<A: Application> ABCIStateMachine<A> {
             consensus_state: Some(Default::default()),
             height: 0,
             skip_init_chain,
+            pending_txs: HashMap::new(),
+        }
+    }
+
+    /// Counts a successfully checked transaction against its sender's pending
+    /// transactions, returning true if the sender is already at the limit.
+    fn exceeds_pending_limit(&mut self, res: &ResponseCheckTx) -> bool {
+        if res.code != 0 || res.sender.is_empty() {
+            return false;
         }
+
+        let pending = self.pending_txs.entry(res.sender.clone()).or_default();
+        if *pending >= MAX_PENDING_TXS_PER_SENDER {
+            return true;
+        }
+        *pending += 1;
+
+        false
     }
 
     /// Handles a single incoming ABCI request.
@@ -103,10 +127,10 @@ impl<A: Application> ABCIStateMachine<A> {
                 let res = app
                     .query(store.clone(), req)
                     .unwrap_or_else(|err| ResponseQuery {
-                        code: 1,
+                        code: err.code(),
                         log: err.to_string(),
                         info: err.to_string(),
-                        codespace: "".to_string(),
+                        codespace: err.codespace().to_string(),
                         height: self.height as i64,
                         index: 0,
                         key: vec![],
@@ -265,6 +289,8 @@ impl<A: Application> ABCIStateMachine<A> {
 
                 self.mempool_state.replace(Default::default());
                 self.consensus_state.replace(Default::default());
+                // Pending transactions are counted again as they are rechecked
+                self.pending_txs.clear();
 
                 let mut res_commit = ResponseCommit::default();
                 let self_store = self_store_shared.into_inner();
@@ -285,10 +311,24 @@ impl<A: Application> ABCIStateMachine<A> {
                 let res_check_tx = {
                     let owned_store = store.take().unwrap();
                     let flush_store = Shared::new(BufStore::wrap(owned_store.clone()));
-                    let res = app.check_tx(flush_store.clone(), req)?;
+                    let mut res = app.check_tx(flush_store.clone(), req)?;
 
                     let mut unwrapped_fs = flush_store.into_inner();
-                    unwrapped_fs.flush()?;
+                    if self.exceeds_pending_limit(&res) {
+                        // Rejected transactions do not affect the mempool state
+                        let err = Error::ABCI(format!(
+                            "Sender has too many pending transactions (max {})",
+                            MAX_PENDING_TXS_PER_SENDER
+                        ));
+                        res = ResponseCheckTx {
+                            code: err.code(),
+                            codespace: err.codespace().to_string(),
+                            log: err.to_string(),
+                            ..Default::default()
+                        };
+                    } else {
+                        unwrapped_fs.flush()?;
+                    }
                     store.replace(owned_store);
                     res
                 };
@@ -404,6 +444,26 @@ impl Worker {
 }
 
 type WrappedMerk = Shared<BufStore<Shared<BufStore<Shared<MerkStore>>>>>;
+
+/// ABCI query path used to resolve a
+/// [`BatchQuery`](../query/struct.BatchQuery.html) in a single round-trip,
+/// returning one proof which covers every query in the batch.
+pub const BATCH_QUERY_PATH: &str = "batch";
+
+/// ABCI query path used to simulate a transaction against the latest state,
+/// with a [SimulateRequest] as the query data. The response value is a
+/// protobuf-encoded `ResponseDeliverTx` with the result of the transaction,
+/// and no writes are persisted.
+pub const SIMULATE_QUERY_PATH: &str = "simulate";
+
+#[derive(Encode, Decode, Debug, Clone)]
+pub struct SimulateRequest {
+    /// Derive signers from public keys without verifying signatures, e.g. to
+    /// estimate the result of a transaction before it is signed.
+    pub skip_signatures: bool,
+    pub tx: Vec<u8>,
+}
+
 /// An interface for handling ABCI requests.
 ///
 /// All methods have a default implemenation which returns an empty response.
@@ -1,11 +1,15 @@
-use super::{ABCIStateMachine, ABCIStore, AbciQuery, App, Application, WrappedMerk};
+use super::{
+    ABCIStateMachine, ABCIStore, AbciQuery, App, Application, SimulateRequest, WrappedMerk,
+    BATCH_QUERY_PATH, SIMULATE_QUERY_PATH,
+};
 use crate::call::Call;
+use crate::context::Context;
 use crate::encoding::Decode;
 use crate::merk::{BackingStore, MerkStore};
-use crate::plugins::{ABCICall, ABCIPlugin};
-use crate::query::Query;
+use crate::plugins::{ABCICall, ABCIPlugin, CheckTxInfo, Recheck, Simulation};
+use crate::query::{BatchQuery, Query};
 use crate::state::State;
-use crate::store::{Read, Shared, Store, Write};
+use crate::store::{BufStore, Read, Shared, Store, Write};
 use crate::tendermint::Tendermint;
 use crate::Result;
 use home::home_dir;
@@ -262,22 +266,29 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
         store: WrappedMerk,
         req: RequestBeginBlock,
     ) -> Result<ResponseBeginBlock> {
-        self.run(store, move |state| state.call(req.into()))??;
+        let events = self.run(store, move |state| -> Result<_> {
+            state.call(req.into())?;
+            Ok(state.events.take().unwrap_or_default())
+        })??;
 
-        Ok(Default::default())
+        Ok(ResponseBeginBlock { events })
     }
 
     fn end_block(&self, store: WrappedMerk, req: RequestEndBlock) -> Result<ResponseEndBlock> {
-        let mut updates = self.run(store, move |state| -> Result<_> {
+        let (mut updates, events) = self.run(store, move |state| -> Result<_> {
             state.call(req.into())?;
-            Ok(state
+            let updates = state
                 .validator_updates
                 .take()
-                .expect("ABCI plugin did not create validator update map"))
+                .expect("ABCI plugin did not create validator update map");
+            Ok((updates, state.events.take().unwrap_or_default()))
         })??;
 
         // Write back validator updates
-        let mut res: ResponseEndBlock = Default::default();
+        let mut res = ResponseEndBlock {
+            events,
+            ..Default::default()
+        };
         updates.drain().for_each(|(_key, update)| {
             if let Ok(flag) = std::env::var("ORGA_STATIC_VALSET") {
                 if flag != "0" && flag != "false" {
@@ -305,7 +316,8 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
                 deliver_tx_res.log = "success".to_string();
             }
             Err(err) => {
-                deliver_tx_res.code = 1;
+                deliver_tx_res.code = err.code();
+                deliver_tx_res.codespace = err.codespace().to_string();
                 deliver_tx_res.log = err.to_string();
             }
         }
@@ -314,21 +326,31 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
     }
 
     fn check_tx(&self, store: WrappedMerk, req: RequestCheckTx) -> Result<ResponseCheckTx> {
+        let recheck = req.r#type == CheckTxType::Recheck as i32;
+        let tx_len = req.tx.len() as u64;
         let run_res = self.run(store, move |state| -> Result<_> {
             let inner_call = Decode::decode(req.tx.as_slice())?;
-            state.call(ABCICall::DeliverTx(inner_call))?;
+            let _info = Context::scope(CheckTxInfo::default());
+            let _recheck = recheck.then(|| Context::scope(Recheck));
+            state.call(ABCICall::CheckTx(inner_call))?;
 
-            Ok(state.events.take().unwrap_or_default())
+            let info = Context::resolve::<CheckTxInfo>().unwrap().clone();
+            Ok((state.events.take().unwrap_or_default(), info))
         })?;
 
         let mut check_tx_res = ResponseCheckTx::default();
 
         match run_res {
-            Ok(events) => {
+            Ok((events, info)) => {
+                let (sender, priority, gas_wanted) = mempool_info(&info, tx_len);
                 check_tx_res.events = events;
+                check_tx_res.sender = sender;
+                check_tx_res.priority = priority;
+                check_tx_res.gas_wanted = gas_wanted;
             }
             Err(err) => {
-                check_tx_res.code = 1;
+                check_tx_res.code = err.code();
+                check_tx_res.codespace = err.codespace().to_string();
                 check_tx_res.log = err.to_string();
             }
         }
@@ -346,7 +368,12 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
             Ok(state)
         };
 
-        if !req.path.is_empty() {
+        if req.path == SIMULATE_QUERY_PATH {
+            return self.simulate(merk_store, req);
+        }
+
+        let is_batch = req.path == BATCH_QUERY_PATH;
+        if !req.path.is_empty() && !is_batch {
             let store = BackingStore::Merk(merk_store);
             let state = create_state(store)?;
             return state.abci_query(&req);
@@ -356,12 +383,21 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
         let store_height = merk_store.borrow().height()?;
         let state = create_state(backing_store.clone())?;
 
-        // Check which keys are accessed by the query and build a proof
+        // Check which keys are accessed by the query (or by every query in the
+        // batch) and build a single proof
         let query_bytes = req.data;
-        let query_decode_res = Decode::decode(query_bytes.as_slice());
-        let query = query_decode_res?;
+        if is_batch {
+            let batch: BatchQuery<<ABCIPlugin<A> as Query>::Query> =
+                Decode::decode(query_bytes.as_slice())?;
+            for query in batch.0 {
+                state.query(query)?;
+            }
+        } else {
+            let query_decode_res = Decode::decode(query_bytes.as_slice());
+            let query = query_decode_res?;
 
-        state.query(query)?;
+            state.query(query)?;
+        }
 
         let proof_builder = backing_store.into_proof_builder()?;
         let root_hash = merk_store.borrow().merk().root_hash();
@@ -382,6 +418,32 @@ impl<A: App> Application for InternalApp<ABCIPlugin<A>> {
     }
 }
 
+/// Scales the fee per gas of transactions into an integer priority.
+const PRIORITY_PRECISION: u64 = 1_000_000;
+
+/// Returns the sender, priority and gas wanted of a checked transaction. The
+/// priority is the fee charged per unit of gas, where calls which do not
+/// specify gas (e.g. native calls) are weighed by their length in bytes.
+fn mempool_info(info: &CheckTxInfo, tx_len: u64) -> (String, i64, i64) {
+    let sender = info
+        .sender
+        .map(|sender| sender.to_string())
+        .unwrap_or_default();
+
+    let gas_wanted = info.gas_wanted.unwrap_or_default();
+    let weight = match gas_wanted {
+        0 => tx_len.max(1),
+        gas => gas,
+    };
+    let priority = u128::from(info.fee).saturating_mul(PRIORITY_PRECISION.into()) / weight as u128;
+
+    (
+        sender,
+        priority.min(i64::MAX as u128) as i64,
+        gas_wanted.min(i64::MAX as u64) as i64,
+    )
+}
+
 struct InternalApp<A> {
     _app: PhantomData<A>,
 }
@@ -390,4 +452,78 @@ impl<A: App> InternalApp<ABCIPlugin<A>> {
     pub fn new() -> Self {
         Self { _app: PhantomData }
     }
+
+    /// Runs a transaction through the full plugin stack against a throwaway
+    /// buffer over the latest state. Gas used is estimated from the size of
+    /// the transaction and of the state it writes.
+    fn simulate(&self, merk_store: Shared<MerkStore>, req: RequestQuery) -> Result<ResponseQuery> {
+        let request: SimulateRequest = Decode::decode(req.data.as_slice())?;
+        let tx_len = request.tx.len() as u64;
+        let store = Shared::new(BufStore::wrap(Shared::new(BufStore::wrap(merk_store))));
+
+        let run_res = self.run(store.clone(), move |state| -> Result<_> {
+            let inner_call = Decode::decode(request.tx.as_slice())?;
+            let _simulation = Context::scope(Simulation {
+                skip_signatures: request.skip_signatures,
+            });
+            state.call(ABCICall::DeliverTx(inner_call))?;
+
+            Ok(state.events.take().unwrap_or_default())
+        })?;
+
+        let written: usize = store
+            .into_inner()
+            .into_map()
+            .iter()
+            .map(|(key, value)| key.len() + value.as_ref().map_or(0, Vec::len))
+            .sum();
+
+        let mut sim_res = ResponseDeliverTx {
+            gas_used: (tx_len + written as u64) as i64,
+            ..Default::default()
+        };
+        match run_res {
+            Ok(events) => {
+                sim_res.events = events;
+                sim_res.log = "success".to_string();
+            }
+            Err(err) => {
+                sim_res.code = err.code();
+                sim_res.codespace = err.codespace().to_string();
+                sim_res.log = err.to_string();
+            }
+        }
+
+        Ok(ResponseQuery {
+            code: 0,
+            value: ::prost::Message::encode_to_vec(&sim_res),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coins::Address;
+
+    #[test]
+    fn mempool_priority() {
+        let sdk = CheckTxInfo {
+            sender: Some(Address::NULL),
+            fee: 10_000.into(),
+            gas_wanted: Some(100_000),
+        };
+        assert_eq!(
+            mempool_info(&sdk, 500),
+            (Address::NULL.to_string(), 100_000, 100_000)
+        );
+
+        let native = CheckTxInfo {
+            fee: 10_000.into(),
+            ..Default::default()
+        };
+        assert_eq!(mempool_info(&native, 200), (String::new(), 50_000_000, 0));
+        assert_eq!(mempool_info(&CheckTxInfo::default(), 200).1, 0);
+    }
 }
@@ -7,18 +7,23 @@ use tendermint_rpc as tm;
 use tm::endpoint::abci_query::AbciQuery;
 use tm::Client as _;
 
+use super::{SimulateRequest, BATCH_QUERY_PATH, SIMULATE_QUERY_PATH};
 use crate::call::Call;
 use crate::client::{AsyncCall, AsyncQuery, Client};
 use crate::encoding::Encode;
 use crate::merk::{BackingStore, ProofStore};
 use crate::plugins::ABCIPlugin;
-use crate::query::Query;
+use crate::query::{BatchQuery, Query};
 use crate::state::State;
 use crate::store::{Shared, Store};
 use crate::{Error, Result};
 
 pub use tm::endpoint::broadcast::tx_commit::Response as TxResponse;
 
+/// A check closure for one query in a batch, called with the state verified
+/// from the batch's combined proof.
+pub type BatchCheck<'a, T, R> = Box<dyn FnOnce(std::rc::Rc<T>) -> Result<R> + 'a>;
+
 pub struct TendermintClient<T: Client<TendermintAdapter<T>>> {
     state_client: T::Client,
     tm_client: tm::HttpClient,
@@ -33,6 +38,7 @@ where
             marker: std::marker::PhantomData,
             client: self.tm_client.clone(),
             res_store: None,
+            tx_res_store: None,
         });
 
         Self {
@@ -49,6 +55,7 @@ impl<T: Client<TendermintAdapter<T>>> TendermintClient<T> {
             marker: std::marker::PhantomData,
             client: tm_client.clone(),
             res_store: None,
+            tx_res_store: None,
         });
         Ok(TendermintClient {
             state_client,
@@ -69,6 +76,7 @@ impl<T: Client<TendermintAdapter<T>>> TendermintClient<T> {
             marker: std::marker::PhantomData,
             client: self.tm_client.clone(),
             res_store: Some(res_store.clone()),
+            tx_res_store: None,
         });
 
         let query_res = f(state_client).await?;
@@ -80,6 +88,103 @@ impl<T: Client<TendermintAdapter<T>>> TendermintClient<T> {
             .ok_or_else(|| Error::Query("No query preformed in closure".to_string()))?;
         Ok((query_res, response))
     }
+
+    /// Runs the given closure with a client which records the result of the
+    /// transaction it broadcasts, e.g. to decode the events it emitted with
+    /// [decode_events](crate::events::decode_events).
+    pub async fn with_tx_response<F, R, X: std::future::Future<Output = Result<R>>>(
+        &self,
+        f: F,
+    ) -> Result<(R, TxResponse)>
+    where
+        F: FnOnce(T::Client) -> X,
+    {
+        let tx_res_store = Arc::new(Mutex::new(Cell::new(None)));
+        let state_client = T::create_client(TendermintAdapter {
+            marker: std::marker::PhantomData,
+            client: self.tm_client.clone(),
+            res_store: None,
+            tx_res_store: Some(tx_res_store.clone()),
+        });
+
+        let call_res = f(state_client).await?;
+
+        let response = tx_res_store
+            .lock()
+            .map_err(|e| Error::Poison(e.to_string()))?
+            .take()
+            .ok_or_else(|| Error::Client("No transaction broadcast in closure".to_string()))?;
+        Ok((call_res, response))
+    }
+
+    /// Simulates the encoded transaction against the node's latest state
+    /// without broadcasting it, returning its result, gas used and events.
+    pub async fn simulate(
+        &self,
+        tx: Vec<u8>,
+        skip_signatures: bool,
+    ) -> Result<tendermint_proto::abci::ResponseDeliverTx> {
+        let path = SIMULATE_QUERY_PATH
+            .parse()
+            .map_err(|e| Error::Tendermint(format!("{}", e)))?;
+        let query_bytes = SimulateRequest {
+            skip_signatures,
+            tx,
+        }
+        .encode()?;
+        let res = self
+            .tm_client
+            .abci_query(Some(path), query_bytes, None, false)
+            .await?;
+
+        if let tendermint::abci::Code::Err(code) = res.code {
+            return Err(Error::from_code(
+                res.codespace.to_string().as_str(),
+                code,
+                res.log.to_string().as_str(),
+            ));
+        }
+
+        ::prost::Message::decode(res.value.as_slice())
+            .map_err(|e| Error::Tendermint(format!("Invalid simulation response: {}", e)))
+    }
+}
+
+impl<T: Client<TendermintAdapter<T>> + Query + State> TendermintClient<T> {
+    /// Resolves several queries in a single ABCI round-trip. The node runs all
+    /// of the queries against the same state and returns one proof, so every
+    /// check closure sees the same verified state at the same height.
+    pub async fn query_batch<R>(
+        &self,
+        queries: Vec<(T::Query, BatchCheck<'_, T, R>)>,
+    ) -> Result<Vec<R>> {
+        let adapter = TendermintAdapter {
+            marker: std::marker::PhantomData,
+            client: self.tm_client.clone(),
+            res_store: None,
+            tx_res_store: None,
+        };
+
+        adapter.query_batch(queries).await
+    }
+}
+
+impl<T: Client<TendermintAdapter<T>> + Call> TendermintClient<T>
+where
+    T::Call: Send,
+{
+    /// Broadcasts an already-built call for the full app type, e.g. a call
+    /// signed by a multisig with signatures collected offline.
+    pub async fn broadcast(&self, call: T::Call) -> Result<()> {
+        let adapter = TendermintAdapter {
+            marker: std::marker::PhantomData,
+            client: self.tm_client.clone(),
+            res_store: None,
+            tx_res_store: None,
+        };
+
+        adapter.call(call).await
+    }
 }
 
 impl<T: Client<TendermintAdapter<T>>> Deref for TendermintClient<T> {
@@ -109,8 +214,11 @@ impl<T: Client<TendermintAdapter<T>> + Query + State> TendermintClient<T> {
             .await?;
 
         if let tendermint::abci::Code::Err(code) = res.code {
-            let msg = format!("code {}: {}", code, res.log);
-            return Err(Error::Query(msg));
+            return Err(Error::from_code(
+                res.codespace.to_string().as_str(),
+                code,
+                res.log.to_string().as_str(),
+            ));
         }
 
         // TODO: we shouldn't need to include the root hash in the result, it
@@ -145,6 +253,7 @@ pub struct TendermintAdapter<T> {
     marker: std::marker::PhantomData<fn() -> T>,
     client: tm::HttpClient,
     res_store: Option<Arc<Mutex<Cell<Option<AbciQuery>>>>>,
+    tx_res_store: Option<Arc<Mutex<Cell<Option<TxResponse>>>>>,
 }
 
 impl<T> Clone for TendermintAdapter<T> {
@@ -153,6 +262,7 @@ impl<T> Clone for TendermintAdapter<T> {
             marker: self.marker,
             client: self.client.clone(),
             res_store: self.res_store.clone(),
+            tx_res_store: self.tx_res_store.clone(),
         }
     }
 }
@@ -168,39 +278,64 @@ where
         let tx = call.encode()?.into();
         let tx_res = self.client.broadcast_tx_commit(tx).await?;
 
-        if tx_res.check_tx.code.is_err() {
-            Err(Error::ABCI(format!(
-                "CheckTx failed: {}",
-                tx_res.check_tx.log
-            )))
-        } else if tx_res.deliver_tx.code.is_err() {
-            Err(Error::ABCI(format!(
-                "DeliverTx failed: {}",
-                tx_res.deliver_tx.log
-            )))
+        if let Some(tx_res_store) = &self.tx_res_store {
+            tx_res_store
+                .lock()
+                .map_err(|e| Error::Poison(e.to_string()))?
+                .replace(Some(tx_res.clone()));
+        }
+
+        if let tendermint::abci::Code::Err(code) = tx_res.check_tx.code {
+            Err(Error::from_code(
+                tx_res.check_tx.codespace.to_string().as_str(),
+                code,
+                tx_res.check_tx.log.to_string().as_str(),
+            ))
+        } else if let tendermint::abci::Code::Err(code) = tx_res.deliver_tx.code {
+            Err(Error::from_code(
+                tx_res.deliver_tx.codespace.to_string().as_str(),
+                code,
+                tx_res.deliver_tx.log.to_string().as_str(),
+            ))
         } else {
             Ok(())
         }
     }
 }
 
-#[async_trait::async_trait(?Send)]
-impl<T: Query + State> AsyncQuery for TendermintAdapter<T> {
-    type Query = T::Query;
-    type Response<'a> = std::rc::Rc<T>;
+impl<T: Query + State> TendermintAdapter<T> {
+    /// Resolves several queries in a single ABCI round-trip, passing the state
+    /// verified from the combined proof to each query's check closure.
+    pub async fn query_batch<R>(
+        &self,
+        queries: Vec<(T::Query, BatchCheck<'_, T, R>)>,
+    ) -> Result<Vec<R>> {
+        let (queries, checks): (Vec<_>, Vec<_>) = queries.into_iter().unzip();
+
+        let path = BATCH_QUERY_PATH
+            .parse()
+            .map_err(|e| Error::Tendermint(format!("{}", e)))?;
+        let query_bytes = BatchQuery(queries).encode()?;
+        let state = std::rc::Rc::new(self.query_state(Some(path), query_bytes).await?);
+
+        checks
+            .into_iter()
+            .map(|check| check(state.clone()))
+            .collect()
+    }
 
-    async fn query<F, R>(&self, query: Self::Query, mut check: F) -> Result<R>
-    where
-        F: FnMut(Self::Response<'_>) -> Result<R>,
-    {
+    async fn query_state(
+        &self,
+        path: Option<tendermint::abci::Path>,
+        query_bytes: Vec<u8>,
+    ) -> Result<T> {
         // TODO: attempt query against locally persisted store data for this
         // height, only issue query if we are missing data (belongs in a
         // different type)
 
-        let query_bytes = query.encode()?;
         let res = self
             .client
-            .abci_query(None, query_bytes, None, true)
+            .abci_query(path, query_bytes, None, true)
             .await?;
 
         if let Some(res_store) = &self.res_store {
@@ -211,8 +346,11 @@ impl<T: Query + State> AsyncQuery for TendermintAdapter<T> {
         }
 
         if let tendermint::abci::Code::Err(code) = res.code {
-            let msg = format!("code {}: {}", code, res.log);
-            return Err(Error::Query(msg));
+            return Err(Error::from_code(
+                res.codespace.to_string().as_str(),
+                code,
+                res.log.to_string().as_str(),
+            ));
         }
 
         // TODO: we shouldn't need to include the root hash in the result, it
@@ -239,7 +377,23 @@ impl<T: Query + State> AsyncQuery for TendermintAdapter<T> {
         let store = BackingStore::ProofMap(store);
         let state = <ABCIPlugin<T>>::load(Store::new(store), &mut root_value.as_slice())?;
 
+        Ok(state.inner)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<T: Query + State> AsyncQuery for TendermintAdapter<T> {
+    type Query = T::Query;
+    type Response<'a> = std::rc::Rc<T>;
+
+    async fn query<F, R>(&self, query: Self::Query, mut check: F) -> Result<R>
+    where
+        F: FnMut(Self::Response<'_>) -> Result<R>,
+    {
+        let query_bytes = query.encode()?;
+        let state = self.query_state(None, query_bytes).await?;
+
         // TODO: retry logic
-        check(std::rc::Rc::new(state.inner))
+        check(std::rc::Rc::new(state))
     }
 }
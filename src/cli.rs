@@ -0,0 +1,318 @@
+//! A generic command-line interface for orga apps.
+//!
+//! [Cli] uses the app's [Descriptor] to route `query` and `tx` commands, so a
+//! chain gets a working CLI without writing its own call and query plumbing:
+//!
+//! ```text
+//! mychain query staking.validators
+//! mychain tx accounts.transfer nomic1... 1000
+//! mychain keys show
+//! ```
+//!
+//! Query and call paths are dot-separated names of fields, with the last
+//! segment of a call path naming a `#[call]` method. Path segments which are
+//! not fields are passed as the key to the node's `get` query method, if it
+//! has one.
+
+use crate::abci::tendermint_client::{TendermintAdapter, TendermintClient};
+use crate::client::{AsyncCall, AsyncQuery, Client};
+use crate::coins::Address;
+use crate::describe::{Children, Describe, Descriptor, Value, VariantDescriptor, VariantKind};
+use crate::encoding::{Decode, Encode};
+use crate::plugins::privkey_path;
+use crate::state::State;
+use crate::{Error, Result};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+const USAGE: &str = "Usage:
+    query [path]                  Print the state at the given path
+    tx <path.method> [args...]    Sign and broadcast a call
+    keys show                     Print the address of the local key
+    keys new [--force]            Generate a new local key
+    keys import <hex> [--force]   Import a hex-encoded private key
+    keys export                   Print the hex-encoded private key";
+
+/// Runs commands against a node using the descriptor of the app type `A`,
+/// where `T` is the full app type including plugins.
+pub struct Cli<T: Client<TendermintAdapter<T>>, A> {
+    client: TendermintClient<T>,
+    desc: Descriptor,
+    marker: PhantomData<fn() -> A>,
+}
+
+impl<T, A> Cli<T, A>
+where
+    T: Client<TendermintAdapter<T>>,
+    T::Client: AsyncCall + for<'a> AsyncQuery<Response<'a> = Rc<A>>,
+    <T::Client as AsyncCall>::Call: Decode,
+    <T::Client as AsyncQuery>::Query: Decode,
+    A: Encode + State + Describe + 'static,
+{
+    pub fn new(addr: &str) -> Result<Self> {
+        Ok(Cli {
+            client: TendermintClient::new(addr)?,
+            desc: A::describe(),
+            marker: PhantomData,
+        })
+    }
+
+    /// Runs the command given by the process arguments.
+    pub async fn run_from_env(&self) -> Result<()> {
+        let args: Vec<_> = std::env::args().skip(1).collect();
+        self.run(args.as_slice()).await
+    }
+
+    /// Runs a command, given its arguments without the program name.
+    pub async fn run(&self, args: &[String]) -> Result<()> {
+        let args: Vec<_> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["query"] => self.print_query("").await,
+            ["query", path] => self.print_query(path).await,
+            ["tx", path, call_args @ ..] => {
+                self.tx(path, call_args).await?;
+                println!("Transaction committed");
+                Ok(())
+            }
+            ["keys", key_args @ ..] => keys(key_args),
+            _ => Err(Error::App(USAGE.to_string())),
+        }
+    }
+
+    async fn print_query(&self, path: &str) -> Result<()> {
+        let json = self.query(path).await?;
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        Ok(())
+    }
+
+    /// Queries the state at the given path, returning it as JSON. Values which
+    /// can not be serialized are returned as their `Debug` representation.
+    pub async fn query(&self, path: &str) -> Result<serde_json::Value> {
+        let segments = split_path(path);
+        let query_bytes = query_bytes(&self.desc, segments.as_slice())?;
+        let query = Decode::decode(query_bytes.as_slice())?;
+
+        // the app client passes raw queries through the plugins' adapters, which
+        // unwrap the plugin state down to the app
+        (*self.client)
+            .query(query, |state| {
+                let state = Rc::try_unwrap(state)
+                    .map_err(|_| Error::Client("Query response is still borrowed".to_string()))?;
+                let mut value = Value::new(state);
+                for segment in segments.iter() {
+                    value = value
+                        .child(segment)?
+                        .ok_or_else(|| Error::App(format!("No value found at '{}'", segment)))?;
+                }
+
+                if let Some(json) = value.maybe_to_json()? {
+                    return Ok(json);
+                }
+                value
+                    .maybe_debug(true)
+                    .or_else(|| value.maybe_to_string())
+                    .map(serde_json::Value::String)
+                    .ok_or_else(|| Error::App(format!("Could not display '{}'", value.type_name())))
+            })
+            .await
+    }
+
+    /// Signs and broadcasts a call to the method at the given path, parsing
+    /// each argument from a string.
+    pub async fn tx(&self, path: &str, args: &[&str]) -> Result<()> {
+        let segments = split_path(path);
+        let call_bytes = call_bytes(&self.desc, segments.as_slice(), args)?;
+        let call = Decode::decode(call_bytes.as_slice())?;
+
+        self.client.call(call).await
+    }
+}
+
+fn keys(args: &[&str]) -> Result<()> {
+    let path = privkey_path()?;
+    let force = args.contains(&"--force");
+    let args: Vec<_> = args.iter().filter(|arg| **arg != "--force").collect();
+
+    let write_key = |privkey: SecretKey| -> Result<()> {
+        if path.exists() && !force {
+            return Err(Error::App(format!(
+                "Key already exists at {}, use --force to overwrite it",
+                path.display()
+            )));
+        }
+        std::fs::write(&path, privkey.secret_bytes())?;
+        println!("{}", address(&privkey));
+        Ok(())
+    };
+    let read_key = || -> Result<SecretKey> {
+        if !path.exists() {
+            return Err(Error::App(
+                "No key found, create one with `keys new`".to_string(),
+            ));
+        }
+        Ok(SecretKey::from_slice(std::fs::read(&path)?.as_slice())?)
+    };
+
+    match args.as_slice() {
+        ["show"] => println!("{}", address(&read_key()?)),
+        ["export"] => println!("{}", hex::encode(read_key()?.secret_bytes())),
+        ["new"] => {
+            let mut rng = secp256k1::rand::thread_rng();
+            write_key(SecretKey::new(&mut rng))?;
+        }
+        ["import", privkey] => {
+            let bytes = hex::decode(privkey)
+                .map_err(|e| Error::App(format!("Invalid private key: {}", e)))?;
+            write_key(SecretKey::from_slice(bytes.as_slice())?)?;
+        }
+        _ => return Err(Error::App(USAGE.to_string())),
+    }
+
+    Ok(())
+}
+
+fn address(privkey: &SecretKey) -> Address {
+    let secp = Secp256k1::signing_only();
+    let pubkey = PublicKey::from_secret_key(&secp, privkey);
+    Address::from_pubkey(pubkey.serialize())
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('.').filter(|s| !s.is_empty()).collect()
+}
+
+/// Builds the encoded query which routes to the value at the given path,
+/// ending with the `This` variant of the target's query.
+fn query_bytes(desc: &Descriptor, segments: &[&str]) -> Result<Vec<u8>> {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None if matches!(desc.children(), Children::None) && desc.queries().is_empty() => {
+            return Ok(vec![]);
+        }
+        None => return Ok(vec![0]),
+    };
+
+    let mut bytes = vec![];
+    let child = if let Some(variant) = find_variant(desc.queries(), segment, true) {
+        bytes.push(variant.index);
+        named_child(desc, segment)?
+    } else if let Some(variant) = find_variant(desc.queries(), "get", false) {
+        let arg = match &variant.kind {
+            VariantKind::Method(args) if args.len() == 1 => &args[0],
+            _ => return Err(Error::App("Invalid get query".to_string())),
+        };
+        bytes.push(variant.index);
+        bytes.extend(arg.parse(segment)?);
+        match desc.children() {
+            Children::Dynamic(child) => child.value_desc(),
+            _ => return Err(Error::App(format!("No child called '{}'", segment))),
+        }
+    } else {
+        return Err(Error::App(format!(
+            "No queryable child called '{}' in {}",
+            segment, desc.type_name
+        )));
+    };
+
+    bytes.extend(query_bytes(child, rest)?);
+    Ok(bytes)
+}
+
+/// Builds the encoded call which routes to the method at the given path.
+fn call_bytes(desc: &Descriptor, segments: &[&str], args: &[&str]) -> Result<Vec<u8>> {
+    let (segment, rest) = segments
+        .split_first()
+        .ok_or_else(|| Error::App("Missing method name".to_string()))?;
+
+    if !rest.is_empty() {
+        let variant = find_variant(desc.calls(), segment, true).ok_or_else(|| {
+            Error::App(format!(
+                "No callable child called '{}' in {}",
+                segment, desc.type_name
+            ))
+        })?;
+        let mut bytes = vec![variant.index];
+        bytes.extend(call_bytes(named_child(desc, segment)?, rest, args)?);
+        return Ok(bytes);
+    }
+
+    let variant = find_variant(desc.calls(), segment, false).ok_or_else(|| {
+        Error::App(format!(
+            "No call method called '{}' in {}",
+            segment, desc.type_name
+        ))
+    })?;
+    let arg_descs = match &variant.kind {
+        VariantKind::Method(arg_descs) => arg_descs,
+        VariantKind::Field => unreachable!(),
+    };
+    if arg_descs.len() != args.len() {
+        let names: Vec<_> = arg_descs.iter().map(|arg| arg.name.as_str()).collect();
+        return Err(Error::App(format!(
+            "Expected {} arguments ({}), got {}",
+            arg_descs.len(),
+            names.join(", "),
+            args.len()
+        )));
+    }
+
+    let mut bytes = vec![variant.index];
+    for (arg_desc, arg) in arg_descs.iter().zip(args) {
+        bytes.extend(arg_desc.parse(arg)?);
+    }
+    Ok(bytes)
+}
+
+fn find_variant<'a>(
+    variants: &'a [VariantDescriptor],
+    name: &str,
+    field: bool,
+) -> Option<&'a VariantDescriptor> {
+    variants
+        .iter()
+        .find(|variant| variant.name == name && matches!(variant.kind, VariantKind::Field) == field)
+}
+
+fn named_child<'a>(desc: &'a Descriptor, name: &str) -> Result<&'a Descriptor> {
+    match desc.children() {
+        Children::Named(children) => children
+            .iter()
+            .find(|child| child.name == name)
+            .map(|child| &child.desc)
+            .ok_or_else(|| Error::App(format!("No child called '{}'", name))),
+        _ => Err(Error::App(format!("No child called '{}'", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::describe::Builder;
+
+    fn desc() -> Descriptor {
+        Builder::new::<u64>()
+            .named_child::<u32>("counter", &[0], |_| Ok(None))
+            .call_field("counter", 1)
+            .call_method("increment", 2, &[Builder::arg::<u32>("by")])
+            .query_field("counter", 1)
+            .build()
+    }
+
+    #[test]
+    fn build_call() {
+        let bytes = call_bytes(&desc(), &["increment"], &["5"]).unwrap();
+        assert_eq!(bytes, vec![2, 0, 0, 0, 5]);
+
+        assert!(call_bytes(&desc(), &["increment"], &[]).is_err());
+        assert!(call_bytes(&desc(), &["increment"], &["x"]).is_err());
+        assert!(call_bytes(&desc(), &["counter", "increment"], &["5"]).is_err());
+    }
+
+    #[test]
+    fn build_query() {
+        assert_eq!(query_bytes(&desc(), &[]).unwrap(), vec![0]);
+        assert_eq!(query_bytes(&desc(), &["counter"]).unwrap(), vec![1]);
+        assert!(query_bytes(&desc(), &["missing"]).is_err());
+    }
+}
@@ -105,6 +105,53 @@ pub trait AsyncQuery {
         F: FnMut(Self::Response<'_>) -> Result<R>;
 }
 
+/// Implements [AsyncCall] and [AsyncQuery] for a client type by passing raw
+/// calls and queries through to one of its fields, e.g. for callers which
+/// build encoded calls at runtime.
+///
+/// ```ignore
+/// pass_through_client! {
+///     impl[T: Client<MyAdapter<T, U>>, U: Clone] MyClient<T, U> => inner: T::Client
+/// }
+/// ```
+#[macro_export]
+macro_rules! pass_through_client {
+    (
+        impl[$($generics:tt)*] $ty:ty => $field:ident: $field_ty:ty
+        $(where [$($preds:tt)*])?
+    ) => {
+        #[$crate::async_trait(?Send)]
+        impl<$($generics)*> $crate::client::AsyncCall for $ty
+        where
+            $field_ty: $crate::client::AsyncCall,
+            $($($preds)*)?
+        {
+            type Call = <$field_ty as $crate::client::AsyncCall>::Call;
+
+            async fn call(&self, call: Self::Call) -> $crate::Result<()> {
+                self.$field.call(call).await
+            }
+        }
+
+        #[$crate::async_trait(?Send)]
+        impl<$($generics)*> $crate::client::AsyncQuery for $ty
+        where
+            $field_ty: $crate::client::AsyncQuery,
+            $($($preds)*)?
+        {
+            type Query = <$field_ty as $crate::client::AsyncQuery>::Query;
+            type Response<'a> = <$field_ty as $crate::client::AsyncQuery>::Response<'a>;
+
+            async fn query<F, R>(&self, query: Self::Query, check: F) -> $crate::Result<R>
+            where
+                F: FnMut(Self::Response<'_>) -> $crate::Result<R>,
+            {
+                self.$field.query(query, check).await
+            }
+        }
+    };
+}
+
 // TODO: support deriving for types inside module in macros, then move this into
 // // tests module
 // #[derive(Debug, Call, Client, Query)]
@@ -1,9 +1,13 @@
-use crate::coins::{Address, Amount, Coin, Give, Symbol, Take};
+use crate::coins::{
+    Address, Allowance, Amount, Coin, Give, Schedule, Symbol, Take, VestingAccount, VestingInfo,
+};
 use crate::collections::Map;
-use crate::context::GetContext;
+use crate::context::{Context, GetContext};
+use crate::encoding::LengthVec;
+use crate::events::{emit, Event};
 use crate::orga;
-use crate::plugins::Paid;
-use crate::plugins::Signer;
+use crate::plugins::{min_fee, Paid};
+use crate::plugins::{Signer, Time};
 use crate::{Error, Result};
 
 #[orga]
@@ -11,6 +15,19 @@ pub struct Accounts<S: Symbol> {
     transfers_allowed: bool,
     transfer_exceptions: Map<Address, ()>,
     accounts: Map<Address, Coin<S>>,
+    vesting: Map<Address, VestingAccount>,
+    // appended maps write no bytes to the encoding, so existing state loads
+    // unchanged
+    fee_allowances: Map<Address, Map<Address, Allowance>>,
+}
+
+/// Emitted when coins are transferred between accounts.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "transfer")]
+pub struct TransferEvent {
+    pub from: Address,
+    pub to: Address,
+    pub amount: Amount,
 }
 
 impl<S: Symbol> Accounts<S> {
@@ -24,7 +41,11 @@ impl<S: Symbol> Accounts<S> {
         let mut receiver = self.accounts.entry(to)?.or_insert_default()?;
         receiver.give(taken_coins)?;
 
-        Ok(())
+        emit(TransferEvent {
+            from: signer,
+            to,
+            amount,
+        })
     }
 
     #[call]
@@ -40,6 +61,7 @@ impl<S: Symbol> Accounts<S> {
 
     fn take_own_coins(&mut self, amount: Amount) -> Result<Coin<S>> {
         let signer = self.signer()?;
+        self.check_spendable(signer, amount)?;
 
         let taken_coins = self
             .accounts
@@ -50,6 +72,14 @@ impl<S: Symbol> Accounts<S> {
         Ok(taken_coins)
     }
 
+    fn check_spendable(&mut self, address: Address, amount: Amount) -> Result<()> {
+        if self.vesting.contains_key(address)? && amount > self.spendable(address)? {
+            return Err(Error::Coins("Insufficient unlocked funds".into()));
+        }
+
+        Ok(())
+    }
+
     fn signer(&mut self) -> Result<Address> {
         self.context::<Signer>()
             .ok_or_else(|| Error::Signer("No Signer context available".into()))?
@@ -97,6 +127,31 @@ impl<S: Symbol> Accounts<S> {
         }
     }
 
+    /// The part of the account's balance which is not locked by a vesting
+    /// schedule.
+    #[query]
+    pub fn spendable(&self, address: Address) -> Result<Amount> {
+        let balance = self.balance(address)?;
+        let locked = match self.vesting.get(address)? {
+            Some(vesting) => vesting.locked(now()?)?,
+            None => return Ok(balance),
+        };
+
+        if balance > locked {
+            (balance - locked).result()
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    #[query]
+    pub fn vesting(&self, address: Address) -> Result<Option<VestingInfo>> {
+        match self.vesting.get(address)? {
+            Some(vesting) => Ok(Some(vesting.info(now()?)?)),
+            None => Ok(None),
+        }
+    }
+
     #[query]
     pub fn exists(&self, address: Address) -> Result<bool> {
         Ok(self.accounts.get(address)?.is_some())
@@ -118,7 +173,153 @@ impl<S: Symbol> Accounts<S> {
     }
 
     pub fn withdraw(&mut self, address: Address, amount: Amount) -> Result<Coin<S>> {
+        self.check_spendable(address, amount)?;
         let mut account = self.accounts.entry(address)?.or_insert_default()?;
         account.take(amount)
     }
+
+    /// Deposits coins into a new vesting account, which unlock according to
+    /// the schedule.
+    pub fn add_vesting(
+        &mut self,
+        address: Address,
+        coins: Coin<S>,
+        schedule: Schedule,
+    ) -> Result<()> {
+        if self.vesting.contains_key(address)? {
+            return Err(Error::Coins("Account is already vesting".into()));
+        }
+        let vesting = VestingAccount::new(coins.amount, schedule)?;
+        self.vesting.insert(address, vesting)?;
+
+        self.deposit(address, coins)
+    }
+
+    /// Takes coins from an account to be delegated, allowing locked coins to
+    /// be taken. See [delegate_vesting](crate::coins::delegate_vesting).
+    pub fn take_for_delegation(&mut self, address: Address, amount: Amount) -> Result<Coin<S>> {
+        let coins = self
+            .accounts
+            .get_mut(address)?
+            .ok_or_else(|| Error::Coins("Insufficient funds".into()))?
+            .take(amount)?;
+        if let Some(mut vesting) = self.vesting.get_mut(address)? {
+            vesting.track_delegation(now()?, amount)?;
+        }
+
+        Ok(coins)
+    }
+
+    /// Records that coins delegated from an account are being returned to it.
+    /// See [withdraw_vesting](crate::coins::withdraw_vesting).
+    pub fn track_undelegation(&mut self, address: Address, amount: Amount) -> Result<()> {
+        if let Some(mut vesting) = self.vesting.get_mut(address)? {
+            vesting.track_undelegation(amount)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Fee allowances are kept with the accounts so that fees paid with them can be
+// taken from the granter's balance as they are charged.
+impl<S: Symbol> Accounts<S> {
+    /// Lets the grantee have the fees of its calls paid from the signer's
+    /// account (see [Allowance]), replacing any existing allowance from the
+    /// signer.
+    #[call]
+    pub fn grant_fee_allowance(
+        &mut self,
+        grantee: Address,
+        spend_limit: Option<Amount>,
+        expiration: Option<i64>,
+        allowed_calls: LengthVec<u8, LengthVec<u8, u8>>,
+    ) -> Result<()> {
+        let granter = self.signer()?;
+        let allowance = Allowance::new(spend_limit, expiration, allowed_calls)?;
+        self.set_fee_allowance(granter, grantee, allowance)
+    }
+
+    /// Sets an allowance from the given granter, e.g. for a chain to sponsor
+    /// the first calls of new accounts from an account it controls.
+    pub fn set_fee_allowance(
+        &mut self,
+        granter: Address,
+        grantee: Address,
+        allowance: Allowance,
+    ) -> Result<()> {
+        self.fee_allowances
+            .entry(granter)?
+            .or_insert_default()?
+            .insert(grantee, allowance)
+    }
+
+    #[call]
+    pub fn revoke_fee_allowance(&mut self, grantee: Address) -> Result<()> {
+        let granter = self.signer()?;
+        let mut allowances = self
+            .fee_allowances
+            .get_mut(granter)?
+            .ok_or_else(|| Error::Coins("No fee allowance found".into()))?;
+        allowances
+            .get_mut(grantee)?
+            .ok_or_else(|| Error::Coins("No fee allowance found".into()))?
+            .remove()
+    }
+
+    /// Pays the fee of the signer's paid call from the granter's account,
+    /// using the granter's allowance for the signer. This must be used as the
+    /// payer call, and the funding it provides can only be spent on the fee.
+    #[call]
+    pub fn pay_fee_with_grant(&mut self, granter: Address) -> Result<()> {
+        let grantee = self.signer()?;
+        let paid = self
+            .context::<Paid>()
+            .ok_or_else(|| Error::Coins("No Paid context found".into()))?;
+        if !paid.running_payer {
+            return Err(Error::Coins(
+                "Fee allowances can only be used by payer calls".into(),
+            ));
+        }
+        let call_path = paid.paid_call_path.clone();
+        let now = self.context::<Time>().map(|time| time.seconds);
+        let fee = min_fee().into();
+
+        let mut allowances = self
+            .fee_allowances
+            .get_mut(granter)?
+            .ok_or_else(|| Error::Coins("No fee allowance found".into()))?;
+        allowances
+            .get_mut(grantee)?
+            .ok_or_else(|| Error::Coins("No fee allowance found".into()))?
+            .spend(fee, now, call_path.as_deref())?;
+        drop(allowances);
+
+        self.check_spendable(granter, fee)?;
+        let fee = self
+            .accounts
+            .get_mut(granter)?
+            .ok_or_else(|| Error::Coins("Insufficient funds".into()))?
+            .take(fee)?;
+
+        self.context::<Paid>()
+            .ok_or_else(|| Error::Coins("No Paid context found".into()))?
+            .give_fee::<S, _>(fee.amount)
+    }
+
+    #[query]
+    pub fn fee_allowance(&self, granter: Address, grantee: Address) -> Result<Option<Allowance>> {
+        let allowance = match self.fee_allowances.get(granter)? {
+            Some(allowances) => allowances.get(grantee)?.map(|allowance| allowance.clone()),
+            None => None,
+        };
+
+        Ok(allowance)
+    }
+}
+
+fn now() -> Result<i64> {
+    Context::resolve::<Time>()
+        .map(|time| time.seconds)
+        .ok_or_else(|| Error::Coins("No Time context available".into()))
 }
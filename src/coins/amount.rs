@@ -1,11 +1,73 @@
+use crate::call::Call;
+use crate::client::Client;
+use crate::compat_mode;
+use crate::describe::{Builder, Describe};
+use crate::encoding::{Decode, Encode, Terminated};
+use crate::migrate::migrate_from_self_impl;
+use crate::query::Query;
+use crate::state::state_impl;
 use crate::{Error, Result};
-use orga::orga;
 use std::convert::TryFrom;
+use std::str::FromStr;
 
-#[orga]
-#[derive(Debug, Clone, Copy)]
+/// The encoding version which widened amounts to 128 bits. Version 0 is the
+/// 64-bit encoding, which is still decoded and is written in compat mode.
+const VERSION: u8 = 1;
+
+#[derive(Call, Query, Client, Default, Debug, Clone, Copy)]
 pub struct Amount {
-    pub(crate) value: u64,
+    pub(crate) value: u128,
+}
+
+impl Encode for Amount {
+    fn encode_into<W: std::io::Write>(&self, dest: &mut W) -> ed::Result<()> {
+        if compat_mode() {
+            let value = u64::try_from(self.value).map_err(|_| {
+                ed::Error::IOError(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Amount does not fit in the 64-bit compat encoding",
+                ))
+            })?;
+            return value.encode_into(dest);
+        }
+
+        dest.write_all(&[VERSION])?;
+        self.value.encode_into(dest)
+    }
+
+    fn encoding_length(&self) -> ed::Result<usize> {
+        if compat_mode() {
+            Ok(8)
+        } else {
+            Ok(17)
+        }
+    }
+}
+
+impl Decode for Amount {
+    fn decode<R: std::io::Read>(mut input: R) -> ed::Result<Self> {
+        if compat_mode() {
+            return Ok(u64::decode(input)?.into());
+        }
+
+        let value = match u8::decode(&mut input)? {
+            0 => u64::decode(input)? as u128,
+            VERSION => u128::decode(input)?,
+            version => return Err(ed::Error::UnexpectedByte(version)),
+        };
+
+        Ok(Amount { value })
+    }
+}
+
+impl Terminated for Amount {}
+state_impl!(Amount);
+migrate_from_self_impl!(Amount);
+
+impl Describe for Amount {
+    fn describe() -> crate::describe::Descriptor {
+        Builder::new::<Self>().build()
+    }
 }
 
 impl std::fmt::Display for Amount {
@@ -14,6 +76,14 @@ impl std::fmt::Display for Amount {
     }
 }
 
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Amount::new(s.parse()?))
+    }
+}
+
 impl Ord for Amount {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other).unwrap()
@@ -23,23 +93,35 @@ impl Ord for Amount {
 impl Eq for Amount {}
 
 impl Amount {
-    pub fn new(value: u64) -> Self {
+    pub fn new(value: u128) -> Self {
         Amount { value }
     }
+
+    pub fn value(&self) -> u128 {
+        self.value
+    }
 }
 
 impl From<u64> for Amount {
     fn from(value: u64) -> Self {
-        Amount::new(value)
+        Amount::new(value.into())
     }
 }
 
-impl From<Amount> for u64 {
+impl From<Amount> for u128 {
     fn from(amount: Amount) -> Self {
         amount.value
     }
 }
 
+impl TryFrom<Amount> for u64 {
+    type Error = Error;
+
+    fn try_from(amount: Amount) -> Result<Self> {
+        u64::try_from(amount.value).map_err(|_| Error::Overflow)
+    }
+}
+
 impl TryFrom<Result<Amount>> for Amount {
     type Error = Error;
 
@@ -47,3 +129,39 @@ impl TryFrom<Result<Amount>> for Amount {
         value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() -> Result<()> {
+        let amount = Amount::new(u64::MAX as u128 + 1);
+        let bytes = amount.encode()?;
+        assert_eq!(bytes.len(), amount.encoding_length()?);
+        assert_eq!(bytes[0], VERSION);
+        assert_eq!(Amount::decode(bytes.as_slice())?, amount);
+
+        // amounts encoded before they were widened to 128 bits
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&123u64.to_be_bytes());
+        assert_eq!(Amount::decode(bytes.as_slice())?, 123);
+
+        assert!(Amount::decode([2, 0].as_slice()).is_err());
+        assert!(u64::try_from(amount).is_err());
+
+        crate::set_compat_mode(true);
+        let err = amount.encode().unwrap_err();
+        assert_eq!(Amount::new(123).encode()?, 123u64.encode()?);
+        crate::set_compat_mode(false);
+        assert!(
+            matches!(err, ed::Error::IOError(err) if err.to_string().contains("64-bit compat"))
+        );
+        assert_eq!(
+            "340282366920938463463374607431768211455".parse::<Amount>()?,
+            Amount::new(u128::MAX)
+        );
+
+        Ok(())
+    }
+}
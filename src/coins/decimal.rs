@@ -1,60 +1,112 @@
+use super::fixed::Fixed;
 use super::Amount;
-use crate::encoding::Adapter;
-use crate::encoding::{Decode, Encode};
-use crate::orga;
+use crate::call::Call;
+use crate::client::Client;
+use crate::compat_mode;
+use crate::describe::{Builder, Describe};
+use crate::encoding::{Decode, Encode, Terminated};
+use crate::migrate::migrate_from_self_impl;
+use crate::query::Query;
+use crate::state::state_impl;
 use crate::{Error, Result};
-use rust_decimal::{prelude::ToPrimitive, Decimal as NumDecimal};
+use rust_decimal::Decimal as NumDecimal;
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
-#[orga(simple)]
-#[derive(Copy, Debug)]
+/// The encoding version which widened decimals to 256-bit fixed-point numbers.
+/// Version 0 is the 96-bit `rust_decimal` encoding, which is still decoded
+/// and is written in compat mode.
+const VERSION: u8 = 1;
+
+/// A signed decimal number with 28 decimal places, wide enough to hold any
+/// [Amount].
+#[derive(Call, Query, Client, Default, Debug, Clone, Copy)]
 pub struct Decimal {
-    pub(crate) value: NumDecimal,
+    pub(crate) value: Fixed,
 }
 
-// impl Describe for Decimal {
-//     fn describe() -> crate::describe::Descriptor {
-//         Builder::new::<Self>().build()
-//     }
-// }
+impl Describe for Decimal {
+    fn describe() -> crate::describe::Descriptor {
+        Builder::new::<Self>().build()
+    }
+}
 
 impl std::fmt::Display for Decimal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.value.fmt(f)
+        std::fmt::Display::fmt(&self.value, f)
     }
 }
 
-impl Encode for Adapter<Decimal> {
+fn encode_legacy(value: &Fixed) -> ed::Result<[u8; 16]> {
+    value
+        .to_num_decimal()
+        .map(|num| num.serialize())
+        .ok_or_else(|| {
+            ed::Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Decimal does not fit in the 96-bit compat encoding",
+            ))
+        })
+}
+
+fn decode_legacy<R: std::io::Read>(mut input: R) -> ed::Result<Fixed> {
+    let mut bytes = [0u8; 16];
+    input.read_exact(&mut bytes)?;
+
+    Ok(NumDecimal::deserialize(bytes).into())
+}
+
+impl Encode for Decimal {
     fn encode_into<W: std::io::Write>(&self, dest: &mut W) -> ed::Result<()> {
-        dest.write_all(&self.0.value.serialize())?;
+        if compat_mode() {
+            dest.write_all(&encode_legacy(&self.value)?)?;
+            return Ok(());
+        }
+
+        dest.write_all(&[VERSION])?;
+        dest.write_all(&self.value.to_bytes())?;
 
         Ok(())
     }
 
     fn encoding_length(&self) -> ed::Result<usize> {
-        Ok(16)
+        if compat_mode() {
+            Ok(16)
+        } else {
+            Ok(34)
+        }
     }
 }
 
-impl Decode for Adapter<Decimal> {
-    fn decode<R: std::io::Read>(mut source: R) -> ed::Result<Self> {
-        let mut bytes = [0u8; 16];
-        source.read_exact(&mut bytes)?;
-        Ok(Decimal {
-            value: NumDecimal::deserialize(bytes),
+impl Decode for Decimal {
+    fn decode<R: std::io::Read>(mut input: R) -> ed::Result<Self> {
+        if compat_mode() {
+            return Ok(decode_legacy(input)?.into());
         }
-        .into())
+
+        let value = match u8::decode(&mut input)? {
+            0 => decode_legacy(input)?,
+            VERSION => {
+                let mut bytes = [0u8; 33];
+                input.read_exact(&mut bytes)?;
+                Fixed::from_bytes(bytes).ok_or(ed::Error::UnexpectedByte(bytes[0]))?
+            }
+            version => return Err(ed::Error::UnexpectedByte(version)),
+        };
+
+        Ok(Decimal { value })
     }
 }
 
-impl ed::Terminated for Adapter<Decimal> {}
+impl Terminated for Decimal {}
+state_impl!(Decimal);
+migrate_from_self_impl!(Decimal);
 
 impl From<u64> for Decimal {
     fn from(value: u64) -> Self {
         Decimal {
-            value: value.into(),
+            value: Fixed::from_u128(value.into()),
         }
     }
 }
@@ -72,10 +124,10 @@ impl Decimal {
         if self.value.is_sign_negative() {
             Err(Error::Coins("Amounts may not be negative".into()))
         } else {
-            match self.value.round().to_u64() {
-                Some(value) => Ok(value.into()),
+            match self.value.round().to_u128() {
+                Some(value) => Ok(Amount::new(value)),
                 None => Err(Error::Coins(
-                    "Amounts may not be greater than u64::MAX".into(),
+                    "Amounts may not be greater than u128::MAX".into(),
                 )),
             }
         }
@@ -88,15 +140,11 @@ impl Decimal {
     }
 
     pub fn zero() -> Self {
-        Decimal {
-            value: NumDecimal::ZERO,
-        }
+        Decimal { value: Fixed::ZERO }
     }
 
     pub fn one() -> Self {
-        Decimal {
-            value: NumDecimal::ONE,
-        }
+        Decimal { value: Fixed::ONE }
     }
 }
 
@@ -108,16 +156,24 @@ impl TryFrom<Result<Decimal>> for Decimal {
     }
 }
 
+impl From<Fixed> for Decimal {
+    fn from(value: Fixed) -> Self {
+        Decimal { value }
+    }
+}
+
 impl From<NumDecimal> for Decimal {
     fn from(value: NumDecimal) -> Self {
-        Decimal { value }
+        Decimal {
+            value: value.into(),
+        }
     }
 }
 
 impl From<Amount> for Decimal {
     fn from(amount: Amount) -> Self {
-        Self {
-            value: amount.value.into(),
+        Decimal {
+            value: Fixed::from_u128(amount.value),
         }
     }
 }
@@ -126,9 +182,9 @@ impl FromStr for Decimal {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        Ok(Self {
-            value: NumDecimal::from_str(s)?,
-        })
+        Fixed::parse(s)
+            .map(Into::into)
+            .ok_or_else(|| Error::Coins(format!("Invalid decimal {}", s)))
     }
 }
 
@@ -141,4 +197,37 @@ mod tests {
         let formatted: Decimal = rust_decimal_macros::dec!(1.23).into();
         assert_eq!(format!("{}", formatted), "1.23");
     }
+
+    #[test]
+    fn encoding() -> Result<()> {
+        let decimal = Decimal::from(Amount::new(u128::MAX));
+        let bytes = decimal.encode()?;
+        assert_eq!(bytes.len(), decimal.encoding_length()?);
+        assert_eq!(bytes[0], VERSION);
+        assert_eq!(Decimal::decode(bytes.as_slice())?, decimal);
+        assert_eq!(decimal.amount()?, Amount::new(u128::MAX));
+
+        // decimals encoded before they were widened
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(&rust_decimal_macros::dec!(-1.5).serialize());
+        assert_eq!(
+            Decimal::decode(bytes.as_slice())?,
+            "-1.5".parse::<Decimal>()?
+        );
+
+        assert!(Decimal::decode([2, 0].as_slice()).is_err());
+
+        crate::set_compat_mode(true);
+        let err = decimal.encode().unwrap_err();
+        let half: Decimal = "0.5".parse()?;
+        let legacy = half.encode()?;
+        crate::set_compat_mode(false);
+        assert!(
+            matches!(err, ed::Error::IOError(err) if err.to_string().contains("96-bit compat"))
+        );
+        assert_eq!(legacy, rust_decimal_macros::dec!(0.5).serialize());
+        assert!("1.2.3".parse::<Decimal>().is_err());
+
+        Ok(())
+    }
 }
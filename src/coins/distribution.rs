@@ -0,0 +1,273 @@
+use super::{Address, Amount, Coin, Decimal, Give, Staking, Symbol, Take};
+#[cfg(feature = "abci")]
+use crate::context::GetContext;
+use crate::describe::Describe;
+use crate::orga;
+use crate::params::{decode_param, ParamStore, Params};
+#[cfg(feature = "abci")]
+use crate::plugins::{BeginBlockCtx, CollectedFees};
+use crate::{Error, Result};
+use std::marker::PhantomData;
+
+/// Splits the fees and rewards of each block between the block proposer, a
+/// community pool and the stakers, the same way as cosmos-sdk's distribution
+/// module.
+///
+/// The fees collected by [FeePlugin](crate::plugins::FeePlugin) in a block,
+/// and any coins given to the module (e.g. coins issued by
+/// [Mint](super::Mint)), are paid out when the app calls
+/// [Distribution::begin_block_step] at the start of the next block:
+/// - the proposer of the previous block receives `base_proposer_reward`, plus
+///   `bonus_proposer_reward` weighted by the fraction of voting power which
+///   precommitted the block,
+/// - `community_tax` of the remainder goes to the community pool,
+/// - the rest is given to [Staking] as rewards for all validators.
+#[orga]
+pub struct Distribution<S: Symbol> {
+    _symbol: PhantomData<S>,
+    #[call]
+    pub params: ParamStore<DistributionParams>,
+    community_pool: Coin<S>,
+    pending: Amount,
+    previous_proposer: Option<Address>,
+}
+
+#[orga]
+#[derive(Clone, Debug, Describe)]
+pub struct DistributionParams {
+    pub community_tax: Decimal,
+    pub base_proposer_reward: Decimal,
+    pub bonus_proposer_reward: Decimal,
+}
+
+impl Params for DistributionParams {
+    const MODULE: &'static str = "distribution";
+
+    fn validate(&self) -> Result<()> {
+        let fractions = [
+            self.community_tax,
+            self.base_proposer_reward,
+            self.bonus_proposer_reward,
+        ];
+        if fractions
+            .iter()
+            .any(|fraction| *fraction < Decimal::zero() || *fraction > Decimal::one())
+        {
+            return Err(Error::Coins(
+                "Distribution fractions must be between 0 and 1".into(),
+            ));
+        }
+        if (self.base_proposer_reward + self.bonus_proposer_reward)? > Decimal::one() {
+            return Err(Error::Coins(
+                "Proposer rewards may not add up to more than 1".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match key {
+            "community_tax" => self.community_tax = decode_param(value)?,
+            "base_proposer_reward" => self.base_proposer_reward = decode_param(value)?,
+            "bonus_proposer_reward" => self.bonus_proposer_reward = decode_param(value)?,
+            _ => {
+                return Err(Error::Coins(format!(
+                    "Unknown distribution parameter {}",
+                    key
+                )))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How the coins of a block are split, as computed by [Distribution::split].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Allocation {
+    pub proposer: Amount,
+    pub community: Amount,
+    pub stakers: Amount,
+}
+
+impl<S: Symbol> Distribution<S> {
+    pub fn set_params(&mut self, params: DistributionParams) -> Result<()> {
+        self.params.set(params)
+    }
+
+    /// Splits `total` for a block which was precommitted by the given fraction
+    /// of voting power.
+    pub fn split(&self, total: Amount, signed_fraction: Decimal) -> Result<Allocation> {
+        let proposer_multiplier = (self.params.base_proposer_reward
+            + self.params.bonus_proposer_reward * signed_fraction)?;
+        let proposer = (total * proposer_multiplier)?.amount()?.min(total);
+        let remaining = (total - proposer)?;
+        let community = (remaining * self.params.community_tax)?
+            .amount()?
+            .min(remaining);
+        let stakers = (remaining - community)?;
+
+        Ok(Allocation {
+            proposer,
+            community,
+            stakers,
+        })
+    }
+
+    /// Pays out the fees collected in the previous block and the coins given
+    /// to the module since the previous call, using the commit info of the
+    /// previous block. Apps call this in their `begin_block`, before the
+    /// staking module's own `begin_block` so the proposer is rewarded before
+    /// any downtime punishment.
+    #[cfg(feature = "abci")]
+    pub fn begin_block_step(
+        &mut self,
+        ctx: &BeginBlockCtx,
+        staking: &mut Staking<S>,
+    ) -> Result<()> {
+        if let Some(fees) = self.context::<CollectedFees>() {
+            let fees = fees.take::<S>();
+            self.pending = (self.pending + fees.amount)?;
+        }
+
+        let mut total_power = 0u64;
+        let mut signed_power = 0u64;
+        if let Some(last_commit_info) = &ctx.last_commit_info {
+            for vote in last_commit_info.votes.iter() {
+                let power = vote.validator.as_ref().map_or(0, |v| v.power.max(0) as u64);
+                total_power = total_power.saturating_add(power);
+                if vote.signed_last_block {
+                    signed_power = signed_power.saturating_add(power);
+                }
+            }
+        }
+        let signed_fraction = if total_power == 0 {
+            Decimal::zero()
+        } else {
+            (Amount::from(signed_power) / Amount::from(total_power))?
+        };
+
+        let proposer = match self.previous_proposer {
+            Some(hash) => staking.address_for_tm_hash(hash.bytes())?,
+            None => None,
+        };
+        self.previous_proposer = ctx
+            .header
+            .proposer_address
+            .as_slice()
+            .try_into()
+            .ok()
+            .map(|bytes: [u8; Address::LENGTH]| bytes.into());
+
+        let total = std::mem::take(&mut self.pending);
+        if total == 0 {
+            return Ok(());
+        }
+        let mut allocation = self.split(total, signed_fraction)?;
+        match proposer {
+            Some(address) if allocation.proposer > 0 => {
+                staking
+                    .get_mut(address)?
+                    .give(S::mint(allocation.proposer))?;
+            }
+            // with no known proposer, its reward goes to all stakers
+            _ => allocation.stakers = (allocation.stakers + allocation.proposer)?,
+        }
+
+        // the staking reward pool can not receive coins while nothing is
+        // staked
+        if allocation.stakers > 0 && staking.staked()? == 0 {
+            allocation.community = (allocation.community + allocation.stakers)?;
+        } else if allocation.stakers > 0 {
+            staking.give(S::mint(allocation.stakers))?;
+        }
+        self.community_pool.give(S::mint(allocation.community))?;
+
+        Ok(())
+    }
+
+    /// Takes coins from the community pool, e.g. to execute a passed spend
+    /// proposal.
+    pub fn spend_community_pool(&mut self, amount: Amount) -> Result<Coin<S>> {
+        self.community_pool.take(amount)
+    }
+
+    /// Adds coins to the community pool directly.
+    pub fn fund_community_pool(&mut self, coins: Coin<S>) -> Result<()> {
+        self.community_pool.give(coins)
+    }
+
+    #[query]
+    pub fn community_pool(&self) -> Amount {
+        self.community_pool.amount
+    }
+
+    #[query]
+    pub fn params(&self) -> DistributionParams {
+        self.params.params()
+    }
+}
+
+impl<S: Symbol> Give<Coin<S>> for Distribution<S> {
+    fn give(&mut self, coins: Coin<S>) -> Result<()> {
+        self.pending = (self.pending + coins.amount)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    #[test]
+    fn split() -> Result<()> {
+        let mut distribution: Distribution<Simp> = Distribution::default();
+        assert!(distribution
+            .set_params(DistributionParams {
+                community_tax: "0.02".parse()?,
+                base_proposer_reward: "0.5".parse()?,
+                bonus_proposer_reward: "0.6".parse()?,
+            })
+            .is_err());
+        distribution.set_params(DistributionParams {
+            community_tax: "0.02".parse()?,
+            base_proposer_reward: "0.01".parse()?,
+            bonus_proposer_reward: "0.04".parse()?,
+        })?;
+
+        let allocation = distribution.split(10_000.into(), Decimal::one())?;
+        assert_eq!(
+            allocation,
+            Allocation {
+                proposer: 500.into(),
+                community: 190.into(),
+                stakers: 9310.into(),
+            }
+        );
+
+        let allocation = distribution.split(10_000.into(), "0.5".parse()?)?;
+        assert_eq!(allocation.proposer, 300.into());
+        assert_eq!(allocation.community, 194.into());
+        assert_eq!(allocation.stakers, 9506.into());
+
+        distribution.give(Simp::mint(100))?;
+        distribution.fund_community_pool(Simp::mint(50))?;
+        assert_eq!(distribution.community_pool(), 50.into());
+        assert!(distribution.spend_community_pool(51.into()).is_err());
+        assert_eq!(
+            distribution.spend_community_pool(50.into())?.amount,
+            50.into()
+        );
+
+        Ok(())
+    }
+}
@@ -77,8 +77,8 @@ impl<S: Symbol> Faucet<S> {
                 // This period is in progress
                 let seconds_into_period =
                     seconds_since_start - (i as i64) * self.seconds_per_period as i64;
-                let period_fraction = (Amount::new(seconds_into_period as u64)
-                    / Amount::new(self.seconds_per_period))?;
+                let period_fraction = (Amount::from(seconds_into_period as u64)
+                    / Amount::from(self.seconds_per_period))?;
                 total = (total + period_fraction * total_to_mint_this_period)?;
                 break;
             }
@@ -145,7 +145,10 @@ mod tests {
                 minted.push(faucet.mint()?);
             }
         }
-        let minted_amounts: Vec<u64> = minted.iter().map(|coin| coin.amount.into()).collect();
+        let minted_amounts: Vec<u64> = minted
+            .iter()
+            .map(|coin| coin.amount.try_into().unwrap())
+            .collect();
         assert_eq!(
             minted_amounts,
             vec![
@@ -188,7 +191,10 @@ mod tests {
                 minted.push(faucet.mint()?);
             }
         }
-        let minted_amounts: Vec<u64> = minted.iter().map(|coin| coin.amount.into()).collect();
+        let minted_amounts: Vec<u64> = minted
+            .iter()
+            .map(|coin| coin.amount.try_into().unwrap())
+            .collect();
         assert_eq!(
             minted_amounts,
             vec![
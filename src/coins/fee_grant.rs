@@ -0,0 +1,272 @@
+use crate::coins::Amount;
+use crate::encoding::LengthVec;
+use crate::orga;
+use crate::{Error, Result};
+
+/// An allowance which lets a grantee have the fees of its calls paid from a
+/// granter's account, granted with [Accounts::grant_fee_allowance].
+///
+/// Nothing is set aside when the allowance is granted: fees paid with it are
+/// taken from the granter's balance as they are charged, up to the spend
+/// limit. A grantee uses the allowance by making
+/// [Accounts::pay_fee_with_grant] the payer call of a paid call, naming the
+/// granter.
+///
+/// [Accounts::grant_fee_allowance]: crate::coins::Accounts::grant_fee_allowance
+/// [Accounts::pay_fee_with_grant]: crate::coins::Accounts::pay_fee_with_grant
+#[orga]
+#[derive(Clone, Debug)]
+pub struct Allowance {
+    spend_limit: Option<Amount>,
+    expiration: Option<i64>,
+    allowed_calls: LengthVec<u8, LengthVec<u8, u8>>,
+}
+
+impl Allowance {
+    /// Creates an allowance, checking that the allowed calls are UTF-8 method
+    /// paths.
+    pub fn new(
+        spend_limit: Option<Amount>,
+        expiration: Option<i64>,
+        allowed_calls: LengthVec<u8, LengthVec<u8, u8>>,
+    ) -> Result<Self> {
+        for path in allowed_calls.iter() {
+            std::str::from_utf8(path)
+                .map_err(|_| Error::Coins("Allowed call paths must be UTF-8".into()))?;
+        }
+
+        Ok(Self {
+            spend_limit,
+            expiration,
+            allowed_calls,
+        })
+    }
+
+    /// The most the grantee can still spend on fees, or `None` if the
+    /// allowance is unlimited.
+    pub fn spend_limit(&self) -> Option<Amount> {
+        self.spend_limit
+    }
+
+    /// The time (in seconds since the Unix epoch) after which the allowance
+    /// can no longer be used, if any.
+    pub fn expiration(&self) -> Option<i64> {
+        self.expiration
+    }
+
+    /// Whether the allowance can pay for a call to the method at the given
+    /// path (see [Paid::paid_call_path](crate::plugins::Paid::paid_call_path)).
+    /// Allowances with no allowed calls can pay for any call, otherwise the
+    /// method must be one of the allowed paths or under one of them, e.g.
+    /// `staking` allows `staking.delegate`.
+    pub fn allows(&self, call_path: Option<&str>) -> bool {
+        if self.allowed_calls.is_empty() {
+            return true;
+        }
+        let call_path = match call_path {
+            Some(call_path) => call_path,
+            None => return false,
+        };
+
+        self.allowed_calls.iter().any(|allowed| {
+            let allowed = allowed.as_slice();
+            let path = call_path.as_bytes();
+            path.starts_with(allowed)
+                && (path.len() == allowed.len() || path[allowed.len()] == b'.')
+        })
+    }
+
+    /// Checks that the allowance can pay `fee` for a call to the method at
+    /// `call_path` at time `now`, and deducts it from the spend limit.
+    pub(crate) fn spend(
+        &mut self,
+        fee: Amount,
+        now: Option<i64>,
+        call_path: Option<&str>,
+    ) -> Result<()> {
+        if let Some(expiration) = self.expiration {
+            let now = now.ok_or_else(|| Error::Coins("No Time context available".into()))?;
+            if now > expiration {
+                return Err(Error::Coins("Fee allowance has expired".into()));
+            }
+        }
+        if !self.allows(call_path) {
+            return Err(Error::Coins(
+                "Fee allowance does not allow this call".into(),
+            ));
+        }
+        if let Some(limit) = self.spend_limit {
+            if fee > limit {
+                return Err(Error::Coins("Fee allowance spend limit exceeded".into()));
+            }
+            self.spend_limit = Some((limit - fee).result()?);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call::Call;
+    use crate::coins::{Accounts, Address, Symbol};
+    use crate::context::{Context, ContextGuard};
+    use crate::encoding::Encode;
+    use crate::params::ParamStore;
+    use crate::plugins::{min_fee, CallPath, FeeParams, FeePlugin, Paid, Signer, Time};
+    use serial_test::serial;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    fn paths(paths: &[&str]) -> LengthVec<u8, LengthVec<u8, u8>> {
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| LengthVec::new(path.len() as u8, path.as_bytes().to_vec()))
+            .collect();
+        LengthVec::new(paths.len() as u8, paths)
+    }
+
+    fn signer(signer: Address) -> ContextGuard<Signer> {
+        Context::scope(Signer {
+            signer: Some(signer),
+        })
+    }
+
+    fn payer(call_path: &str) -> ContextGuard<Paid> {
+        let mut paid = Paid::default();
+        paid.running_payer = true;
+        paid.paid_call_path = Some(call_path.to_string());
+        Context::scope(paid)
+    }
+
+    #[test]
+    fn allows() -> Result<()> {
+        let allowance = Allowance::new(None, None, paths(&["staking", "accounts.transfer"]))?;
+        assert!(allowance.allows(Some("staking.delegate")));
+        assert!(allowance.allows(Some("accounts.transfer")));
+        assert!(!allowance.allows(Some("stakingx.delegate")));
+        assert!(!allowance.allows(Some("accounts.take_as_funding")));
+        assert!(!allowance.allows(None));
+
+        let allowance = Allowance::new(None, None, paths(&[]))?;
+        assert!(allowance.allows(None));
+
+        assert!(Allowance::new(
+            None,
+            None,
+            LengthVec::new(1, vec![LengthVec::new(1, vec![0xff])])
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decoded_call_paths() -> Result<()> {
+        type ParamsCall = <ParamStore<FeeParams> as Call>::Call;
+        let app: FeePlugin<Simp, ParamStore<FeeParams>> = Default::default();
+        let admin = Address::from_pubkey([2; 33]);
+        let call_bytes = ParamsCall::MethodSetAdmin(admin, vec![]).encode()?;
+
+        let call_path = app.call_path(call_bytes.as_slice());
+        assert_eq!(call_path.as_deref(), Some("set_admin"));
+        assert_eq!(app.call_path(&[0]).as_deref(), Some("noop"));
+        assert_eq!(app.call_path(&[99]), None);
+
+        let allowance = Allowance::new(None, None, paths(&["set_admin"]))?;
+        assert!(allowance.allows(call_path.as_deref()));
+        let call_bytes = ParamsCall::MethodRemoveAdmin(vec![]).encode()?;
+        assert!(!allowance.allows(app.call_path(call_bytes.as_slice()).as_deref()));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn pay_fee_with_grant() -> Result<()> {
+        let granter = Address::from_pubkey([2; 33]);
+        let grantee = Address::from_pubkey([3; 33]);
+        let fee = min_fee();
+        let mut accounts: Accounts<Simp> = Accounts::default();
+        accounts.deposit(granter, (fee * 5).into())?;
+
+        {
+            let _signer = signer(granter);
+            let limit = Some((fee * 2).into());
+            accounts.grant_fee_allowance(grantee, limit, Some(100), paths(&["accounts"]))?;
+        }
+        assert_eq!(
+            accounts
+                .fee_allowance(granter, grantee)?
+                .unwrap()
+                .spend_limit(),
+            Some((fee * 2).into())
+        );
+
+        let _time = Context::scope(Time::from_seconds(50));
+        let _signer = signer(grantee);
+        {
+            let _paid = payer("staking.delegate");
+            assert!(accounts.pay_fee_with_grant(granter).is_err());
+        }
+        {
+            let _paid = payer("accounts.transfer");
+            accounts.pay_fee_with_grant(granter)?;
+
+            // the funding can only be spent on the fee
+            let paid = Context::resolve::<Paid>().unwrap();
+            assert_eq!(paid.balance::<Simp>()?, 0.into());
+            assert!(paid.take::<Simp, _>(fee).is_err());
+            paid.take_fee::<Simp, _>(fee)?;
+        }
+        assert_eq!(accounts.balance(granter)?, (fee * 4).into());
+        assert_eq!(
+            accounts
+                .fee_allowance(granter, grantee)?
+                .unwrap()
+                .spend_limit(),
+            Some(fee.into())
+        );
+
+        {
+            let _signer = signer(granter);
+            let _paid = payer("accounts.transfer");
+            assert!(accounts.pay_fee_with_grant(granter).is_err());
+        }
+        {
+            let _paid = payer("accounts.transfer");
+            accounts.pay_fee_with_grant(granter)?;
+            assert!(accounts.pay_fee_with_grant(granter).is_err());
+        }
+        assert_eq!(accounts.balance(granter)?, (fee * 3).into());
+
+        {
+            let _signer = signer(granter);
+            accounts.grant_fee_allowance(grantee, None, Some(100), paths(&[]))?;
+        }
+        {
+            let _time = Context::scope(Time::from_seconds(101));
+            let _paid = payer("accounts.transfer");
+            assert!(accounts.pay_fee_with_grant(granter).is_err());
+        }
+
+        {
+            let _signer = signer(granter);
+            accounts.revoke_fee_allowance(grantee)?;
+        }
+        assert!(accounts.fee_allowance(granter, grantee)?.is_none());
+        {
+            let _paid = payer("accounts.transfer");
+            assert!(accounts.pay_fee_with_grant(granter).is_err());
+        }
+        assert_eq!(accounts.balance(granter)?, (fee * 3).into());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,542 @@
+//! Signed 256-bit fixed-point numbers, which back [Decimal](super::Decimal)
+//! so that it can represent any 128-bit [Amount](super::Amount).
+
+use primitive_types::{U256, U512};
+use rust_decimal::Decimal as NumDecimal;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// The number of decimal places of a [Fixed]. This is the maximum scale of
+/// `rust_decimal` numbers, so they convert without loss of precision.
+pub(crate) const SCALE: u32 = 28;
+
+/// `10^SCALE`, the magnitude of one.
+const ONE: U256 = U256([4477988020393345024, 542101086, 0, 0]);
+
+/// `10^SCALE / 2`, the fraction of a midpoint between two integers.
+const HALF: U256 = U256([2238994010196672512, 271050543, 0, 0]);
+
+/// Largest mantissa of a `rust_decimal` number.
+const MAX_NUM_DECIMAL_MANTISSA: u128 = (1 << 96) - 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Fixed {
+    negative: bool,
+    magnitude: U256,
+}
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed {
+        negative: false,
+        magnitude: U256([0; 4]),
+    };
+
+    pub const ONE: Fixed = Fixed {
+        negative: false,
+        magnitude: ONE,
+    };
+
+    fn new(negative: bool, magnitude: U256) -> Self {
+        Fixed {
+            negative: negative && !magnitude.is_zero(),
+            magnitude,
+        }
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        // u128::MAX * 10^28 < 2^256, so this can not overflow
+        Fixed::new(false, U256::from(value) * ONE)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_zero()
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Self {
+        Fixed::new(false, self.magnitude)
+    }
+
+    pub fn checked_add(self, other: Fixed) -> Option<Fixed> {
+        if self.negative == other.negative {
+            let magnitude = self.magnitude.checked_add(other.magnitude)?;
+            return Some(Fixed::new(self.negative, magnitude));
+        }
+
+        if self.magnitude < other.magnitude {
+            Some(Fixed::new(other.negative, other.magnitude - self.magnitude))
+        } else {
+            Some(Fixed::new(self.negative, self.magnitude - other.magnitude))
+        }
+    }
+
+    pub fn checked_sub(self, other: Fixed) -> Option<Fixed> {
+        self.checked_add(Fixed::new(!other.negative, other.magnitude))
+    }
+
+    /// Multiplies, rounding the result to the nearest multiple of
+    /// `10^-SCALE`, with midpoints rounded away from zero.
+    pub fn checked_mul(self, other: Fixed) -> Option<Fixed> {
+        let product = self.magnitude.full_mul(other.magnitude);
+        let magnitude = div_rounded(product, ONE.into());
+
+        Some(Fixed::new(
+            self.negative != other.negative,
+            U256::try_from(magnitude).ok()?,
+        ))
+    }
+
+    /// Divides, rounding the result to the nearest multiple of `10^-SCALE`,
+    /// with midpoints rounded away from zero. Returns `None` when dividing by
+    /// zero or on overflow.
+    pub fn checked_div(self, other: Fixed) -> Option<Fixed> {
+        if other.is_zero() {
+            return None;
+        }
+
+        // (2^256 - 1) * 10^28 < 2^512, so this can not overflow
+        let numerator = self.magnitude.full_mul(ONE);
+        let magnitude = div_rounded(numerator, other.magnitude.into());
+
+        Some(Fixed::new(
+            self.negative != other.negative,
+            U256::try_from(magnitude).ok()?,
+        ))
+    }
+
+    /// Rounds to the nearest integer, with midpoints rounded to the nearest
+    /// even integer.
+    pub fn round(&self) -> Fixed {
+        let (integer, fraction) = self.magnitude.div_mod(ONE);
+        let round_up = fraction > HALF || (fraction == HALF && integer.bit(0));
+
+        // the fraction of the largest magnitude is below one half, so this
+        // only rounds up to magnitudes which fit
+        self.with_integer(integer, round_up).unwrap()
+    }
+
+    /// Rounds toward negative infinity, keeping only the integer part of
+    /// non-negative numbers. Returns `None` on overflow.
+    pub fn floor(&self) -> Option<Fixed> {
+        let (integer, fraction) = self.magnitude.div_mod(ONE);
+        let round_up = self.negative && !fraction.is_zero();
+
+        self.with_integer(integer, round_up)
+    }
+
+    /// Returns the integer part, or `None` if it is negative or does not fit
+    /// in a `u128`.
+    pub fn to_u128(self) -> Option<u128> {
+        let integer = self.magnitude / ONE;
+        if self.negative || integer.bits() > 128 {
+            return None;
+        }
+
+        Some(integer.as_u128())
+    }
+
+    /// Converts to a `rust_decimal` number, rounding away the fewest decimal
+    /// places needed to fit its 96-bit mantissa, with midpoints rounded away
+    /// from zero. Returns `None` if the integer part does not fit.
+    pub fn to_num_decimal(self) -> Option<NumDecimal> {
+        let max = U256::from(MAX_NUM_DECIMAL_MANTISSA);
+        let (mantissa, scale) = (0..=SCALE).rev().find_map(|scale| {
+            let divisor = U256::exp10((SCALE - scale) as usize);
+            let mantissa = div_rounded(self.magnitude.into(), divisor.into());
+            (mantissa <= max.into()).then_some((mantissa.low_u128() as i128, scale))
+        })?;
+
+        let mantissa = if self.negative { -mantissa } else { mantissa };
+        Some(NumDecimal::from_i128_with_scale(mantissa, scale).normalize())
+    }
+
+    /// Parses a decimal string such as `-12.345`, returning `None` if it is
+    /// malformed, has more than [SCALE] decimal places or overflows.
+    pub fn parse(s: &str) -> Option<Fixed> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if (integer.is_empty() && fraction.is_empty()) || fraction.len() > SCALE as usize {
+            return None;
+        }
+        if !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|digit| digit.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let digits = format!("{}{:0<width$}", integer, fraction, width = SCALE as usize);
+        let magnitude = U256::from_dec_str(&digits).ok()?;
+
+        Some(Fixed::new(negative, magnitude))
+    }
+
+    /// Encodes as a sign byte followed by the big-endian magnitude.
+    pub fn to_bytes(self) -> [u8; 33] {
+        let mut bytes = [0; 33];
+        bytes[0] = self.negative as u8;
+        self.magnitude.to_big_endian(&mut bytes[1..]);
+
+        bytes
+    }
+
+    /// Decodes the encoding of [Fixed::to_bytes], returning `None` for an
+    /// invalid sign byte.
+    pub fn from_bytes(bytes: [u8; 33]) -> Option<Fixed> {
+        let negative = match bytes[0] {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+
+        Some(Fixed::new(negative, U256::from_big_endian(&bytes[1..])))
+    }
+
+    /// Scales an integer part with the sign of `self` back to a [Fixed],
+    /// adding one first if `round_up` is set. Returns `None` on overflow.
+    fn with_integer(&self, integer: U256, round_up: bool) -> Option<Fixed> {
+        // the integer part is at most (2^256 - 1) / 10^28, so adding one can
+        // not overflow
+        let integer = if round_up { integer + 1 } else { integer };
+
+        Some(Fixed::new(self.negative, integer.checked_mul(ONE)?))
+    }
+}
+
+impl From<NumDecimal> for Fixed {
+    fn from(value: NumDecimal) -> Self {
+        let mantissa = U256::from(value.mantissa().unsigned_abs());
+        // rust_decimal mantissas are at most 96 bits, so this can not overflow
+        let magnitude = mantissa * U256::exp10((SCALE - value.scale()) as usize);
+
+        Fixed::new(value.is_sign_negative(), magnitude)
+    }
+}
+
+impl PartialOrd for Fixed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fixed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+        }
+    }
+}
+
+/// Formats without trailing zeros in the decimal places.
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (integer, fraction) = self.magnitude.div_mod(ONE);
+        let mut out = integer.to_string();
+        if !fraction.is_zero() {
+            let fraction = format!("{:0width$}", fraction.as_u128(), width = SCALE as usize);
+            out.push('.');
+            out.push_str(fraction.trim_end_matches('0'));
+        }
+
+        f.pad_integral(!self.negative, "", &out)
+    }
+}
+
+/// Divides by a non-zero divisor, rounding to the nearest integer with
+/// midpoints rounded up.
+fn div_rounded(numerator: U512, divisor: U512) -> U512 {
+    let (quotient, remainder) = numerator.div_mod(divisor);
+    if remainder >= divisor - remainder {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_rational::BigRational;
+    use num_traits::Signed;
+    use rust_decimal_macros::dec;
+
+    fn fixed(s: &str) -> Fixed {
+        Fixed::parse(s).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(Fixed::from_u128(1), Fixed::ONE);
+        assert_eq!(ONE, U256::exp10(SCALE as usize));
+        assert_eq!(HALF, ONE / 2);
+        assert_eq!(fixed("1.5").checked_add(fixed("-2")), Some(fixed("-0.5")));
+        assert_eq!(fixed("-1.5").checked_sub(fixed("-1.5")), Some(Fixed::ZERO));
+        assert_eq!(fixed("1.5").checked_mul(fixed("-0.2")), Some(fixed("-0.3")));
+        assert_eq!(
+            fixed("2").checked_div(fixed("3")),
+            Some(fixed("0.6666666666666666666666666667"))
+        );
+        assert_eq!(fixed("1").checked_div(Fixed::ZERO), None);
+        assert_eq!(fixed("2.5").round(), fixed("2"));
+        assert_eq!(fixed("3.5").round(), fixed("4"));
+        assert_eq!(fixed("-2.6").round(), fixed("-3"));
+        assert!(fixed("-1") < fixed("0.5"));
+        assert_eq!(fixed("2.75").floor(), Some(fixed("2")));
+        assert_eq!(fixed("0.25").floor(), Some(Fixed::ZERO));
+        assert_eq!(fixed("-2.25").floor(), Some(fixed("-3")));
+        assert_eq!(fixed("-0.25").floor(), Some(fixed("-1")));
+        assert_eq!(fixed("3").floor(), Some(fixed("3")));
+        assert_eq!(fixed("-3").floor(), Some(fixed("-3")));
+        assert!(fixed("-2") < fixed("-1"));
+
+        let max = Fixed::from_u128(u128::MAX);
+        assert_eq!(max.to_u128(), Some(u128::MAX));
+        assert_eq!(
+            max.checked_div(fixed("2")).unwrap().round().to_u128(),
+            Some(u128::MAX / 2 + 1)
+        );
+        assert_eq!(max.checked_mul(max), None);
+        assert_eq!(fixed("-1").to_u128(), None);
+
+        let largest = Fixed::new(true, U256::MAX);
+        assert_eq!(largest.round(), fixed(&format!("-{}", U256::MAX / ONE)));
+        assert_eq!(largest.floor(), None);
+        assert_eq!(largest.checked_add(largest), None);
+        assert_eq!(largest.checked_div(fixed("0.5")), None);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(fixed("1.230").to_string(), "1.23");
+        assert_eq!(fixed("-0.05").to_string(), "-0.05");
+        assert_eq!(fixed("100").to_string(), "100");
+        assert_eq!(
+            Fixed::from_u128(u128::MAX).to_string(),
+            u128::MAX.to_string()
+        );
+        assert_eq!(Fixed::parse("1.2.3"), None);
+        assert_eq!(Fixed::parse("."), None);
+        assert_eq!(Fixed::parse("1.-2"), None);
+        assert_eq!(Fixed::parse("0.00000000000000000000000000001"), None);
+        assert_eq!(Fixed::parse(&format!("{}1", U256::MAX)), None);
+    }
+
+    #[test]
+    fn bytes() {
+        for value in [fixed("-1.5"), Fixed::ZERO, Fixed::from_u128(u128::MAX)] {
+            assert_eq!(Fixed::from_bytes(value.to_bytes()), Some(value));
+        }
+        assert_eq!(Fixed::ONE.to_bytes()[17..], 10u128.pow(SCALE).to_be_bytes());
+        assert_eq!(Fixed::from_bytes([2; 33]), None);
+    }
+
+    #[test]
+    fn num_decimal() {
+        assert_eq!(Fixed::from(dec!(-1.25)), fixed("-1.25"));
+        assert_eq!(fixed("-1.25").to_num_decimal(), Some(dec!(-1.25)));
+        assert_eq!(
+            fixed("0.0000000000000000000000000001").to_num_decimal(),
+            Some(dec!(0.0000000000000000000000000001))
+        );
+        assert_eq!(
+            fixed("1000000.0000000000000000000000000001").to_num_decimal(),
+            Some(dec!(1000000))
+        );
+        // rounds once rather than digit by digit, which would round up
+        assert_eq!(
+            fixed("1000000.0000000000000000000001444445").to_num_decimal(),
+            Some(dec!(1000000.0000000000000000000001))
+        );
+        assert_eq!(
+            fixed("10000000000000000000000000000.49").to_num_decimal(),
+            Some(dec!(10000000000000000000000000000))
+        );
+        assert_eq!(Fixed::from_u128(u128::MAX).to_num_decimal(), None);
+    }
+
+    /// A xorshift generator, so the randomized tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A random number of at most `bits` bits, with its length picked
+        /// uniformly so that small and large magnitudes are equally covered.
+        fn magnitude(&mut self, bits: usize) -> U256 {
+            let value = U256([self.next(), self.next(), self.next(), self.next()]);
+            match self.next() as usize % (bits + 1) {
+                0 => U256::zero(),
+                len => value >> (256 - len),
+            }
+        }
+
+        /// Any `rust_decimal` number, i.e. a 96-bit mantissa with a scale of
+        /// up to 28.
+        fn num_decimal(&mut self) -> NumDecimal {
+            let mantissa = self.magnitude(96).as_u128() as i128;
+            let mantissa = if self.next() & 1 == 1 {
+                -mantissa
+            } else {
+                mantissa
+            };
+            let scale = (self.next() % (SCALE as u64 + 1)) as u32;
+
+            NumDecimal::from_i128_with_scale(mantissa, scale)
+        }
+
+        /// Any [Fixed], including ones out of the range of `rust_decimal`.
+        fn fixed(&mut self) -> Fixed {
+            let magnitude = self.magnitude(256);
+            Fixed::new(self.next() & 1 == 1, magnitude)
+        }
+    }
+
+    const ITERATIONS: usize = 2_000;
+
+    fn rational(s: &str) -> BigRational {
+        s.parse().unwrap()
+    }
+
+    fn exact(value: &Fixed) -> BigRational {
+        let sign = if value.negative { "-" } else { "" };
+        rational(&format!("{}{}/{}", sign, value.magnitude, ONE))
+    }
+
+    fn exact_num_decimal(value: &NumDecimal) -> BigRational {
+        rational(&format!(
+            "{}/{}",
+            value.mantissa(),
+            10u128.pow(value.scale())
+        ))
+    }
+
+    /// Rounds an exact number to the nearest [Fixed] with midpoints rounded
+    /// away from zero, or `None` if it overflows.
+    fn nearest(value: BigRational) -> Option<Fixed> {
+        let negative = value < BigRational::default();
+        let scaled = value.abs() * rational(&ONE.to_string()) + rational("1/2");
+        let magnitude = U256::from_dec_str(&scaled.floor().to_integer().to_string()).ok()?;
+
+        Some(Fixed::new(negative, magnitude))
+    }
+
+    /// Checks a `rust_decimal` result is within one unit in its last place of
+    /// the [Fixed] result, since `rust_decimal` rounds to fewer places.
+    fn assert_close(actual: Option<Fixed>, expected: Option<NumDecimal>) {
+        let Some(expected) = expected else {
+            // rust_decimal overflows at 2^96, Fixed only beyond 2^256 / 10^28
+            if let Some(actual) = actual {
+                assert!(actual.abs() >= Fixed::from(NumDecimal::MAX));
+            }
+            return;
+        };
+        let actual = exact(&actual.unwrap());
+        let ulp = rational(&format!("1/{}", 10u128.pow(expected.scale())));
+        let diff = &actual - exact_num_decimal(&expected);
+        assert!(
+            diff.clone() <= ulp && -diff <= ulp,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    fn check_arithmetic(a: Fixed, b: Fixed) {
+        let (exact_a, exact_b) = (exact(&a), exact(&b));
+        assert_eq!(a.checked_add(b), nearest(&exact_a + &exact_b));
+        assert_eq!(a.checked_sub(b), nearest(&exact_a - &exact_b));
+        assert_eq!(a.checked_mul(b), nearest(&exact_a * &exact_b));
+        if b.is_zero() {
+            assert_eq!(a.checked_div(b), None);
+        } else {
+            assert_eq!(a.checked_div(b), nearest(&exact_a / &exact_b));
+        }
+        assert_eq!(a.cmp(&b), exact_a.cmp(&exact_b));
+    }
+
+    #[test]
+    fn random_arithmetic() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..ITERATIONS {
+            let (a, b) = (rng.num_decimal(), rng.num_decimal());
+            check_arithmetic(a.into(), b.into());
+
+            let (fa, fb) = (Fixed::from(a), Fixed::from(b));
+            assert_close(fa.checked_add(fb), a.checked_add(b));
+            assert_close(fa.checked_sub(fb), a.checked_sub(b));
+            assert_close(fa.checked_mul(fb), a.checked_mul(b));
+            if !b.is_zero() {
+                assert_close(fa.checked_div(fb), a.checked_div(b));
+            }
+
+            check_arithmetic(rng.fixed(), rng.fixed());
+        }
+    }
+
+    #[test]
+    fn random_rounding() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..ITERATIONS {
+            let value = rng.num_decimal();
+            let fixed = Fixed::from(value);
+            assert_eq!(fixed.round(), value.round().into());
+            assert_eq!(fixed.floor(), Some(value.floor().into()));
+            let integer = value.trunc().mantissa();
+            assert_eq!(
+                fixed.to_u128(),
+                (!value.is_sign_negative()).then_some(integer as u128)
+            );
+
+            let fixed = rng.fixed();
+            let floor = exact(&fixed).floor();
+            let fraction = exact(&fixed) - &floor;
+            let half = rational("1/2");
+            let round = if fraction > half
+                || (fraction == half && !(&floor / rational("2")).is_integer())
+            {
+                &floor + rational("1")
+            } else {
+                floor.clone()
+            };
+            assert_eq!(fixed.floor(), nearest(floor));
+            assert_eq!(Some(fixed.round()), nearest(round));
+        }
+    }
+
+    #[test]
+    fn random_strings() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+        for _ in 0..ITERATIONS {
+            let value = rng.num_decimal();
+            let fixed = Fixed::from(value);
+            assert_eq!(fixed.to_string(), value.normalize().to_string());
+            assert_eq!(Fixed::parse(&value.to_string()), Some(fixed));
+            assert_eq!(fixed.to_num_decimal(), Some(value.normalize()));
+            assert_eq!(Fixed::from_bytes(fixed.to_bytes()), Some(fixed));
+
+            let fixed = rng.fixed();
+            assert_eq!(Fixed::parse(&fixed.to_string()), Some(fixed));
+            assert_eq!(Fixed::from_bytes(fixed.to_bytes()), Some(fixed));
+            if let Some(value) = fixed.to_num_decimal() {
+                let half_ulp = rational(&format!("1/{}", 2 * 10u128.pow(value.scale())));
+                let diff = exact(&fixed) - exact_num_decimal(&value);
+                assert!(diff.clone() <= half_ulp && -diff <= half_ulp);
+            }
+        }
+    }
+}
@@ -0,0 +1,819 @@
+//! On-chain governance, where stakers vote on proposals.
+//!
+//! A proposal is submitted with a deposit, and enters its voting period once
+//! its deposits reach the minimum deposit. Votes are weighted by stake: a
+//! validator's vote counts the coins delegated to it, except for the
+//! delegations of delegators who voted themselves, which count towards their
+//! own votes instead. When the voting period ends, the proposal passes if
+//! enough of the stake voted (the quorum), more than the threshold of the
+//! non-abstaining votes are yes, and no more than the veto threshold of the
+//! votes are vetoes. Passed proposals are executed by the app's
+//! [ProposalHandler].
+
+use super::{Address, Amount, Coin, Decimal, Give, Staking, Symbol, Take};
+use crate::collections::Map;
+use crate::context::{Context, GetContext};
+use crate::describe::Describe;
+use crate::encoding::{Decode, Encode, LengthVec, Terminated};
+use crate::migrate::migrate_from_self_impl;
+use crate::orga;
+use crate::params::{decode_param, GovernanceAuthority, ParamChange, ParamStore, Params};
+use crate::plugins::{fee_params, Paid, Signer, Time};
+use crate::state::state_impl;
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+#[orga]
+pub struct Governance<S: Symbol> {
+    _symbol: PhantomData<S>,
+    #[call]
+    pub params: ParamStore<GovParams>,
+    next_proposal_id: u64,
+    proposals: Map<u64, Proposal>,
+    active_proposals: Map<u64, ()>,
+    deposits: Map<u64, Map<Address, Amount>>,
+    votes: Map<u64, Map<Address, VoteOption>>,
+    deposit_pool: Coin<S>,
+    refunds: Map<Address, Coin<S>>,
+}
+
+#[orga]
+#[derive(Clone, Debug, Describe)]
+pub struct GovParams {
+    /// The deposits a proposal needs before it enters its voting period.
+    pub min_deposit: Amount,
+    pub max_deposit_period_seconds: i64,
+    pub voting_period_seconds: i64,
+    /// The fraction of the bonded stake which must vote for a tally to count.
+    pub quorum: Decimal,
+    /// The fraction of non-abstaining votes which must be yes to pass.
+    pub threshold: Decimal,
+    /// The fraction of votes which must be vetoes to reject a proposal and
+    /// burn its deposits.
+    pub veto_threshold: Decimal,
+}
+
+impl Params for GovParams {
+    const MODULE: &'static str = "gov";
+
+    fn validate(&self) -> Result<()> {
+        if self.max_deposit_period_seconds <= 0 || self.voting_period_seconds <= 0 {
+            return Err(Error::App("Governance periods must be positive".into()));
+        }
+        let fractions = [self.quorum, self.threshold, self.veto_threshold];
+        if fractions
+            .iter()
+            .any(|fraction| *fraction < Decimal::zero() || *fraction > Decimal::one())
+        {
+            return Err(Error::App(
+                "Governance fractions must be between 0 and 1".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match key {
+            "min_deposit" => self.min_deposit = decode_param(value)?,
+            "max_deposit_period_seconds" => self.max_deposit_period_seconds = decode_param(value)?,
+            "voting_period_seconds" => self.voting_period_seconds = decode_param(value)?,
+            "quorum" => self.quorum = decode_param(value)?,
+            "threshold" => self.threshold = decode_param(value)?,
+            "veto_threshold" => self.veto_threshold = decode_param(value)?,
+            _ => return Err(Error::App(format!("Unknown governance parameter {}", key))),
+        }
+
+        Ok(())
+    }
+}
+
+/// What a proposal does when it passes.
+#[derive(Encode, Decode, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ProposalContent {
+    /// Has no effect other than recording the result of the vote.
+    #[default]
+    Text,
+    ParamChange(LengthVec<u8, ParamChange>),
+    CommunityPoolSpend {
+        recipient: Address,
+        amount: Amount,
+    },
+    SoftwareUpgrade {
+        version: LengthVec<u8, u8>,
+    },
+}
+
+impl Terminated for ProposalContent {}
+state_impl!(ProposalContent);
+migrate_from_self_impl!(ProposalContent);
+
+#[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProposalStatus {
+    #[default]
+    DepositPeriod,
+    VotingPeriod,
+    Passed,
+    Rejected,
+    /// The proposal passed, but its execution failed.
+    Failed,
+}
+
+impl Terminated for ProposalStatus {}
+state_impl!(ProposalStatus);
+migrate_from_self_impl!(ProposalStatus);
+
+#[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoteOption {
+    Yes,
+    #[default]
+    Abstain,
+    No,
+    NoWithVeto,
+}
+
+impl Terminated for VoteOption {}
+state_impl!(VoteOption);
+migrate_from_self_impl!(VoteOption);
+
+#[orga]
+#[derive(Clone, Debug)]
+pub struct Proposal {
+    proposer: Address,
+    title: LengthVec<u16, u8>,
+    description: LengthVec<u16, u8>,
+    content: ProposalContent,
+    status: ProposalStatus,
+    total_deposit: Amount,
+    deposit_end_seconds: i64,
+    voting_end_seconds: i64,
+    tally: TallyResult,
+}
+
+impl Proposal {
+    pub fn proposer(&self) -> Address {
+        self.proposer
+    }
+
+    pub fn title(&self) -> &[u8] {
+        self.title.as_slice()
+    }
+
+    pub fn description(&self) -> &[u8] {
+        self.description.as_slice()
+    }
+
+    pub fn content(&self) -> &ProposalContent {
+        &self.content
+    }
+
+    pub fn status(&self) -> ProposalStatus {
+        self.status
+    }
+
+    pub fn total_deposit(&self) -> Amount {
+        self.total_deposit
+    }
+
+    pub fn deposit_end_seconds(&self) -> i64 {
+        self.deposit_end_seconds
+    }
+
+    pub fn voting_end_seconds(&self) -> i64 {
+        self.voting_end_seconds
+    }
+
+    /// The final tally, set when the voting period ends.
+    pub fn tally(&self) -> &TallyResult {
+        &self.tally
+    }
+}
+
+#[orga]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TallyResult {
+    pub yes: Amount,
+    pub abstain: Amount,
+    pub no: Amount,
+    pub no_with_veto: Amount,
+}
+
+impl TallyResult {
+    fn add(&mut self, option: VoteOption, amount: Amount) -> Result<()> {
+        let total = match option {
+            VoteOption::Yes => &mut self.yes,
+            VoteOption::Abstain => &mut self.abstain,
+            VoteOption::No => &mut self.no,
+            VoteOption::NoWithVeto => &mut self.no_with_veto,
+        };
+        *total = (*total + amount)?;
+
+        Ok(())
+    }
+
+    pub fn total(&self) -> Result<Amount> {
+        (self.yes + self.abstain + self.no + self.no_with_veto).result()
+    }
+}
+
+/// Executes the changes of passed proposals, implemented by the app since the
+/// changes affect other modules.
+///
+/// Proposals whose execution returns an error are marked as failed, and none
+/// of their changes to the governance and fee parameters are kept, so handlers
+/// should likewise check that every change can be applied before making any.
+pub trait ProposalHandler {
+    /// Applies parameter changes to the app's modules, e.g. by passing each
+    /// change to the [ParamStore::apply_change] of every module.
+    fn change_params(&mut self, changes: &[ParamChange]) -> Result<()>;
+
+    /// Pays coins from the community pool, e.g. with
+    /// [Distribution::spend_community_pool](super::Distribution::spend_community_pool).
+    fn spend_community_pool(&mut self, recipient: Address, amount: Amount) -> Result<()>;
+
+    /// Switches the network to a new version, e.g. by setting the
+    /// [Upgrade](crate::upgrade::Upgrade) module's current version, so nodes
+    /// which are still running the old version stop.
+    fn upgrade(&mut self, version: &LengthVec<u8, u8>) -> Result<()>;
+}
+
+impl<S: Symbol> Governance<S> {
+    pub fn set_params(&mut self, params: GovParams) -> Result<()> {
+        self.params.set(params)
+    }
+
+    /// Submits a proposal, depositing the funding of the current call.
+    #[call]
+    pub fn submit_proposal(
+        &mut self,
+        title: LengthVec<u16, u8>,
+        description: LengthVec<u16, u8>,
+        content: ProposalContent,
+    ) -> Result<()> {
+        let proposer = self.signer()?;
+        let now = self.current_seconds()?;
+        let deposit = self.take_funding()?;
+
+        if let ProposalContent::CommunityPoolSpend { amount, .. } = &content {
+            if *amount == 0 {
+                return Err(Error::App("Spend amount must be positive".into()));
+            }
+        }
+
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        self.proposals.insert(
+            id,
+            Proposal {
+                proposer,
+                title,
+                description,
+                content,
+                status: ProposalStatus::DepositPeriod,
+                total_deposit: 0.into(),
+                deposit_end_seconds: now + self.params.max_deposit_period_seconds,
+                voting_end_seconds: 0,
+                tally: Default::default(),
+            },
+        )?;
+        self.active_proposals.insert(id, ())?;
+
+        self.add_deposit(id, proposer, deposit, now)
+    }
+
+    /// Adds the funding of the current call to the deposits of a proposal.
+    #[call]
+    pub fn deposit(&mut self, proposal_id: u64) -> Result<()> {
+        let depositor = self.signer()?;
+        let now = self.current_seconds()?;
+        let deposit = self.take_funding()?;
+
+        self.add_deposit(proposal_id, depositor, deposit, now)
+    }
+
+    /// Votes on a proposal in its voting period, replacing any previous vote
+    /// of the signer.
+    #[call]
+    pub fn vote(&mut self, proposal_id: u64, option: VoteOption) -> Result<()> {
+        let voter = self.signer()?;
+        let now = self.current_seconds()?;
+
+        let proposal = self
+            .proposals
+            .get(proposal_id)?
+            .ok_or_else(|| Error::App("Proposal not found".into()))?;
+        if proposal.status != ProposalStatus::VotingPeriod || now >= proposal.voting_end_seconds {
+            return Err(Error::App("Proposal is not in its voting period".into()));
+        }
+        drop(proposal);
+
+        self.votes
+            .entry(proposal_id)?
+            .or_insert_default()?
+            .insert(voter, option)
+    }
+
+    /// Returns the signer's refunded deposits as funding for the paid call
+    /// (e.g. `give_from_funding_all`).
+    #[call]
+    pub fn claim_refund(&mut self) -> Result<()> {
+        let depositor = self.signer()?;
+        let amount = match self.refunds.get(depositor)? {
+            Some(refund) => refund.amount,
+            None => return Err(Error::App("No refunded deposits to claim".into())),
+        };
+        self.refunds.remove(depositor)?;
+
+        self.context::<Paid>()
+            .ok_or_else(|| Error::Coins("No Paid context found".into()))?
+            .give::<S, _>(amount)
+    }
+
+    fn add_deposit(
+        &mut self,
+        proposal_id: u64,
+        depositor: Address,
+        deposit: Coin<S>,
+        now: i64,
+    ) -> Result<()> {
+        let mut proposal = self
+            .proposals
+            .get_mut(proposal_id)?
+            .ok_or_else(|| Error::App("Proposal not found".into()))?;
+        let open = match proposal.status {
+            ProposalStatus::DepositPeriod => now < proposal.deposit_end_seconds,
+            ProposalStatus::VotingPeriod => now < proposal.voting_end_seconds,
+            _ => false,
+        };
+        if !open {
+            return Err(Error::App("Proposal is not accepting deposits".into()));
+        }
+
+        proposal.total_deposit = (proposal.total_deposit + deposit.amount)?;
+        if proposal.status == ProposalStatus::DepositPeriod
+            && proposal.total_deposit >= self.params.min_deposit
+        {
+            proposal.status = ProposalStatus::VotingPeriod;
+            proposal.voting_end_seconds = now + self.params.voting_period_seconds;
+        }
+        drop(proposal);
+
+        let mut deposits = self.deposits.entry(proposal_id)?.or_insert_default()?;
+        let mut total = deposits.entry(depositor)?.or_insert_default()?;
+        *total = (*total + deposit.amount)?;
+        drop(total);
+        drop(deposits);
+
+        self.deposit_pool.give(deposit)
+    }
+
+    /// Ends the deposit and voting periods which are over, tallying votes and
+    /// executing passed proposals with the handler. Apps call this in their
+    /// `end_block`.
+    pub fn end_block_step<H: ProposalHandler>(
+        &mut self,
+        staking: &Staking<S>,
+        handler: &mut H,
+    ) -> Result<()> {
+        let now = self.current_seconds()?;
+
+        let mut ended = vec![];
+        for entry in self.active_proposals.iter()? {
+            let (id, _) = entry?;
+            let proposal = self
+                .proposals
+                .get(*id)?
+                .ok_or_else(|| Error::App("Proposal not found".into()))?;
+            let over = match proposal.status {
+                ProposalStatus::DepositPeriod => now >= proposal.deposit_end_seconds,
+                ProposalStatus::VotingPeriod => now >= proposal.voting_end_seconds,
+                _ => true,
+            };
+            if over {
+                ended.push(*id);
+            }
+        }
+
+        for id in ended {
+            self.active_proposals.remove(id)?;
+            let mut proposal = match self.proposals.get(id)? {
+                Some(proposal) => (*proposal).clone(),
+                None => return Err(Error::App("Proposal not found".into())),
+            };
+
+            if proposal.status == ProposalStatus::DepositPeriod {
+                proposal.status = ProposalStatus::Rejected;
+                self.burn_deposits(id)?;
+                self.proposals.insert(id, proposal)?;
+                continue;
+            }
+
+            let (tally, bonded) = self.tally_votes(id, staking)?;
+            let voted = tally.total()?;
+            let quorum = bonded > 0 && (voted / bonded)? >= self.params.quorum;
+            let vetoed = voted > 0 && (tally.no_with_veto / voted)? > self.params.veto_threshold;
+            let non_abstaining = (voted - tally.abstain)?;
+            let passed =
+                non_abstaining > 0 && (tally.yes / non_abstaining)? > self.params.threshold;
+
+            if !quorum || vetoed {
+                proposal.status = ProposalStatus::Rejected;
+                self.burn_deposits(id)?;
+            } else {
+                self.refund_deposits(id)?;
+                proposal.status = if !passed {
+                    ProposalStatus::Rejected
+                } else if self.execute(&proposal.content, handler).is_ok() {
+                    ProposalStatus::Passed
+                } else {
+                    ProposalStatus::Failed
+                };
+            }
+            proposal.tally = tally;
+            self.proposals.insert(id, proposal)?;
+            self.votes.remove(id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes a passed proposal with the [GovernanceAuthority] context, so
+    /// its parameter changes are authorized. Changes to the governance
+    /// parameters and to the fee parameters of the enclosing
+    /// [FeePlugin](crate::plugins::FeePlugin) are applied here, and all others
+    /// are left to the handler.
+    fn execute<H: ProposalHandler>(
+        &mut self,
+        content: &ProposalContent,
+        handler: &mut H,
+    ) -> Result<()> {
+        let _authority = Context::scope(GovernanceAuthority);
+        match content {
+            ProposalContent::Text => Ok(()),
+            ProposalContent::ParamChange(changes) => {
+                // changes are applied to copies of the parameters, which only
+                // replace them once every change has been applied
+                let mut params = self.params.clone();
+                let mut fees = fee_params().map(|fees| fees.clone());
+                let mut others = vec![];
+                for change in changes.iter() {
+                    if params.apply_change(change)? {
+                        continue;
+                    }
+                    if let Some(fees) = fees.as_mut() {
+                        if fees.apply_change(change)? {
+                            continue;
+                        }
+                    }
+                    others.push(change.clone());
+                }
+                if !others.is_empty() {
+                    handler.change_params(&others)?;
+                }
+
+                self.params = params;
+                if let (Some(current), Some(fees)) = (fee_params(), fees) {
+                    *current = fees;
+                }
+
+                Ok(())
+            }
+            ProposalContent::CommunityPoolSpend { recipient, amount } => {
+                handler.spend_community_pool(*recipient, *amount)
+            }
+            ProposalContent::SoftwareUpgrade { version } => handler.upgrade(version),
+        }
+    }
+
+    /// Tallies the votes of a proposal, returning the tally and the total
+    /// bonded stake it is measured against.
+    fn tally_votes(&self, proposal_id: u64, staking: &Staking<S>) -> Result<(TallyResult, Amount)> {
+        let mut validators = HashMap::new();
+        let mut bonded = Amount::new(0);
+        for info in staking.all_validators()? {
+            if info.in_active_set {
+                bonded = (bonded + info.amount_staked)?;
+                validators.insert(info.address, info.amount_staked);
+            }
+        }
+
+        let mut tally = TallyResult::default();
+        let votes = match self.votes.get(proposal_id)? {
+            Some(votes) => votes,
+            None => return Ok((tally, bonded)),
+        };
+
+        // delegators who voted count their own delegations, which no longer
+        // count towards their validators' votes
+        let mut validator_votes = vec![];
+        let mut overridden: HashMap<Address, Amount> = HashMap::new();
+        for entry in votes.iter()? {
+            let (voter, option) = entry?;
+            for (validator, delegation) in staking.delegations(*voter)? {
+                if !validators.contains_key(&validator) {
+                    continue;
+                }
+                tally.add(*option, delegation.staked)?;
+                let total = overridden.entry(validator).or_default();
+                *total = (*total + delegation.staked)?;
+            }
+            if validators.contains_key(&*voter) {
+                validator_votes.push((*voter, *option));
+            }
+        }
+
+        for (validator, option) in validator_votes {
+            let staked = validators[&validator];
+            let overridden = overridden.get(&validator).copied().unwrap_or_default();
+            if staked > overridden {
+                tally.add(option, (staked - overridden)?)?;
+            }
+        }
+
+        Ok((tally, bonded))
+    }
+
+    fn refund_deposits(&mut self, proposal_id: u64) -> Result<()> {
+        if let Some(deposits) = self.deposits.get(proposal_id)? {
+            for entry in deposits.iter()? {
+                let (depositor, amount) = entry?;
+                let coins = self.deposit_pool.take(*amount)?;
+                self.refunds
+                    .entry(*depositor)?
+                    .or_insert_default()?
+                    .give(coins)?;
+            }
+        }
+
+        self.deposits.remove(proposal_id)
+    }
+
+    fn burn_deposits(&mut self, proposal_id: u64) -> Result<()> {
+        if let Some(deposits) = self.deposits.get(proposal_id)? {
+            for entry in deposits.iter()? {
+                let (_, amount) = entry?;
+                self.deposit_pool.take(*amount)?.burn();
+            }
+        }
+
+        self.deposits.remove(proposal_id)
+    }
+
+    #[query]
+    pub fn proposal(&self, proposal_id: u64) -> Result<Option<Proposal>> {
+        Ok(self
+            .proposals
+            .get(proposal_id)?
+            .map(|proposal| (*proposal).clone()))
+    }
+
+    #[query]
+    pub fn vote_of(&self, proposal_id: u64, voter: Address) -> Result<Option<VoteOption>> {
+        Ok(match self.votes.get(proposal_id)? {
+            Some(votes) => votes.get(voter)?.map(|option| *option),
+            None => None,
+        })
+    }
+
+    /// The deposits of passed or rejected proposals which the depositor can
+    /// claim with `claim_refund`.
+    #[query]
+    pub fn refund(&self, depositor: Address) -> Result<Amount> {
+        Ok(self
+            .refunds
+            .get(depositor)?
+            .map(|refund| refund.amount)
+            .unwrap_or_default())
+    }
+
+    #[query]
+    pub fn params(&self) -> GovParams {
+        self.params.params()
+    }
+
+    fn take_funding(&mut self) -> Result<Coin<S>> {
+        let paid = self
+            .context::<Paid>()
+            .ok_or_else(|| Error::Coins("No Paid context found".into()))?;
+        paid.take(paid.balance::<S>()?)
+    }
+
+    fn signer(&mut self) -> Result<Address> {
+        self.context::<Signer>()
+            .ok_or_else(|| Error::Signer("No Signer context available".into()))?
+            .signer
+            .ok_or_else(|| Error::Coins("Unauthorized account action".into()))
+    }
+
+    fn current_seconds(&mut self) -> Result<i64> {
+        Ok(self
+            .context::<Time>()
+            .ok_or_else(|| Error::Coins("No Time context".into()))?
+            .seconds)
+    }
+}
+
+#[cfg(all(test, feature = "abci"))]
+mod tests {
+    use super::*;
+    use crate::abci::EndBlock;
+    use crate::coins::{Commission, Declaration, StakingParams};
+    use crate::collections::EntryMap;
+    use crate::context::{Context, ContextGuard};
+    use crate::plugins::Validators;
+    use serial_test::serial;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    #[derive(Default)]
+    struct Spends(Vec<(Address, Amount)>);
+
+    impl ProposalHandler for Spends {
+        fn change_params(&mut self, _changes: &[ParamChange]) -> Result<()> {
+            Err(Error::App("Unknown parameter".into()))
+        }
+
+        fn spend_community_pool(&mut self, recipient: Address, amount: Amount) -> Result<()> {
+            self.0.push((recipient, amount));
+            Ok(())
+        }
+
+        fn upgrade(&mut self, _version: &LengthVec<u8, u8>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn declare(
+        staking: &mut Staking<Simp>,
+        address: Address,
+        consensus_key: [u8; 32],
+    ) -> Result<()> {
+        staking.declare(
+            address,
+            Declaration {
+                consensus_key,
+                commission: Commission {
+                    rate: Decimal::zero(),
+                    max: Decimal::one(),
+                    max_change: "0.1".parse()?,
+                },
+                amount: 50.into(),
+                min_self_delegation: 1.into(),
+                validator_info: vec![].try_into()?,
+            },
+            Simp::mint(50),
+        )
+    }
+
+    type Guards = (ContextGuard<Signer>, ContextGuard<Time>, ContextGuard<Paid>);
+
+    fn setup(signer: Address, time: i64, funding: u64) -> Result<Guards> {
+        let mut paid = Paid::default();
+        paid.give::<Simp, _>(funding)?;
+
+        Ok((
+            Context::scope(Signer {
+                signer: Some(signer),
+            }),
+            Context::scope(Time::from_seconds(time)),
+            Context::scope(paid),
+        ))
+    }
+
+    fn title() -> LengthVec<u16, u8> {
+        b"title".to_vec().try_into().unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn proposal_lifecycle() -> Result<()> {
+        let alice = Address::from_pubkey([0; 33]);
+        let bob = Address::from_pubkey([1; 33]);
+        let carol = Address::from_pubkey([2; 33]);
+        let dave = Address::from_pubkey([3; 33]);
+
+        let _validators = Context::scope(Validators::new(
+            Rc::new(RefCell::new(Some(EntryMap::new()))),
+            Rc::new(RefCell::new(Some(Default::default()))),
+        ));
+        let time = Context::scope(Time::from_seconds(0));
+        let mut staking: Staking<Simp> = Default::default();
+        staking.set_params(StakingParams {
+            max_validators: 100,
+            min_self_delegation_min: 1,
+            ..Default::default()
+        })?;
+        declare(&mut staking, alice, [4; 32])?;
+        declare(&mut staking, bob, [5; 32])?;
+        staking.delegate(alice, carol, Simp::mint(20))?;
+        staking.end_block(&Default::default())?;
+        drop(time);
+
+        let mut gov: Governance<Simp> = Governance::default();
+        gov.set_params(GovParams {
+            min_deposit: 100.into(),
+            max_deposit_period_seconds: 10,
+            voting_period_seconds: 10,
+            quorum: "0.334".parse()?,
+            threshold: "0.5".parse()?,
+            veto_threshold: "0.334".parse()?,
+        })?;
+        let mut handler = Spends::default();
+
+        {
+            let _ctx = setup(carol, 0, 100)?;
+            gov.submit_proposal(
+                title(),
+                title(),
+                ProposalContent::CommunityPoolSpend {
+                    recipient: dave,
+                    amount: 5.into(),
+                },
+            )?;
+        }
+        {
+            let _ctx = setup(dave, 0, 10)?;
+            gov.submit_proposal(title(), title(), ProposalContent::Text)?;
+        }
+        assert_eq!(
+            gov.proposal(0)?.unwrap().status(),
+            ProposalStatus::VotingPeriod
+        );
+        assert_eq!(
+            gov.proposal(1)?.unwrap().status(),
+            ProposalStatus::DepositPeriod
+        );
+        assert!(gov.vote(1, VoteOption::Yes).is_err());
+
+        // carol's vote overrides alice's vote for her delegation
+        {
+            let _ctx = setup(alice, 1, 0)?;
+            gov.vote(0, VoteOption::Yes)?;
+        }
+        {
+            let _ctx = setup(carol, 1, 0)?;
+            gov.vote(0, VoteOption::No)?;
+        }
+        {
+            let _ctx = setup(carol, 5, 0)?;
+            gov.end_block_step(&staking, &mut handler)?;
+            assert!(handler.0.is_empty());
+        }
+
+        let _ctx = setup(carol, 10, 0)?;
+        assert!(gov.vote(0, VoteOption::Yes).is_err());
+        gov.end_block_step(&staking, &mut handler)?;
+        let proposal = gov.proposal(0)?.unwrap();
+        assert_eq!(proposal.status(), ProposalStatus::Passed);
+        assert_eq!(proposal.tally().yes, 50.into());
+        assert_eq!(proposal.tally().no, 20.into());
+        assert_eq!(handler.0, vec![(dave, 5.into())]);
+        assert_eq!(gov.proposal(1)?.unwrap().status(), ProposalStatus::Rejected);
+
+        // deposits of passed proposals are refunded, expired ones are burned
+        assert_eq!(gov.refund(carol)?, 100.into());
+        assert_eq!(gov.refund(dave)?, 0.into());
+        gov.claim_refund()?;
+        assert_eq!(
+            Context::resolve::<Paid>().unwrap().balance::<Simp>()?,
+            100.into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn param_change_atomic() -> Result<()> {
+        let mut gov: Governance<Simp> = Governance::default();
+        let voting_period = gov.params().voting_period_seconds;
+        let change = |key: &str, value: Vec<u8>| -> Result<ParamChange> {
+            Ok(ParamChange {
+                key: key.as_bytes().to_vec().try_into()?,
+                value: value.try_into()?,
+            })
+        };
+        let gov_change = change("gov/voting_period_seconds", 20i64.encode()?)?;
+
+        // the handler rejects the unknown change, so the gov change is dropped
+        let content = ProposalContent::ParamChange(
+            vec![gov_change.clone(), change("mint/unknown", vec![])?].try_into()?,
+        );
+        assert!(gov.execute(&content, &mut Spends::default()).is_err());
+        assert_eq!(gov.params().voting_period_seconds, voting_period);
+
+        let content = ProposalContent::ParamChange(vec![gov_change].try_into()?);
+        gov.execute(&content, &mut Spends::default())?;
+        assert_eq!(gov.params().voting_period_seconds, 20);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+//! Display metadata for denominations.
+//!
+//! Coins are identified on-chain by a [Symbol]'s index or, for coins received
+//! over IBC, by their denom trace path (e.g. `transfer/channel-0/uatom`). The
+//! [DenomRegistry] maps these to [DenomMetadata] describing how wallets should
+//! display them, in the same shape as the cosmos-sdk bank module's metadata.
+//! IBC denoms are registered under their `ibc/<HASH>` denom, and can be looked
+//! up either by that denom or by their trace path.
+
+use super::Symbol;
+use crate::call::Call;
+use crate::client::Client;
+use crate::collections::Map;
+use crate::encoding::{Decode, Encode, LengthVec, Terminated};
+use crate::migrate::{migrate_from_self_impl, MigrateFrom};
+use crate::query::Query;
+use crate::state::{state_impl, State};
+use crate::store::Store;
+use crate::{Error, Result};
+use sha2::{Digest, Sha256};
+
+pub type Denom = LengthVec<u8, u8>;
+
+/// Returns the `ibc/<HASH>` denom of an IBC denom trace path, as defined by
+/// ICS-20: the uppercase hex SHA-256 hash of the path.
+pub fn ibc_denom(trace_path: &str) -> String {
+    format!(
+        "ibc/{}",
+        hex::encode_upper(Sha256::digest(trace_path.as_bytes()))
+    )
+}
+
+fn denom(value: &str) -> Result<Denom> {
+    value.as_bytes().to_vec().try_into()
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|_| Error::Coins("Denom metadata must be UTF-8".into()))
+}
+
+/// A unit a denomination can be displayed in, worth `10^exponent` of the
+/// base denom.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct DenomUnit {
+    pub denom: Denom,
+    pub exponent: u32,
+}
+
+impl Terminated for DenomUnit {}
+state_impl!(DenomUnit);
+migrate_from_self_impl!(DenomUnit);
+
+impl DenomUnit {
+    pub fn new(denom: &str, exponent: u32) -> Result<Self> {
+        Ok(Self {
+            denom: self::denom(denom)?,
+            exponent,
+        })
+    }
+}
+
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct DenomMetadata {
+    pub description: LengthVec<u16, u8>,
+    /// The units of the denomination, starting with the base denom at
+    /// exponent 0, in increasing order of exponent.
+    pub denom_units: LengthVec<u8, DenomUnit>,
+    pub base: Denom,
+    /// The unit amounts are usually displayed in, e.g. `atom` for `uatom`.
+    pub display: Denom,
+    pub name: Denom,
+    pub symbol: Denom,
+}
+
+impl Terminated for DenomMetadata {}
+state_impl!(DenomMetadata);
+migrate_from_self_impl!(DenomMetadata);
+
+impl DenomMetadata {
+    /// Creates metadata for a denomination displayed as `display`, which is
+    /// worth `10^exponent` of `base`. The name and symbol default to the
+    /// display denom, and the description is empty.
+    pub fn new(base: &str, display: &str, exponent: u32) -> Result<Self> {
+        let mut denom_units = vec![DenomUnit::new(base, 0)?];
+        if display != base {
+            denom_units.push(DenomUnit::new(display, exponent)?);
+        }
+
+        Ok(Self {
+            description: vec![].try_into()?,
+            denom_units: denom_units.try_into()?,
+            base: denom(base)?,
+            display: denom(display)?,
+            name: denom(display)?,
+            symbol: denom(&display.to_uppercase())?,
+        })
+    }
+
+    /// The default metadata of a denom received over IBC, which can only be
+    /// displayed in its base `ibc/<HASH>` unit.
+    pub fn ibc(trace_path: &str) -> Result<Self> {
+        let base = ibc_denom(trace_path);
+        let base_denom = trace_path.rsplit('/').next().unwrap_or(trace_path);
+
+        Ok(Self {
+            description: format!("IBC token from {}", trace_path)
+                .into_bytes()
+                .try_into()?,
+            denom_units: vec![DenomUnit::new(&base, 0)?].try_into()?,
+            base: denom(&base)?,
+            display: denom(&base)?,
+            name: denom(&format!("{} IBC token", trace_path))?,
+            symbol: denom(&base_denom.to_uppercase())?,
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for text in [&self.base, &self.display, &self.name, &self.symbol] {
+            if utf8(text)?.trim().is_empty() {
+                return Err(Error::Coins(
+                    "Denom metadata fields must not be empty".into(),
+                ));
+            }
+        }
+        utf8(&self.description)?;
+
+        match self.denom_units.first() {
+            Some(unit) if unit.denom == self.base && unit.exponent == 0 => {}
+            _ => {
+                return Err(Error::Coins(
+                    "First denom unit must be the base denom with exponent 0".into(),
+                ))
+            }
+        }
+        for pair in self.denom_units.windows(2) {
+            utf8(&pair[1].denom)?;
+            if pair[1].exponent <= pair[0].exponent {
+                return Err(Error::Coins(
+                    "Denom units must be in increasing order of exponent".into(),
+                ));
+            }
+        }
+        if !self
+            .denom_units
+            .iter()
+            .any(|unit| unit.denom == self.display)
+        {
+            return Err(Error::Coins("Display denom must be a denom unit".into()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps denominations to their metadata. Registration is only reachable from
+/// app code, e.g. at genesis or in a migration, except for the default
+/// metadata of IBC denoms which is registered when they are first received.
+///
+/// The registry lives entirely in its store and writes no bytes to its
+/// parent's encoding, so it can be added to existing state without a
+/// migration.
+#[derive(Default, MigrateFrom, Call, Query, Client)]
+pub struct DenomRegistry {
+    metadata: Map<Denom, DenomMetadata>,
+    symbols: Map<u8, Denom>,
+    traces: Map<Denom, Denom>,
+}
+
+impl State for DenomRegistry {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        self.metadata.attach(store.sub(&[0]))?;
+        self.symbols.attach(store.sub(&[1]))?;
+        self.traces.attach(store.sub(&[2]))
+    }
+
+    fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
+        self.metadata.flush(out)?;
+        self.symbols.flush(out)?;
+        self.traces.flush(out)
+    }
+
+    fn load(store: Store, _bytes: &mut &[u8]) -> Result<Self> {
+        let mut value = Self::default();
+        value.attach(store)?;
+
+        Ok(value)
+    }
+}
+
+impl DenomRegistry {
+    /// Registers the metadata of the static symbol `S`, replacing any
+    /// previously registered for it.
+    pub fn register<S: Symbol>(&mut self, metadata: DenomMetadata) -> Result<()> {
+        metadata.validate()?;
+        if metadata.base.starts_with(b"ibc/") {
+            return Err(Error::Coins(
+                "Static symbols may not use the ibc/ denom prefix".into(),
+            ));
+        }
+
+        let prev = self.symbols.get(S::INDEX)?.map(|base| base.clone());
+        if prev.as_ref() != Some(&metadata.base)
+            && self.metadata.contains_key(metadata.base.clone())?
+        {
+            return Err(Error::Coins("Denom is already registered".into()));
+        }
+        if let Some(prev) = prev {
+            self.metadata.remove(prev)?;
+        }
+        self.symbols.insert(S::INDEX, metadata.base.clone())?;
+        self.metadata.insert(metadata.base.clone(), metadata)
+    }
+
+    /// Registers the metadata of an IBC denom by its trace path, replacing any
+    /// previously registered for it. The metadata's base must be the path's
+    /// `ibc/<HASH>` denom.
+    pub fn register_ibc(&mut self, trace_path: &str, metadata: DenomMetadata) -> Result<()> {
+        metadata.validate()?;
+        if utf8(&metadata.base)? != ibc_denom(trace_path) {
+            return Err(Error::Coins(
+                "IBC denom metadata must have the trace's ibc/ denom as its base".into(),
+            ));
+        }
+
+        self.traces
+            .insert(metadata.base.clone(), denom(trace_path)?)?;
+        self.metadata.insert(metadata.base.clone(), metadata)
+    }
+
+    /// Registers the default metadata of an IBC denom if none is registered.
+    pub fn ensure_ibc(&mut self, trace_path: &str) -> Result<()> {
+        if self.traces.contains_key(denom(&ibc_denom(trace_path))?)? {
+            return Ok(());
+        }
+
+        self.register_ibc(trace_path, DenomMetadata::ibc(trace_path)?)
+    }
+
+    /// Looks up metadata by base denom, `ibc/<HASH>` denom or IBC trace path.
+    #[query]
+    pub fn metadata(&self, denom: Denom) -> Result<Option<DenomMetadata>> {
+        if let Some(metadata) = self.metadata.get(denom.clone())? {
+            return Ok(Some(metadata.clone()));
+        }
+
+        let trace_path = utf8(&denom)?;
+        if !trace_path.contains('/') || trace_path.starts_with("ibc/") {
+            return Ok(None);
+        }
+        Ok(self
+            .metadata
+            .get(self::denom(&ibc_denom(trace_path))?)?
+            .map(|metadata| metadata.clone()))
+    }
+
+    #[query]
+    pub fn symbol_metadata(&self, index: u8) -> Result<Option<DenomMetadata>> {
+        match self.symbols.get(index)? {
+            Some(base) => Ok(self.metadata.get(base.clone())?.map(|m| m.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the trace path of an `ibc/<HASH>` denom.
+    #[query]
+    pub fn trace(&self, denom: Denom) -> Result<Option<Denom>> {
+        Ok(self.traces.get(denom)?.map(|path| path.clone()))
+    }
+
+    #[query]
+    pub fn all_metadata(&self) -> Result<Vec<DenomMetadata>> {
+        self.metadata
+            .iter()?
+            .map(|entry| {
+                let (_, metadata) = entry?;
+                Ok(metadata.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orga;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    fn lookup(registry: &DenomRegistry, denom: &str) -> Result<Option<DenomMetadata>> {
+        registry.metadata(self::denom(denom)?)
+    }
+
+    #[test]
+    fn registry() -> Result<()> {
+        let mut registry = DenomRegistry::default();
+
+        let simp = DenomMetadata::new("usimp", "simp", 6)?;
+        registry.register::<Simp>(simp.clone())?;
+        assert_eq!(registry.symbol_metadata(Simp::INDEX)?, Some(simp.clone()));
+        assert_eq!(lookup(&registry, "usimp")?, Some(simp));
+        let simp = DenomMetadata::new("usimp", "simp", 8)?;
+        registry.register::<Simp>(simp.clone())?;
+        assert_eq!(registry.symbol_metadata(Simp::INDEX)?, Some(simp));
+        assert!(registry
+            .register::<Simp>(DenomMetadata::new("ibc/ABC", "abc", 6)?)
+            .is_err());
+
+        let path = "transfer/channel-0/uatom";
+        let hash = ibc_denom(path);
+        assert_eq!(
+            hash,
+            "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+        );
+        registry.ensure_ibc(path)?;
+        let atom = lookup(&registry, &hash)?.unwrap();
+        assert_eq!(atom, DenomMetadata::ibc(path)?);
+        assert_eq!(atom.display, denom(&hash)?);
+        assert_eq!(lookup(&registry, path)?, Some(atom.clone()));
+        assert_eq!(atom.symbol.as_slice(), b"UATOM");
+        assert_eq!(registry.trace(denom(&hash)?)?, Some(denom(path)?));
+
+        let mut custom = DenomMetadata::ibc(path)?;
+        custom.denom_units =
+            vec![DenomUnit::new(&hash, 0)?, DenomUnit::new("atom", 6)?].try_into()?;
+        custom.display = denom("atom")?;
+        registry.register_ibc(path, custom.clone())?;
+        registry.ensure_ibc(path)?;
+        assert_eq!(lookup(&registry, path)?, Some(custom));
+        assert!(registry
+            .register_ibc("transfer/channel-1/uatom", DenomMetadata::ibc(path)?)
+            .is_err());
+
+        assert_eq!(registry.all_metadata()?.len(), 2);
+        assert_eq!(lookup(&registry, "transfer/channel-1/uosmo")?, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use super::{Amount, Coin, Decimal, Give, Staking, Symbol};
+use crate::context::GetContext;
+use crate::describe::Describe;
+use crate::orga;
+use crate::params::{decode_param, ParamStore, Params};
+use crate::plugins::Time;
+use crate::{Error, Result};
+use std::marker::PhantomData;
+
+/// The length of a year used for inflation, in seconds (365.25 days).
+pub const SECONDS_PER_YEAR: u64 = 31_557_600;
+
+/// Issues new coins at an annual inflation rate which adjusts toward a target
+/// ratio of bonded coins, the same way as cosmos-sdk's mint module.
+///
+/// The inflation rate increases while less than `goal_bonded` of the supply is
+/// staked, and decreases while more is staked, by up to
+/// `inflation_rate_change` per year. The app calls [Mint::mint_to_staking]
+/// once per block (e.g. in its `begin_block`) to pay the coins issued since the
+/// previous block into the staking reward pool.
+///
+/// The total supply is tracked by the module, starting from the supply given
+/// to [Mint::configure]. Apps which burn coins should report them with
+/// [Mint::burn] so issuance is based on the current supply.
+#[orga]
+pub struct Mint<S: Symbol> {
+    _symbol: PhantomData<S>,
+    configured: bool,
+    #[call]
+    pub params: ParamStore<MintParams>,
+    inflation: Decimal,
+    supply: Amount,
+    last_seconds: Option<i64>,
+    remainder: Decimal,
+}
+
+#[orga]
+#[derive(Clone, Debug, Describe)]
+pub struct MintParams {
+    /// The maximum change of the inflation rate per year.
+    pub inflation_rate_change: Decimal,
+    pub inflation_min: Decimal,
+    pub inflation_max: Decimal,
+    /// The target fraction of the supply which is staked.
+    pub goal_bonded: Decimal,
+}
+
+impl Params for MintParams {
+    const MODULE: &'static str = "mint";
+
+    fn validate(&self) -> Result<()> {
+        if self.inflation_rate_change < Decimal::zero() {
+            return Err(Error::Coins(
+                "Inflation rate change may not be negative".into(),
+            ));
+        }
+        if self.inflation_min < Decimal::zero() || self.inflation_max < self.inflation_min {
+            return Err(Error::Coins(
+                "Inflation bounds must satisfy 0 <= min <= max".into(),
+            ));
+        }
+        if self.goal_bonded <= Decimal::zero() || self.goal_bonded > Decimal::one() {
+            return Err(Error::Coins(
+                "Bonded goal must be greater than 0 and at most 1".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match key {
+            "inflation_rate_change" => self.inflation_rate_change = decode_param(value)?,
+            "inflation_min" => self.inflation_min = decode_param(value)?,
+            "inflation_max" => self.inflation_max = decode_param(value)?,
+            "goal_bonded" => self.goal_bonded = decode_param(value)?,
+            _ => return Err(Error::Coins(format!("Unknown mint parameter {}", key))),
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Symbol> Mint<S> {
+    /// Sets the parameters, starting inflation rate and current total supply.
+    pub fn configure(
+        &mut self,
+        params: MintParams,
+        inflation: Decimal,
+        supply: Amount,
+    ) -> Result<()> {
+        params.validate()?;
+        if inflation < params.inflation_min || inflation > params.inflation_max {
+            return Err(Error::Coins(
+                "Inflation must be within the inflation bounds".into(),
+            ));
+        }
+
+        self.params.set(params)?;
+        self.inflation = inflation;
+        self.supply = supply;
+        self.configured = true;
+
+        Ok(())
+    }
+
+    /// Replaces the parameters, clamping the current inflation rate to the
+    /// new bounds.
+    pub fn set_params(&mut self, params: MintParams) -> Result<()> {
+        self.params.set(params)?;
+        self.inflation = self
+            .inflation
+            .max(self.params.inflation_min)
+            .min(self.params.inflation_max);
+
+        Ok(())
+    }
+
+    /// Removes burned coins from the tracked supply.
+    pub fn burn(&mut self, amount: Amount) -> Result<()> {
+        self.supply = (self.supply - amount)?;
+
+        Ok(())
+    }
+
+    /// Adjusts the inflation rate for the time elapsed since the previous
+    /// mint and issues the coins for that time, given the amount of coins
+    /// currently staked. The first mint only records the current time.
+    pub fn mint(&mut self, bonded: Amount) -> Result<Coin<S>> {
+        if !self.configured {
+            return Err(Error::Coins(
+                "Mint must be configured before minting".into(),
+            ));
+        }
+        let now = self.current_seconds()?;
+        let last_seconds = self.last_seconds.replace(now);
+        let elapsed = match last_seconds {
+            Some(last_seconds) if now > last_seconds => (now - last_seconds) as u64,
+            _ => return Ok(0.into()),
+        };
+        if self.supply == 0 {
+            return Ok(0.into());
+        }
+        let year_fraction = (Amount::from(elapsed) / Amount::from(SECONDS_PER_YEAR))?;
+
+        let bonded_ratio = (bonded / self.supply)?;
+        let annual_change = ((Decimal::one() - (bonded_ratio / self.params.goal_bonded))?
+            * self.params.inflation_rate_change)?;
+        self.inflation = (self.inflation + annual_change * year_fraction)?
+            .max(self.params.inflation_min)
+            .min(self.params.inflation_max);
+
+        let provision = (self.inflation * self.supply * year_fraction + self.remainder)?;
+        let minted: Decimal = provision.value.floor().ok_or(Error::Overflow)?.into();
+        self.remainder = (provision - minted)?;
+
+        let minted = minted.amount()?;
+        self.supply = (self.supply + minted)?;
+
+        Ok(minted.into())
+    }
+
+    /// Mints based on the amount staked in `staking` and pays the minted
+    /// coins into its reward pool. Nothing is issued while nothing is staked,
+    /// since the reward pool can not receive coins.
+    pub fn mint_to_staking(&mut self, staking: &mut Staking<S>) -> Result<Amount> {
+        let bonded = staking.staked()?;
+        if bonded == 0 {
+            self.last_seconds = Some(self.current_seconds()?);
+            return Ok(0.into());
+        }
+
+        let coins = self.mint(bonded)?;
+        let amount = coins.amount;
+        if amount > 0 {
+            staking.give(coins)?;
+        }
+
+        Ok(amount)
+    }
+
+    /// The current annual inflation rate.
+    #[query]
+    pub fn inflation(&self) -> Decimal {
+        self.inflation
+    }
+
+    /// The coins which would be issued over a year at the current inflation
+    /// rate and supply.
+    #[query]
+    pub fn annual_provisions(&self) -> Result<Amount> {
+        (self.inflation * self.supply)?.amount()
+    }
+
+    /// The total supply of the coin, as tracked by the module.
+    #[query]
+    pub fn total_supply(&self) -> Amount {
+        self.supply
+    }
+
+    #[query]
+    pub fn params(&self) -> MintParams {
+        self.params.params()
+    }
+
+    fn current_seconds(&mut self) -> Result<i64> {
+        Ok(self
+            .context::<Time>()
+            .ok_or_else(|| Error::Coins("No Time context".into()))?
+            .seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use serial_test::serial;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    #[test]
+    #[serial]
+    fn inflation_adjustment() -> Result<()> {
+        let mut mint: Mint<Simp> = Mint::default();
+        let params = MintParams {
+            inflation_rate_change: "0.1".parse()?,
+            inflation_min: "0.05".parse()?,
+            inflation_max: "0.2".parse()?,
+            goal_bonded: "0.5".parse()?,
+        };
+        assert!(mint.mint(0.into()).is_err());
+        assert!(mint
+            .configure(params.clone(), "0.3".parse()?, 1_000_000.into())
+            .is_err());
+        mint.configure(params, "0.1".parse()?, 1_000_000.into())?;
+
+        {
+            let _time = Context::scope(Time::from_seconds(0));
+            assert_eq!(mint.mint(250_000.into())?.amount, 0.into());
+        }
+
+        // below the bonded goal, inflation increases
+        {
+            let _time = Context::scope(Time::from_seconds(SECONDS_PER_YEAR as i64 / 100));
+            assert_eq!(mint.mint(250_000.into())?.amount, 1005.into());
+            assert_eq!(mint.inflation(), "0.1005".parse::<Decimal>()?);
+            assert_eq!(mint.total_supply(), 1_001_005.into());
+        }
+
+        // above the bonded goal, inflation decreases down to the minimum
+        let _time = Context::scope(Time::from_seconds(SECONDS_PER_YEAR as i64 * 101 / 100));
+        assert_eq!(mint.mint(1_001_005.into())?.amount, 50050.into());
+        assert_eq!(mint.inflation(), "0.05".parse::<Decimal>()?);
+        assert_eq!(mint.total_supply(), 1_051_055.into());
+        assert_eq!(mint.remainder, "0.25".parse::<Decimal>()?);
+
+        // fractions of coins are carried over to the next mint
+        let _time = Context::scope(Time::from_seconds(SECONDS_PER_YEAR as i64 * 102 / 100));
+        assert_eq!(mint.mint(1_051_055.into())?.amount, 525.into());
+        assert_eq!(mint.remainder, "0.7775".parse::<Decimal>()?);
+        assert_eq!(mint.total_supply(), 1_051_580.into());
+
+        Ok(())
+    }
+}
@@ -42,12 +42,32 @@ pub use balance::*;
 pub mod decimal;
 pub use decimal::Decimal;
 
+mod fixed;
+
 pub mod math;
 pub use math::*;
 
 pub mod faucet;
 pub use faucet::*;
 
+pub mod fee_grant;
+pub use fee_grant::*;
+
+pub mod mint;
+pub use mint::*;
+
+pub mod distribution;
+pub use distribution::*;
+
+pub mod gov;
+pub use gov::*;
+
+pub mod vesting;
+pub use vesting::*;
+
+pub mod metadata;
+pub use metadata::*;
+
 mod ops;
 pub use ops::*;
 
@@ -121,6 +141,32 @@ impl Address {
         Self { bytes }
     }
 
+    /// Derives the address of an ed25519 public key the same way as
+    /// Tendermint, so an account key matches its validator address.
+    pub fn from_pubkey_ed25519(bytes: [u8; 32]) -> Self {
+        let hash = Sha256::digest(bytes);
+
+        let mut bytes = [0; Address::LENGTH];
+        bytes.copy_from_slice(&hash[..Address::LENGTH]);
+
+        Self { bytes }
+    }
+
+    /// Derives the address of a compressed secp256r1 public key, using the
+    /// cosmos-sdk (ADR-028) hash of the key type and key truncated to the
+    /// address length.
+    pub fn from_pubkey_secp256r1(bytes: [u8; 33]) -> Self {
+        let mut sha = Sha256::new();
+        sha.update(Sha256::digest(b"secp256r1"));
+        sha.update(bytes);
+        let hash = sha.finalize();
+
+        let mut bytes = [0; Address::LENGTH];
+        bytes.copy_from_slice(&hash[..Address::LENGTH]);
+
+        Self { bytes }
+    }
+
     pub fn bytes(&self) -> [u8; Address::LENGTH] {
         self.bytes
     }
@@ -9,7 +9,7 @@ impl Add<Amount> for Amount {
     fn add(self, other: Amount) -> Self::Output {
         self.value
             .checked_add(other.value)
-            .map(|value| value.into())
+            .map(Amount::new)
             .ok_or(Error::Overflow)
             .into()
     }
@@ -9,13 +9,13 @@ impl PartialEq<Amount> for Amount {
 
 impl PartialEq<Amount> for u64 {
     fn eq(&self, other: &Amount) -> bool {
-        *self == other.value
+        u128::from(*self) == other.value
     }
 }
 
 impl PartialEq<u64> for Amount {
     fn eq(&self, other: &u64) -> bool {
-        self.value == *other
+        self.value == u128::from(*other)
     }
 }
 
@@ -9,13 +9,13 @@ impl PartialOrd<Amount> for Amount {
 
 impl PartialOrd<Amount> for u64 {
     fn partial_cmp(&self, other: &Amount) -> Option<Ordering> {
-        self.partial_cmp(&other.value)
+        u128::from(*self).partial_cmp(&other.value)
     }
 }
 
 impl PartialOrd<u64> for Amount {
     fn partial_cmp(&self, other: &u64) -> Option<Ordering> {
-        self.value.partial_cmp(other)
+        self.value.partial_cmp(&u128::from(*other))
     }
 }
 
@@ -10,7 +10,7 @@ impl Sub<Amount> for Amount {
     fn sub(self, other: Amount) -> Self::Output {
         self.value
             .checked_sub(other.value)
-            .map(|value| value.into())
+            .map(Amount::new)
             .ok_or(Error::Overflow)
             .into()
     }
@@ -6,8 +6,6 @@ use crate::orga;
 use crate::plugins::Time;
 use crate::{Error, Result};
 
-use super::UNBONDING_SECONDS;
-
 #[orga]
 pub struct Unbond<S: Symbol> {
     pub(super) coins: Share<S>,
@@ -137,11 +135,11 @@ impl<S: Symbol> Delegator<S> {
         Ok(())
     }
 
-    pub(super) fn process_unbonds(&mut self) -> Result<()> {
+    pub(super) fn process_unbonds(&mut self, unbonding_seconds: u64) -> Result<()> {
         let now = self.current_seconds()?;
 
         while let Some(unbond) = self.unbonding.front()? {
-            let unbond_matured = now - unbond.start_seconds >= UNBONDING_SECONDS as i64;
+            let unbond_matured = now - unbond.start_seconds >= unbonding_seconds as i64;
             if unbond_matured {
                 let unbond = self
                     .unbonding
@@ -156,10 +154,10 @@ impl<S: Symbol> Delegator<S> {
         Ok(())
     }
 
-    pub(super) fn process_redelegations_in(&mut self) -> Result<()> {
+    pub(super) fn process_redelegations_in(&mut self, unbonding_seconds: u64) -> Result<()> {
         let now = self.current_seconds()?;
         while let Some(redelegation) = self.redelegations_in.front()? {
-            let matured = now - redelegation.start_seconds >= UNBONDING_SECONDS as i64;
+            let matured = now - redelegation.start_seconds >= unbonding_seconds as i64;
             if matured {
                 self.redelegations_in
                     .pop_front()?
@@ -172,10 +170,10 @@ impl<S: Symbol> Delegator<S> {
         Ok(())
     }
 
-    pub(super) fn process_redelegations_out(&mut self) -> Result<()> {
+    pub(super) fn process_redelegations_out(&mut self, unbonding_seconds: u64) -> Result<()> {
         let now = self.current_seconds()?;
         while let Some(redelegation) = self.redelegations_out.front()? {
-            let matured = now - redelegation.start_seconds >= UNBONDING_SECONDS as i64;
+            let matured = now - redelegation.start_seconds >= unbonding_seconds as i64;
             if matured {
                 self.redelegations_out
                     .pop_front()?
@@ -7,15 +7,18 @@ use crate::client::Client;
 use crate::collections::{Deque, Entry, EntryMap, Map};
 use crate::compat_mode;
 use crate::context::GetContext;
-use crate::encoding::{Decode, Encode, Terminated};
+use crate::describe::Describe;
+use crate::encoding::{Decode, Encode, LengthVec, Terminated};
+use crate::events::{emit, Event};
 use crate::migrate::MigrateFrom;
 use crate::orga;
+use crate::params::{authorize, decode_param, emit_param_change, ParamChange, Params};
 #[cfg(feature = "abci")]
 use crate::plugins::{BeginBlockCtx, EndBlockCtx, Validators};
 use crate::plugins::{Paid, Signer, Time};
 use crate::query::Query;
 use crate::state::State;
-use crate::store::Store;
+use crate::store::{Read, Store, Write};
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -31,40 +34,77 @@ pub use validator::*;
 
 #[cfg(test)]
 const UNBONDING_SECONDS: u64 = 10; // 10 seconds
-#[cfg(not(test))]
-const UNBONDING_SECONDS: u64 = 60 * 60 * 24 * 14; // 2 weeks
-const EDIT_INTERVAL_SECONDS: u64 = 60 * 60 * 24; // 1 day
+/// The state version which added the edit interval and parameter admin.
+const VERSION: u8 = 1;
+/// Where the edit interval and parameter admin are kept in compat mode, whose
+/// layout has no room for them. No child state uses this key.
+const COMPAT_PARAMS_KEY: &[u8] = &[0xff];
 
 #[derive(Call, Query, Default, Client, MigrateFrom)]
 pub struct Staking<S: Symbol> {
     validators: Pool<Address, Validator<S>, S>,
+    // The parameter fields were once public field calls and queries. They keep
+    // their variants, which only reach the no-op calls and queries of their
+    // types, so that the indexes of the method variants after them don't
+    // change. Parameters are only changed through `set_params` and
+    // `update_param`.
     #[call]
-    pub min_self_delegation_min: u64,
+    #[query]
+    min_self_delegation_min: u64,
     consensus_keys: Map<Address, [u8; 32]>,
     last_signed_block: Map<[u8; 20], u64>,
     validators_by_power: EntryMap<ValidatorPowerEntry>,
     last_validator_powers: Map<Address, u64>,
     #[call]
-    pub max_validators: u64,
+    #[query]
+    max_validators: u64,
     last_indexed_power: Map<Address, u64>,
     address_for_tm_hash: Map<[u8; 20], Address>,
     unbonding_seconds: u64,
     #[call]
-    pub max_offline_blocks: u64,
+    #[query]
+    max_offline_blocks: u64,
     #[call]
-    pub slash_fraction_double_sign: Decimal,
+    #[query]
+    slash_fraction_double_sign: Decimal,
     #[call]
-    pub slash_fraction_downtime: Decimal,
+    #[query]
+    slash_fraction_downtime: Decimal,
     #[call]
-    pub downtime_jail_seconds: u64,
+    #[query]
+    downtime_jail_seconds: u64,
     validator_queue: EntryMap<ValidatorQueueEntry>,
     unbonding_delegation_queue: Deque<UnbondingDelegationEntry>,
     redelegation_queue: Deque<RedelegationEntry>,
     delegation_index: Map<Address, Map<Address, ()>>,
+    edit_interval_seconds: u64,
+    param_admin: Option<Address>,
+    locked_withdrawals: Map<Address, ()>,
+    store: Store,
+}
+
+/// The parameters kept under [COMPAT_PARAMS_KEY] in compat mode.
+#[derive(Encode, Decode)]
+struct CompatParams {
+    edit_interval_seconds: u64,
+    param_admin: Option<Address>,
+}
+
+impl CompatParams {
+    fn load(store: &Store) -> Result<Self> {
+        Ok(match store.get(COMPAT_PARAMS_KEY)? {
+            Some(bytes) => Self::decode(bytes.as_slice())?,
+            None => Self {
+                edit_interval_seconds: StakingParams::default().edit_interval_seconds,
+                param_admin: None,
+            },
+        })
+    }
 }
 
 impl<S: Symbol> State for Staking<S> {
     fn attach(&mut self, store: Store) -> Result<()> {
+        self.store = store.clone();
         ::orga::state::Attacher::new(store)
             .attach_child(&mut self.validators)?
             .attach_child(&mut self.min_self_delegation_min)?
@@ -83,7 +123,10 @@ impl<S: Symbol> State for Staking<S> {
             .attach_child(&mut self.validator_queue)?
             .attach_child(&mut self.unbonding_delegation_queue)?
             .attach_child(&mut self.redelegation_queue)?
-            .attach_child(&mut self.delegation_index)?;
+            .attach_child(&mut self.delegation_index)?
+            .attach_child(&mut self.edit_interval_seconds)?
+            .attach_child(&mut self.param_admin)?
+            .attach_child(&mut self.locked_withdrawals)?;
         Ok(())
     }
 
@@ -108,10 +151,18 @@ impl<S: Symbol> State for Staking<S> {
                 .flush_child(self.last_indexed_power)?
                 .flush_child(self.address_for_tm_hash)?
                 .flush_child(self.validator_queue)?
-                .flush_child(self.delegation_index)?;
+                .flush_child(self.delegation_index)?
+                .flush_child(self.locked_withdrawals)?;
+
+            let params = CompatParams {
+                edit_interval_seconds: self.edit_interval_seconds,
+                param_admin: self.param_admin,
+            };
+            let mut store = self.store;
+            store.put(COMPAT_PARAMS_KEY.to_vec(), params.encode()?)?;
         } else {
             ::orga::state::Flusher::new(out)
-                .version(0u8)?
+                .version(VERSION)?
                 .flush_child(self.validators)?
                 .flush_child(self.min_self_delegation_min)?
                 .flush_child(self.consensus_keys)?
@@ -129,15 +180,32 @@ impl<S: Symbol> State for Staking<S> {
                 .flush_child(self.validator_queue)?
                 .flush_child(self.unbonding_delegation_queue)?
                 .flush_child(self.redelegation_queue)?
-                .flush_child(self.delegation_index)?;
+                .flush_child(self.delegation_index)?
+                .flush_child(self.edit_interval_seconds)?
+                .flush_child(self.param_admin)?
+                .flush_child(self.locked_withdrawals)?;
         }
 
         Ok(())
     }
 
     fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
-        let mut loader = ::orga::state::Loader::new(store.clone(), bytes, 0u8);
+        let version = if compat_mode() {
+            0
+        } else {
+            *bytes
+                .first()
+                .ok_or_else(|| Error::State("Unexpected EOF".into()))?
+        };
+        if version > VERSION {
+            return Err(Error::State(format!(
+                "Expected version {}, got {} for Staking",
+                VERSION, version
+            )));
+        }
+        let mut loader = ::orga::state::Loader::new(store.clone(), bytes, version);
         let mut value: Self = if compat_mode() {
+            let params = CompatParams::load(&store)?;
             Self {
                 max_validators: loader.load_child()?,
                 min_self_delegation_min: loader.load_child()?,
@@ -157,6 +225,10 @@ impl<S: Symbol> State for Staking<S> {
                 address_for_tm_hash: loader.load_child()?,
                 validator_queue: loader.load_child()?,
                 delegation_index: loader.load_child()?,
+                edit_interval_seconds: params.edit_interval_seconds,
+                param_admin: params.param_admin,
+                locked_withdrawals: loader.load_child()?,
+                store: Store::default(),
             }
         } else {
             Self {
@@ -178,6 +250,16 @@ impl<S: Symbol> State for Staking<S> {
                 unbonding_delegation_queue: loader.load_child()?,
                 redelegation_queue: loader.load_child()?,
                 delegation_index: loader.load_child()?,
+                edit_interval_seconds: match version {
+                    0 => StakingParams::default().edit_interval_seconds,
+                    _ => loader.load_child()?,
+                },
+                param_admin: match version {
+                    0 => None,
+                    _ => loader.load_child()?,
+                },
+                locked_withdrawals: loader.load_child()?,
+                store: Store::default(),
             }
         };
         value.attach(store)?;
@@ -393,6 +475,107 @@ impl<S: Symbol> BeginBlock for Staking<S> {
     }
 }
 
+/// Emitted when coins are delegated to a validator.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "delegate")]
+pub struct DelegateEvent {
+    pub validator: Address,
+    pub delegator: Address,
+    pub amount: Amount,
+}
+
+/// Emitted when a delegator begins unbonding from a validator.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "unbond")]
+pub struct UnbondEvent {
+    pub validator: Address,
+    pub delegator: Address,
+    pub amount: Amount,
+}
+
+/// Emitted when a validator is slashed, with `reason` either `downtime` or
+/// `double_sign`.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "slash")]
+pub struct SlashEvent {
+    pub validator: Address,
+    pub fraction: Decimal,
+    pub reason: String,
+}
+
+/// The parameters of [Staking], which are stored in its fields so state from
+/// before parameters could be changed by governance keeps its layout.
+#[orga(skip(Default))]
+#[derive(Clone, Debug, Describe)]
+pub struct StakingParams {
+    pub max_validators: u64,
+    pub min_self_delegation_min: u64,
+    pub unbonding_seconds: u64,
+    pub max_offline_blocks: u64,
+    pub slash_fraction_double_sign: Decimal,
+    pub slash_fraction_downtime: Decimal,
+    pub downtime_jail_seconds: u64,
+    /// The minimum time between edits of a validator.
+    pub edit_interval_seconds: u64,
+}
+
+// The edit interval defaults to that of state from before it was a
+// parameter, the other parameters are set by the app, e.g. at genesis.
+impl Default for StakingParams {
+    fn default() -> Self {
+        Self {
+            max_validators: 0,
+            min_self_delegation_min: 0,
+            unbonding_seconds: 0,
+            max_offline_blocks: 0,
+            slash_fraction_double_sign: Decimal::zero(),
+            slash_fraction_downtime: Decimal::zero(),
+            downtime_jail_seconds: 0,
+            edit_interval_seconds: 60 * 60 * 24, // 1 day
+        }
+    }
+}
+
+impl Params for StakingParams {
+    const MODULE: &'static str = "staking";
+
+    fn validate(&self) -> Result<()> {
+        if self.max_validators == 0 {
+            return Err(Error::Coins("Max validators must be positive".into()));
+        }
+        let fractions = [
+            self.slash_fraction_double_sign,
+            self.slash_fraction_downtime,
+        ];
+        if fractions
+            .iter()
+            .any(|fraction| *fraction < Decimal::zero() || *fraction > Decimal::one())
+        {
+            return Err(Error::Coins(
+                "Slash fractions must be between 0 and 1".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match key {
+            "max_validators" => self.max_validators = decode_param(value)?,
+            "min_self_delegation_min" => self.min_self_delegation_min = decode_param(value)?,
+            "unbonding_seconds" => self.unbonding_seconds = decode_param(value)?,
+            "max_offline_blocks" => self.max_offline_blocks = decode_param(value)?,
+            "slash_fraction_double_sign" => self.slash_fraction_double_sign = decode_param(value)?,
+            "slash_fraction_downtime" => self.slash_fraction_downtime = decode_param(value)?,
+            "downtime_jail_seconds" => self.downtime_jail_seconds = decode_param(value)?,
+            "edit_interval_seconds" => self.edit_interval_seconds = decode_param(value)?,
+            _ => return Err(Error::Coins(format!("Unknown staking parameter {}", key))),
+        }
+
+        Ok(())
+    }
+}
+
 impl<S: Symbol> Staking<S> {
     pub fn delegate(
         &mut self,
@@ -401,6 +584,7 @@ impl<S: Symbol> Staking<S> {
         coins: Coin<S>,
     ) -> Result<()> {
         let _ = self.consensus_key(val_address)?;
+        let amount = coins.amount;
         {
             let mut validator = self.validators.get_mut(val_address)?;
             if validator.tombstoned {
@@ -412,7 +596,13 @@ impl<S: Symbol> Staking<S> {
             delegator.add_stake(coins)?;
         }
         self.index_delegation(val_address, delegator_address)?;
-        self.update_vp(val_address)
+        self.update_vp(val_address)?;
+
+        emit(DelegateEvent {
+            validator: val_address,
+            delegator: delegator_address,
+            amount,
+        })
     }
 
     fn index_delegation(&mut self, val_address: Address, delegator_address: Address) -> Result<()> {
@@ -432,6 +622,13 @@ impl<S: Symbol> Staking<S> {
         Ok(consensus_key)
     }
 
+    /// The address of the validator with the given Tendermint address (the
+    /// truncated hash of its consensus key), as used in block headers and
+    /// commit info.
+    pub fn address_for_tm_hash(&self, hash: [u8; 20]) -> Result<Option<Address>> {
+        Ok(self.address_for_tm_hash.get(hash)?.map(|address| *address))
+    }
+
     pub fn declare(
         &mut self,
         val_address: Address,
@@ -543,10 +740,11 @@ impl<S: Symbol> Staking<S> {
                     .into(),
             ));
         }
-        if now - (EDIT_INTERVAL_SECONDS as i64) < validator.last_edited_seconds {
-            return Err(Error::Coins(
-                "Validators may only be edited once per 24 hours".into(),
-            ));
+        if now - (self.edit_interval_seconds as i64) < validator.last_edited_seconds {
+            return Err(Error::Coins(format!(
+                "Validators may only be edited once per {} seconds",
+                self.edit_interval_seconds
+            )));
         }
         validator.commission.rate = commission;
         validator.info = validator_info;
@@ -567,7 +765,13 @@ impl<S: Symbol> Staking<S> {
             validator.jail_for_seconds(self.downtime_jail_seconds)?;
             validator.slash(self.slash_fraction_downtime, true)?;
         }
-        self.update_vp(val_address)
+        self.update_vp(val_address)?;
+
+        emit(SlashEvent {
+            validator: val_address,
+            fraction: self.slash_fraction_downtime,
+            reason: "downtime".to_string(),
+        })
     }
 
     fn punish_double_sign(&mut self, val_address: Address) -> Result<()> {
@@ -585,7 +789,13 @@ impl<S: Symbol> Staking<S> {
                 delegator.slash_redelegation((multiplier * redelegation.amount)?.amount()?)?;
             }
         }
-        self.update_vp(val_address)
+        self.update_vp(val_address)?;
+
+        emit(SlashEvent {
+            validator: val_address,
+            fraction: self.slash_fraction_double_sign,
+            reason: "double_sign".to_string(),
+        })
     }
 
     fn punish_light_client_attack(&mut self, val_address: Address) -> Result<()> {
@@ -603,7 +813,7 @@ impl<S: Symbol> Staking<S> {
         let amount = amount.into();
         let mut validator = self.validators.get_mut(val_address)?;
         let mut delegator = validator.get_mut(delegator_address)?;
-        delegator.process_unbonds()?;
+        delegator.process_unbonds(self.unbonding_seconds)?;
 
         delegator.deduct(amount, denom)?;
 
@@ -616,8 +826,8 @@ impl<S: Symbol> Staking<S> {
         delegator_address: Address,
         amount: A,
     ) -> Result<()> {
+        let amount = amount.into();
         let start_seconds = {
-            let amount = amount.into();
             let now = self.current_seconds()?;
             let mut validator = self.validators.get_mut(validator_address)?;
             let start_seconds = match validator.status() {
@@ -641,7 +851,13 @@ impl<S: Symbol> Staking<S> {
                 })?;
         }
 
-        self.update_vp(validator_address)
+        self.update_vp(validator_address)?;
+
+        emit(UnbondEvent {
+            validator: validator_address,
+            delegator: delegator_address,
+            amount,
+        })
     }
 
     pub fn redelegate<A: Into<Amount>>(
@@ -719,6 +935,33 @@ impl<S: Symbol> Staking<S> {
         self.validators.get_mut(val_address)
     }
 
+    /// Locks the withdrawals of a delegator's liquid coins of the staking
+    /// denom, so they can only be taken by the app with
+    /// [Staking::withdraw_locked], e.g. to return coins delegated from a
+    /// vesting account to the account. This is not a call, so it is only
+    /// reachable from app code.
+    pub fn lock_withdrawals(&mut self, delegator_address: Address) -> Result<()> {
+        self.locked_withdrawals.insert(delegator_address, ())
+    }
+
+    /// Takes all of a delegator's liquid coins of the staking denom, including
+    /// unbonds which have matured, regardless of whether its withdrawals are
+    /// locked.
+    pub fn withdraw_locked(&mut self, delegator_address: Address) -> Result<Coin<S>> {
+        let mut total = Amount::new(0);
+        for (val_address, _) in self.delegations(delegator_address)? {
+            let mut validator = self.validators.get_mut(val_address)?;
+            let mut delegator = validator.get_mut(delegator_address)?;
+            delegator.process_unbonds(self.unbonding_seconds)?;
+
+            let amount = Balance::<S, Amount>::balance(&delegator.liquid)?;
+            delegator.deduct(amount, S::INDEX)?;
+            total = (total + amount)?;
+        }
+
+        Ok(S::mint(total))
+    }
+
     #[query]
     pub fn delegations(
         &self,
@@ -794,18 +1037,29 @@ impl<S: Symbol> Staking<S> {
     ) -> Result<()> {
         assert_positive(amount)?;
         let signer = self.signer()?;
+        if denom == S::INDEX && self.locked_withdrawals.contains_key(signer)? {
+            return Err(Error::Coins(
+                "Unbonded coins of this delegator can only be withdrawn by the app".into(),
+            ));
+        }
         self.deduct(validator_address, signer, amount, denom)?;
         self.paid()?.give_denom(amount, denom)
     }
 
+    /// Takes all liquid coins of the signer's delegations, except for coins
+    /// of the staking denom if its withdrawals are locked.
     #[call]
     pub fn claim_all(&mut self) -> Result<()> {
         let signer = self.signer()?;
+        let locked = self.locked_withdrawals.contains_key(signer)?;
         let delegations = self.delegations(signer)?;
         delegations
             .iter()
             .try_for_each(|(val_address, delegation)| {
                 for (denom, amount) in delegation.liquid.iter() {
+                    if locked && *denom == S::INDEX {
+                        continue;
+                    }
                     if *amount > 0 {
                         self.take_as_funding(*val_address, *amount, *denom)?;
                     }
@@ -854,7 +1108,7 @@ impl<S: Symbol> Staking<S> {
 
     fn update_vp(&mut self, val_address: Address) -> Result<()> {
         let mut validator = self.validators.get_mut(val_address)?;
-        let vp = validator.potential_vp()?.into();
+        let vp = validator.potential_vp()?.try_into()?;
         drop(validator);
         self.set_potential_voting_power(val_address, vp)
     }
@@ -911,7 +1165,7 @@ impl<S: Symbol> Staking<S> {
                     .ok_or_else(|| Error::Coins("Unbonding delegation queue is empty".into()))?;
                 let mut validator = self.validators.get_mut(unbond.validator_address)?;
                 let mut delegator = validator.get_mut(unbond.delegator_address)?;
-                delegator.process_unbonds()?;
+                delegator.process_unbonds(self.unbonding_seconds)?;
             } else {
                 break;
             }
@@ -937,7 +1191,7 @@ impl<S: Symbol> Staking<S> {
                         .get_mut(redelegation.src_validator_address)?;
                     let mut src_delegator =
                         src_validator.get_mut(redelegation.delegator_address)?;
-                    src_delegator.process_redelegations_out()?;
+                    src_delegator.process_redelegations_out(self.unbonding_seconds)?;
                 }
 
                 {
@@ -946,7 +1200,7 @@ impl<S: Symbol> Staking<S> {
                         .get_mut(redelegation.dst_validator_address)?;
                     let mut dst_delegator =
                         dst_validator.get_mut(redelegation.delegator_address)?;
-                    dst_delegator.process_redelegations_in()?;
+                    dst_delegator.process_redelegations_in(self.unbonding_seconds)?;
                 }
             } else {
                 break;
@@ -1088,6 +1342,81 @@ impl<S: Symbol> Staking<S> {
         Ok(())
     }
 
+    #[query]
+    pub fn params(&self) -> StakingParams {
+        StakingParams {
+            max_validators: self.max_validators,
+            min_self_delegation_min: self.min_self_delegation_min,
+            unbonding_seconds: self.unbonding_seconds,
+            max_offline_blocks: self.max_offline_blocks,
+            slash_fraction_double_sign: self.slash_fraction_double_sign,
+            slash_fraction_downtime: self.slash_fraction_downtime,
+            downtime_jail_seconds: self.downtime_jail_seconds,
+            edit_interval_seconds: self.edit_interval_seconds,
+        }
+    }
+
+    /// Replaces all of the parameters, e.g. at genesis.
+    pub fn set_params(&mut self, params: StakingParams) -> Result<()> {
+        params.validate()?;
+        self.max_validators = params.max_validators;
+        self.min_self_delegation_min = params.min_self_delegation_min;
+        self.unbonding_seconds = params.unbonding_seconds;
+        self.max_offline_blocks = params.max_offline_blocks;
+        self.slash_fraction_double_sign = params.slash_fraction_double_sign;
+        self.slash_fraction_downtime = params.slash_fraction_downtime;
+        self.downtime_jail_seconds = params.downtime_jail_seconds;
+        self.edit_interval_seconds = params.edit_interval_seconds;
+
+        Ok(())
+    }
+
+    #[query]
+    pub fn param_admin(&self) -> Option<Address> {
+        self.param_admin
+    }
+
+    /// Sets the account which may change parameters in addition to
+    /// governance, e.g. at genesis.
+    pub fn set_param_admin(&mut self, admin: Option<Address>) {
+        self.param_admin = admin;
+    }
+
+    /// Changes a single parameter, if signed by the parameter admin or
+    /// executed by governance.
+    #[call]
+    pub fn update_param(
+        &mut self,
+        key: LengthVec<u8, u8>,
+        value: LengthVec<u16, u8>,
+    ) -> Result<()> {
+        authorize(self.param_admin)?;
+        let key = std::str::from_utf8(&key)
+            .map_err(|_| Error::Coins("Parameter key must be UTF-8".into()))?;
+        self.change_param(key, &value)
+    }
+
+    /// Applies a change from a passed parameter change proposal, returning
+    /// `false` if the change is for another module.
+    pub fn apply_param_change(&mut self, change: &ParamChange) -> Result<bool> {
+        let (module, key) = change.split_key()?;
+        if module != StakingParams::MODULE {
+            return Ok(false);
+        }
+        authorize(self.param_admin)?;
+        self.change_param(key, &change.value)?;
+
+        Ok(true)
+    }
+
+    fn change_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let mut params = self.params();
+        params.set_param(key, value)?;
+        self.set_params(params)?;
+
+        emit_param_change::<StakingParams>(key)
+    }
+
     fn current_seconds(&mut self) -> Result<i64> {
         let time = self
             .context::<Time>()
@@ -364,11 +364,11 @@ fn val_size_limit() -> Result<()> {
                     max: dec!(1.0).into(),
                     max_change: dec!(0.1).into(),
                 },
-                amount: Amount::new(i as u64 * 100),
+                amount: Amount::from(i as u64 * 100),
                 min_self_delegation: 1.into(),
                 validator_info: vec![].try_into()?,
             },
-            Amount::new(i as u64 * 100).into(),
+            Amount::from(i as u64 * 100).into(),
         )?;
     }
     staking.end_block_step(&Default::default())?;
@@ -1571,3 +1571,51 @@ fn alt_coin_rewards() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[serial]
+fn compat_params_persist() -> Result<()> {
+    let admin = Address::from_pubkey([0; 33]);
+    let store = Store::default();
+    let mut staking: Staking<Simp> = Default::default();
+    staking.attach(store.clone())?;
+    staking.set_params(StakingParams {
+        max_validators: 100,
+        edit_interval_seconds: 5,
+        ..Default::default()
+    })?;
+    staking.set_param_admin(Some(admin));
+
+    crate::set_compat_mode(true);
+    let mut bytes = vec![];
+    let res = staking
+        .flush(&mut bytes)
+        .and_then(|()| Staking::<Simp>::load(store, &mut bytes.as_slice()));
+    crate::set_compat_mode(false);
+
+    let staking = res?;
+    assert_eq!(staking.params().edit_interval_seconds, 5);
+    assert_eq!(staking.param_admin(), Some(admin));
+
+    Ok(())
+}
+
+#[test]
+fn call_and_query_indexes() -> Result<()> {
+    // the variant indexes are part of signed txs and deployed clients
+    let call = staking_call::Call::MethodDelegateFromSelf(Address::NULL, 1.into(), vec![]);
+    assert_eq!(call.encode()?[0], 10);
+    let call = staking_call::Call::MethodUpdateParam(
+        LengthVec::new(0, vec![]),
+        LengthVec::new(0, vec![]),
+        vec![],
+    );
+    assert_eq!(call.encode()?[0], 15);
+
+    let query = staking_query::Query::MethodConsensusKey(Address::NULL, vec![]);
+    assert_eq!(query.encode()?[0], 7);
+    let query = staking_query::Query::MethodAllValidators(vec![]);
+    assert_eq!(query.encode()?[0], 9);
+
+    Ok(())
+}
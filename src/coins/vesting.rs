@@ -0,0 +1,437 @@
+//! Vesting accounts, whose coins unlock over time.
+//!
+//! Vesting accounts hold their coins in [Accounts] like any other account, but
+//! coins which have not vested yet can not be transferred or taken as
+//! funding. Locked coins can still be delegated with [delegate_vesting], which
+//! tracks how much of the delegation came from locked coins, the same way as
+//! cosmos-sdk's vesting accounts. Vesting delegators unbond like any other
+//! delegator, but their unbonded coins can not be taken from [Staking]
+//! directly: they are returned to the account with [withdraw_vesting], which
+//! locks them again.
+
+use super::{Accounts, Address, Amount, Staking, Symbol};
+use crate::encoding::{Decode, Encode, LengthVec, Terminated};
+use crate::orga;
+use crate::{Error, Result};
+use std::convert::TryInto;
+
+/// A tranche of a vesting schedule, vesting `amount` coins over
+/// `length_seconds` after the end of the previous tranche.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period {
+    pub length_seconds: u64,
+    pub amount: Amount,
+}
+
+impl Terminated for Period {}
+
+/// When the coins of a vesting account unlock.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// Coins unlock linearly between the start and end times.
+    Continuous {
+        start_seconds: i64,
+        end_seconds: i64,
+    },
+    /// All coins unlock at the end time.
+    Delayed { end_seconds: i64 },
+    /// Each period's coins unlock at the end of the period, with periods
+    /// following each other from the start time.
+    Periodic {
+        start_seconds: i64,
+        periods: Vec<Period>,
+    },
+}
+
+#[orga]
+pub struct VestingAccount {
+    original: Amount,
+    start_seconds: i64,
+    continuous: bool,
+    periods: LengthVec<u16, Period>,
+    delegated_vesting: Amount,
+    delegated_free: Amount,
+}
+
+/// The vesting state of an account, as reported by [Accounts::vesting].
+#[orga]
+#[derive(Debug)]
+pub struct VestingInfo {
+    pub original: Amount,
+    pub vested: Amount,
+    pub unvested: Amount,
+    /// Locked coins which are currently delegated.
+    pub delegated_vesting: Amount,
+    /// Unlocked coins which are currently delegated.
+    pub delegated_free: Amount,
+}
+
+impl VestingAccount {
+    pub fn new(original: Amount, schedule: Schedule) -> Result<Self> {
+        let (start_seconds, continuous, periods) = match schedule {
+            Schedule::Continuous {
+                start_seconds,
+                end_seconds,
+            } => {
+                let period = Period {
+                    length_seconds: seconds_between(start_seconds, end_seconds)?,
+                    amount: original,
+                };
+                (start_seconds, true, vec![period])
+            }
+            Schedule::Delayed { end_seconds } => {
+                let period = Period {
+                    length_seconds: 0,
+                    amount: original,
+                };
+                (end_seconds, false, vec![period])
+            }
+            Schedule::Periodic {
+                start_seconds,
+                periods,
+            } => (start_seconds, false, periods),
+        };
+
+        let total = periods.iter().try_fold(Amount::new(0), |total, period| {
+            (total + period.amount).result()
+        })?;
+        if total != original {
+            return Err(Error::Coins(
+                "Vesting periods must add up to the vesting amount".into(),
+            ));
+        }
+        let len = periods
+            .len()
+            .try_into()
+            .map_err(|_| Error::Coins("Too many vesting periods".into()))?;
+
+        Ok(Self {
+            original,
+            start_seconds,
+            continuous,
+            periods: LengthVec::new(len, periods),
+            delegated_vesting: 0.into(),
+            delegated_free: 0.into(),
+        })
+    }
+
+    /// The amount of coins which have vested at the given time.
+    pub fn vested(&self, now_seconds: i64) -> Result<Amount> {
+        if now_seconds < self.start_seconds {
+            return Ok(0.into());
+        }
+        let elapsed = (now_seconds - self.start_seconds) as u64;
+
+        let mut vested = Amount::new(0);
+        let mut period_start = 0u64;
+        for period in self.periods.iter() {
+            let period_end = period_start.saturating_add(period.length_seconds);
+            if elapsed >= period_end {
+                vested = (vested + period.amount)?;
+            } else {
+                if self.continuous {
+                    let partial = period
+                        .amount
+                        .value()
+                        .checked_mul((elapsed - period_start) as u128)
+                        .ok_or(Error::Overflow)?
+                        / period.length_seconds as u128;
+                    vested = (vested + Amount::new(partial))?;
+                }
+                break;
+            }
+            period_start = period_end;
+        }
+
+        Ok(vested)
+    }
+
+    /// The amount of coins which have not vested yet at the given time.
+    pub fn unvested(&self, now_seconds: i64) -> Result<Amount> {
+        (self.original - self.vested(now_seconds)?).result()
+    }
+
+    /// The amount of the account's balance which can not be spent at the given
+    /// time. Delegated locked coins are no longer part of the balance, so they
+    /// do not count towards it.
+    pub fn locked(&self, now_seconds: i64) -> Result<Amount> {
+        let unvested = self.unvested(now_seconds)?;
+        if unvested > self.delegated_vesting {
+            (unvested - self.delegated_vesting).result()
+        } else {
+            Ok(0.into())
+        }
+    }
+
+    /// Records a delegation from the account, counting it as a delegation of
+    /// locked coins up to the amount currently locked.
+    pub fn track_delegation(&mut self, now_seconds: i64, amount: Amount) -> Result<()> {
+        let from_locked = self.locked(now_seconds)?.min(amount);
+        self.delegated_vesting = (self.delegated_vesting + from_locked)?;
+        self.delegated_free = (self.delegated_free + (amount - from_locked)?)?;
+
+        Ok(())
+    }
+
+    /// Records an undelegation from the account, reducing delegated unlocked
+    /// coins first so that as many coins as possible stay spendable.
+    pub fn track_undelegation(&mut self, amount: Amount) -> Result<()> {
+        let from_free = self.delegated_free.min(amount);
+        self.delegated_free = (self.delegated_free - from_free)?;
+        let from_vesting = self.delegated_vesting.min((amount - from_free)?);
+        self.delegated_vesting = (self.delegated_vesting - from_vesting)?;
+
+        Ok(())
+    }
+
+    pub fn info(&self, now_seconds: i64) -> Result<VestingInfo> {
+        Ok(VestingInfo {
+            original: self.original,
+            vested: self.vested(now_seconds)?,
+            unvested: self.unvested(now_seconds)?,
+            delegated_vesting: self.delegated_vesting,
+            delegated_free: self.delegated_free,
+        })
+    }
+}
+
+fn seconds_between(start_seconds: i64, end_seconds: i64) -> Result<u64> {
+    if end_seconds <= start_seconds {
+        return Err(Error::Coins(
+            "Vesting end time must be after its start time".into(),
+        ));
+    }
+
+    Ok((end_seconds - start_seconds) as u64)
+}
+
+/// Delegates coins from an account to a validator, including coins which are
+/// still locked. The delegator's withdrawals from [Staking] are locked, so its
+/// unbonded coins must be returned with [withdraw_vesting].
+pub fn delegate_vesting<S: Symbol>(
+    accounts: &mut Accounts<S>,
+    staking: &mut Staking<S>,
+    delegator_address: Address,
+    validator_address: Address,
+    amount: Amount,
+) -> Result<()> {
+    let coins = accounts.take_for_delegation(delegator_address, amount)?;
+    staking.lock_withdrawals(delegator_address)?;
+    staking.delegate(validator_address, delegator_address, coins)
+}
+
+/// Returns the liquid coins of a vesting delegator's delegations (e.g.
+/// matured unbonds) to its account, where the coins which came from locked
+/// coins are locked again. Returned coins count towards the delegator's
+/// unlocked delegations first, so rewards returned while coins are still
+/// delegated stay locked until those coins are returned too.
+pub fn withdraw_vesting<S: Symbol>(
+    accounts: &mut Accounts<S>,
+    staking: &mut Staking<S>,
+    delegator_address: Address,
+) -> Result<()> {
+    let coins = staking.withdraw_locked(delegator_address)?;
+    accounts.track_undelegation(delegator_address, coins.amount)?;
+    accounts.deposit(delegator_address, coins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::plugins::{Signer, Time};
+    use serial_test::serial;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    #[test]
+    fn schedules() -> Result<()> {
+        let continuous = VestingAccount::new(
+            100.into(),
+            Schedule::Continuous {
+                start_seconds: 10,
+                end_seconds: 20,
+            },
+        )?;
+        assert_eq!(continuous.vested(5)?, 0.into());
+        assert_eq!(continuous.vested(13)?, 30.into());
+        assert_eq!(continuous.vested(25)?, 100.into());
+
+        let delayed = VestingAccount::new(100.into(), Schedule::Delayed { end_seconds: 20 })?;
+        assert_eq!(delayed.vested(19)?, 0.into());
+        assert_eq!(delayed.vested(20)?, 100.into());
+
+        let periodic = VestingAccount::new(
+            100.into(),
+            Schedule::Periodic {
+                start_seconds: 10,
+                periods: vec![
+                    Period {
+                        length_seconds: 5,
+                        amount: 40.into(),
+                    },
+                    Period {
+                        length_seconds: 5,
+                        amount: 60.into(),
+                    },
+                ],
+            },
+        )?;
+        assert_eq!(periodic.vested(14)?, 0.into());
+        assert_eq!(periodic.vested(17)?, 40.into());
+        assert_eq!(periodic.vested(20)?, 100.into());
+
+        assert!(VestingAccount::new(
+            99.into(),
+            Schedule::Periodic {
+                start_seconds: 10,
+                periods: vec![Period {
+                    length_seconds: 5,
+                    amount: 100.into(),
+                }],
+            },
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn locked_coins() -> Result<()> {
+        let owner = Address::from_pubkey([2; 33]);
+        let other = Address::from_pubkey([3; 33]);
+        let mut accounts: Accounts<Simp> = Default::default();
+        accounts.allow_transfers(true);
+        accounts.add_vesting(
+            owner,
+            100.into(),
+            Schedule::Continuous {
+                start_seconds: 0,
+                end_seconds: 100,
+            },
+        )?;
+
+        let _signer = Context::scope(Signer {
+            signer: Some(owner),
+        });
+        {
+            let _time = Context::scope(Time::from_seconds(40));
+            assert_eq!(accounts.spendable(owner)?, 40.into());
+            assert!(accounts.transfer(other, 41.into()).is_err());
+            accounts.transfer(other, 40.into())?;
+
+            // delegating locked coins makes them leave the balance without
+            // unlocking any other coins
+            let coins = accounts.take_for_delegation(owner, 30.into())?;
+            assert_eq!(coins.amount, 30.into());
+            assert_eq!(accounts.balance(owner)?, 30.into());
+            assert_eq!(accounts.spendable(owner)?, 0.into());
+            let info = accounts.vesting(owner)?.unwrap();
+            assert_eq!(info.unvested, 60.into());
+            assert_eq!(info.delegated_vesting, 30.into());
+
+            // undelegated coins are locked again when they are returned
+            accounts.track_undelegation(owner, 30.into())?;
+            accounts.deposit(owner, coins)?;
+            assert_eq!(accounts.spendable(owner)?, 0.into());
+        }
+
+        let _time = Context::scope(Time::from_seconds(100));
+        assert_eq!(accounts.spendable(owner)?, 60.into());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "abci")]
+    #[test]
+    #[serial]
+    fn vesting_delegation() -> Result<()> {
+        use crate::coins::{Commission, Decimal, Declaration, StakingParams};
+        use crate::collections::EntryMap;
+        use crate::plugins::{Paid, Validators};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let owner = Address::from_pubkey([2; 33]);
+        let validator = Address::from_pubkey([3; 33]);
+        let mut accounts: Accounts<Simp> = Default::default();
+        accounts.add_vesting(
+            owner,
+            100.into(),
+            Schedule::Continuous {
+                start_seconds: 0,
+                end_seconds: 100,
+            },
+        )?;
+
+        let _validators = Context::scope(Validators::new(
+            Rc::new(RefCell::new(Some(EntryMap::new()))),
+            Rc::new(RefCell::new(Some(Default::default()))),
+        ));
+        let _signer = Context::scope(Signer {
+            signer: Some(owner),
+        });
+        let _paid = Context::scope(Paid::default());
+        let _time = Context::scope(Time::from_seconds(40));
+        let mut staking: Staking<Simp> = Default::default();
+        staking.set_params(StakingParams {
+            max_validators: 100,
+            min_self_delegation_min: 1,
+            unbonding_seconds: 10,
+            max_offline_blocks: 100,
+            slash_fraction_double_sign: Decimal::zero(),
+            slash_fraction_downtime: Decimal::zero(),
+            downtime_jail_seconds: 0,
+            edit_interval_seconds: 0,
+        })?;
+        staking.declare(
+            validator,
+            Declaration {
+                consensus_key: [4; 32],
+                commission: Commission {
+                    rate: Decimal::zero(),
+                    max: Decimal::one(),
+                    max_change: Decimal::one(),
+                },
+                amount: 50.into(),
+                min_self_delegation: 1.into(),
+                validator_info: vec![].try_into()?,
+            },
+            Simp::mint(50),
+        )?;
+
+        delegate_vesting(&mut accounts, &mut staking, owner, validator, 30.into())?;
+        assert_eq!(accounts.balance(owner)?, 70.into());
+        assert_eq!(accounts.spendable(owner)?, 40.into());
+
+        // unbonded coins stay locked in staking until they are returned to
+        // the account, and still count as delegated until then
+        staking.unbond_self(validator, 30.into())?;
+        let _time = Context::scope(Time::from_seconds(50));
+        assert!(staking
+            .take_as_funding(validator, 30.into(), Simp::INDEX)
+            .is_err());
+        staking.claim_all()?;
+        assert_eq!(
+            Context::resolve::<Paid>().unwrap().balance::<Simp>()?,
+            0.into()
+        );
+        assert_eq!(accounts.spendable(owner)?, 50.into());
+
+        withdraw_vesting(&mut accounts, &mut staking, owner)?;
+        assert_eq!(accounts.balance(owner)?, 100.into());
+        assert_eq!(accounts.spendable(owner)?, 50.into());
+        assert_eq!(
+            accounts.vesting(owner)?.unwrap().delegated_vesting,
+            0.into()
+        );
+
+        Ok(())
+    }
+}
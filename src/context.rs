@@ -1,13 +1,66 @@
 use crate::state::State;
-use std::any::TypeId;
+use std::any::{type_name, Any, TypeId};
 use std::collections::HashMap;
-use std::mem::{transmute, ManuallyDrop};
+use std::marker::PhantomData;
 use std::sync::LazyLock;
 use std::sync::Mutex;
 
-type ContextMap = ManuallyDrop<HashMap<TypeId, Box<()>>>;
-static CONTEXT_MAP: LazyLock<Mutex<ContextMap>> =
-    LazyLock::new(|| Mutex::new(ManuallyDrop::new(HashMap::new())));
+/// A context value, along with its type name for debugging.
+struct Entry {
+    value: Box<dyn Any>,
+    type_name: &'static str,
+}
+
+// Context values are only accessed from the thread processing calls, the mutex
+// only guards the registry itself.
+unsafe impl Send for Entry {}
+
+impl Entry {
+    fn new<T: 'static>(value: T) -> Self {
+        Self {
+            value: Box::new(value),
+            type_name: type_name::<T>(),
+        }
+    }
+}
+
+/// The values which were replaced by a frame, to be restored when it ends.
+/// `None` means there was no value of the type before the frame.
+type FrameRecord = HashMap<TypeId, Option<Entry>>;
+
+#[derive(Default)]
+struct ContextStore {
+    values: HashMap<TypeId, Entry>,
+    frames: Vec<FrameRecord>,
+}
+
+impl ContextStore {
+    fn insert(&mut self, id: TypeId, entry: Option<Entry>) {
+        let prev = match entry {
+            Some(entry) => self.values.insert(id, entry),
+            None => self.values.remove(&id),
+        };
+
+        match self.frames.last_mut() {
+            Some(frame) if !frame.contains_key(&id) => {
+                frame.insert(id, prev);
+            }
+            _ => drop(prev),
+        }
+    }
+}
+
+static CONTEXT_STORE: LazyLock<Mutex<ContextStore>> =
+    LazyLock::new(|| Mutex::new(ContextStore::default()));
+
+static DEBUG_LEAKS: LazyLock<bool> = LazyLock::new(|| std::env::var("ORGA_DEBUG_CONTEXT").is_ok());
+
+/// Whether context values which outlive the ABCI request that added them
+/// should be reported, enabled by setting the `ORGA_DEBUG_CONTEXT` environment
+/// variable.
+pub fn debug_leaks() -> bool {
+    *DEBUG_LEAKS
+}
 
 pub struct Context<I> {
     _inner: I,
@@ -15,30 +68,164 @@ pub struct Context<I> {
 
 impl Context<()> {
     pub fn add<T: 'static>(ctx: T) {
-        let mut context_store = CONTEXT_MAP.lock().unwrap();
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        store.insert(TypeId::of::<T>(), Some(Entry::new(ctx)));
+    }
+
+    pub fn resolve<'a, T: 'static>() -> Option<&'a mut T> {
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        let value = store
+            .values
+            .get_mut(&TypeId::of::<T>())?
+            .value
+            .downcast_mut::<T>()?;
+
+        // Values are boxed, so references stay valid until the value is
+        // replaced or removed.
+        Some(unsafe { &mut *(value as *mut T) })
+    }
+
+    pub fn remove<T: 'static>() {
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        store.insert(TypeId::of::<T>(), None);
+    }
+
+    /// Adds a value for as long as the returned guard lives. When the guard is
+    /// dropped, the value of the type from before is restored, or the value
+    /// is removed if there was none. A guard created in a frame must be
+    /// dropped before the frame ends.
+    #[must_use]
+    pub fn scope<T: 'static>(ctx: T) -> ContextGuard<T> {
+        let mut store = CONTEXT_STORE.lock().unwrap();
         let id = TypeId::of::<T>();
-        let boxed_ctx = Box::new(ctx);
-        let raw = unsafe { transmute::<_, Box<()>>(boxed_ctx) };
-        let replaced = context_store.insert(id, raw);
-        if let Some(replaced) = replaced {
-            unsafe { transmute::<_, Box<T>>(replaced) };
+        let prev = store.values.insert(id, Entry::new(ctx));
+
+        // like any other change, the first scope of a type in a frame records
+        // the value from before the frame, which the guard restores from there
+        let depth = store.frames.len();
+        let restore = match store.frames.last_mut() {
+            Some(frame) if !frame.contains_key(&id) => {
+                frame.insert(id, prev);
+                Restore::Frame(depth)
+            }
+            _ => Restore::Value(prev),
+        };
+
+        ContextGuard {
+            restore,
+            marker: PhantomData,
         }
     }
 
-    pub fn resolve<'a, T: 'static>() -> Option<&'a mut T> {
-        let mut context_store = CONTEXT_MAP.lock().unwrap();
+    /// Starts a frame, e.g. for a nested call. When the returned frame is
+    /// dropped, every value added or removed since it started is restored to
+    /// its value from before the frame, including on early returns.
+    #[must_use]
+    pub fn frame() -> Frame {
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        store.frames.push(FrameRecord::new());
+
+        Frame {
+            depth: store.frames.len(),
+        }
+    }
+}
+
+/// Where the value from before a scope is kept until its guard is dropped.
+enum Restore {
+    /// The value itself, `None` if there was no value of the type.
+    Value(Option<Entry>),
+    /// The record of the frame at the given depth, since the scope was the
+    /// first change to the type in that frame.
+    Frame(usize),
+}
+
+/// Restores the previous value of a scoped context type when dropped.
+pub struct ContextGuard<T: 'static> {
+    restore: Restore,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> Drop for ContextGuard<T> {
+    fn drop(&mut self) {
+        let mut store = CONTEXT_STORE.lock().unwrap();
         let id = TypeId::of::<T>();
-        let boxed_ctx = context_store.get_mut(&id);
-        match boxed_ctx {
-            Some(ctx) => unsafe { Some(transmute::<_, &'a mut Box<T>>(ctx)) },
-            None => None,
+        let prev = match std::mem::replace(&mut self.restore, Restore::Value(None)) {
+            Restore::Value(prev) => prev,
+            // the value is back to the one from before the frame, so the
+            // frame no longer needs to restore it
+            Restore::Frame(depth) => {
+                match store
+                    .frames
+                    .get_mut(depth - 1)
+                    .and_then(|frame| frame.remove(&id))
+                {
+                    Some(prev) => prev,
+                    // the frame already ended and restored the value
+                    None => return,
+                }
+            }
+        };
+        match prev {
+            Some(prev) => store.values.insert(id, prev),
+            None => store.values.remove(&id),
+        };
+    }
+}
+
+/// A context frame, see [Context::frame]. Frames must end in the reverse
+/// order they were started.
+pub struct Frame {
+    depth: usize,
+}
+
+impl Frame {
+    /// The type names of the values added in this frame which are still
+    /// present, excluding the given types.
+    pub fn entries(&self, exclude: &[TypeId]) -> Vec<&'static str> {
+        let store = CONTEXT_STORE.lock().unwrap();
+        let mut names: Vec<_> = store.frames[self.depth - 1]
+            .keys()
+            .filter(|id| !exclude.contains(id))
+            .filter_map(|id| store.values.get(id))
+            .map(|entry| entry.type_name)
+            .collect();
+        names.sort_unstable();
+
+        names
+    }
+
+    /// Ends the frame, keeping the values added in it rather than restoring
+    /// the values from before it. An enclosing frame still restores them.
+    pub fn keep(self) {
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        let record = self.pop(&mut store);
+        if let Some(parent) = store.frames.last_mut() {
+            for (id, prev) in record {
+                parent.entry(id).or_insert(prev);
+            }
         }
+        std::mem::forget(self);
     }
 
-    pub fn remove<T: 'static>() {
-        let mut context_store = CONTEXT_MAP.lock().unwrap();
-        if let Some(replaced) = context_store.remove(&TypeId::of::<T>()) {
-            unsafe { transmute::<_, Box<T>>(replaced) };
+    fn pop(&self, store: &mut ContextStore) -> FrameRecord {
+        assert_eq!(
+            store.frames.len(),
+            self.depth,
+            "Context frames must end in the reverse order they were started"
+        );
+        store.frames.pop().unwrap()
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let mut store = CONTEXT_STORE.lock().unwrap();
+        for (id, prev) in self.pop(&mut store) {
+            match prev {
+                Some(prev) => store.values.insert(id, prev),
+                None => store.values.remove(&id),
+            };
         }
     }
 }
@@ -56,6 +243,7 @@ impl<S: State> GetContext for S {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serial_test::serial;
     struct ContextA {
         foo: u32,
     }
@@ -99,4 +287,97 @@ mod tests {
         let resolved_e = Context::resolve::<ContextD<Vec<i32>>>().unwrap();
         assert_eq!(resolved_e.inner, vec![1, 2, 3, 4]);
     }
+
+    struct ScopedA(u32);
+
+    struct ScopedB(u32);
+
+    #[test]
+    #[serial]
+    fn scoped_values() {
+        Context::add(ScopedA(1));
+        {
+            let _guard = Context::scope(ScopedA(2));
+            assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 2);
+        }
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 1);
+
+        Context::remove::<ScopedA>();
+        {
+            let _guard = Context::scope(ScopedA(3));
+            assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 3);
+        }
+        assert!(Context::resolve::<ScopedA>().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn frames() {
+        Context::add(ScopedA(1));
+
+        let outer = Context::frame();
+        Context::add(ScopedA(2));
+        Context::add(ScopedB(2));
+        assert_eq!(
+            outer.entries(&[TypeId::of::<ScopedA>()]),
+            vec![type_name::<ScopedB>()]
+        );
+
+        let inner = Context::frame();
+        Context::remove::<ScopedA>();
+        Context::add(ScopedB(3));
+        drop(inner);
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 2);
+        assert_eq!(Context::resolve::<ScopedB>().unwrap().0, 2);
+
+        let inner = Context::frame();
+        Context::add(ScopedA(4));
+        inner.keep();
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 4);
+
+        // the outer frame restores values kept by the inner frame
+        drop(outer);
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 1);
+        assert!(Context::resolve::<ScopedB>().is_none());
+
+        Context::remove::<ScopedA>();
+    }
+
+    #[test]
+    #[serial]
+    fn scopes_in_frames() {
+        Context::add(ScopedA(1));
+
+        let frame = Context::frame();
+        {
+            let _guard = Context::scope(ScopedA(2));
+            Context::add(ScopedA(3));
+            assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 3);
+        }
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 1);
+        assert!(frame.entries(&[]).is_empty());
+
+        Context::add(ScopedA(4));
+        {
+            let _guard = Context::scope(ScopedA(5));
+            let inner = Context::frame();
+            Context::add(ScopedA(6));
+            inner.keep();
+            assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 6);
+        }
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 4);
+
+        // the frame restores the value from before any scope or add in it
+        drop(frame);
+        assert_eq!(Context::resolve::<ScopedA>().unwrap().0, 1);
+
+        let frame = Context::frame();
+        let guard = Context::scope(ScopedB(1));
+        drop(frame);
+        assert!(Context::resolve::<ScopedB>().is_none());
+        drop(guard);
+        assert!(Context::resolve::<ScopedB>().is_none());
+
+        Context::remove::<ScopedA>();
+    }
 }
@@ -15,6 +15,7 @@ use std::{
 use wasm_bindgen::prelude::*;
 
 mod builder;
+pub mod schema;
 
 pub use crate::macros::Describe;
 pub use builder::Builder;
@@ -28,7 +29,15 @@ pub trait Describe {
 pub struct Descriptor {
     pub type_name: String,
     pub state_version: u32,
+    /// Whether the type is one of the `<Name>V<version>` structs generated by
+    /// `#[orga]`, which are exported under their unversioned name.
+    #[serde(default)]
+    pub versioned: bool,
     children: Children,
+    #[serde(default)]
+    calls: Vec<VariantDescriptor>,
+    #[serde(default)]
+    queries: Vec<VariantDescriptor>,
     #[serde(skip)]
     decode: Option<DecodeFn>,
     #[serde(skip)]
@@ -40,7 +49,10 @@ impl Debug for Descriptor {
         f.debug_struct("Descriptor")
             .field("type_name", &self.type_name)
             .field("state_version", &self.state_version)
+            .field("versioned", &self.versioned)
             .field("children", &self.children)
+            .field("calls", &self.calls)
+            .field("queries", &self.queries)
             .finish()
     }
 }
@@ -66,6 +78,55 @@ impl Descriptor {
         &self.children
     }
 
+    pub fn calls(&self) -> &[VariantDescriptor] {
+        self.calls.as_slice()
+    }
+
+    pub fn queries(&self) -> &[VariantDescriptor] {
+        self.queries.as_slice()
+    }
+
+    /// The dotted path of the method invoked by an encoded call, e.g.
+    /// `staking.delegate`, following field variants through the named
+    /// children. A no-op call's path ends with `noop`.
+    pub fn call_path(&self, call_bytes: &[u8]) -> Result<String> {
+        let mut path = vec![];
+        let mut desc = self;
+        let mut bytes = call_bytes;
+        loop {
+            let (&index, rest) = bytes
+                .split_first()
+                .ok_or_else(|| Error::App("Unexpected end of call".to_string()))?;
+            if index == 0 {
+                path.push("noop");
+                break;
+            }
+            let variant = desc
+                .calls
+                .iter()
+                .find(|variant| variant.index == index)
+                .ok_or_else(|| Error::App(format!("Unknown call variant {}", index)))?;
+            path.push(variant.name.as_str());
+
+            match variant.kind {
+                VariantKind::Method(_) => break,
+                VariantKind::Field => {
+                    desc = match &desc.children {
+                        Children::Named(children) => children
+                            .iter()
+                            .find(|child| child.name == variant.name)
+                            .map(|child| &child.desc),
+                        _ => None,
+                    }
+                    .ok_or_else(|| Error::App(format!("Unknown call field {}", variant.name)))?;
+                    bytes = rest;
+                }
+            }
+        }
+
+        Ok(path.join("."))
+    }
+
     // pub fn kv_descs(self) -> impl Iterator<Item = DynamicChild> {
     //     let (own, named) = match self.children {
     //         Children::None => (vec![], vec![]),
@@ -178,6 +239,77 @@ impl DynamicChild {
     }
 }
 
+/// A variant of the `Call` or `Query` enum generated for a type, used to build
+/// encoded calls and queries from outside of Rust.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VariantDescriptor {
+    pub name: String,
+    pub index: u8,
+    pub kind: VariantKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VariantKind {
+    /// Routes a subcall or subquery to the named child.
+    Field,
+    /// Invokes a `#[call]` or `#[query]` method with the given arguments.
+    Method(Vec<ArgDescriptor>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArgDescriptor {
+    pub name: String,
+    pub type_name: String,
+    #[serde(skip)]
+    parse: Option<ArgParseFn>,
+    #[serde(skip)]
+    render: Option<ArgRenderFn>,
+}
+
+pub type ArgParseFn = fn(&str) -> Result<Option<Vec<u8>>>;
+pub type ArgRenderFn = fn(&mut &[u8]) -> Result<String>;
+
+impl Debug for ArgDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArgDescriptor")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+impl PartialEq for ArgDescriptor {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.type_name == other.type_name
+    }
+}
+
+impl ArgDescriptor {
+    /// Parses the argument from a string and returns its encoding, or an
+    /// error if the argument's type can not be parsed from a string.
+    pub fn parse(&self, string: &str) -> Result<Vec<u8>> {
+        let parse = self
+            .parse
+            .ok_or_else(|| Error::App("Parse function is not available".to_string()))?;
+        parse(string)?.ok_or_else(|| {
+            Error::App(format!(
+                "Argument '{}' of type {} can not be parsed from a string",
+                self.name, self.type_name
+            ))
+        })
+    }
+
+    /// Decodes the argument from the front of `bytes` and renders it for
+    /// display, using its `Display` or `Debug` implementation if it has one
+    /// or else the hex of its encoding.
+    pub fn render(&self, bytes: &mut &[u8]) -> Result<String> {
+        let render = self
+            .render
+            .ok_or_else(|| Error::App("Render function is not available".to_string()))?;
+        render(bytes)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum KeyOp {
     Append(Vec<u8>),
@@ -415,10 +547,9 @@ impl<T: Encode + State + Describe + 'static> Inspect for T {
     }
 
     fn to_any(&self) -> Result<Box<dyn Any>> {
-        todo!()
-        // let bytes = self.encode()?;
-        // let cloned = Self::decode(bytes.as_slice())?;
-        // Ok(Box::new(cloned))
+        let bytes = Encode::encode(self)?;
+        let cloned = Self::load(Store::default(), &mut bytes.as_slice())?;
+        Ok(Box::new(cloned))
     }
 }
 
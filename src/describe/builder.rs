@@ -6,16 +6,20 @@ use crate::{
 use std::{any::type_name, marker::PhantomData, str::FromStr};
 
 use super::{
-    AccessFn, Children, DecodeFn, Describe, Descriptor, DynamicChild, Inspect, KeyOp, NamedChild,
-    ParseFn, Value,
+    AccessFn, ArgDescriptor, ArgParseFn, ArgRenderFn, Children, DebugWrapper, DecodeFn, Describe,
+    Descriptor, DisplayWrapper, DynamicChild, Inspect, KeyOp, MaybeDebug, MaybeDisplay, NamedChild,
+    ParseFn, Value, VariantDescriptor, VariantKind,
 };
 
 pub struct Builder {
     type_name: String,
     state_version: u32,
+    versioned: bool,
     decode: DecodeFn,
     parse: ParseFn,
     children: Option<Children>,
+    calls: Vec<VariantDescriptor>,
+    queries: Vec<VariantDescriptor>,
 }
 
 impl Builder {
@@ -23,12 +27,23 @@ impl Builder {
         Builder {
             type_name: type_name::<T>().to_string(),
             state_version: 0, // TODO
+            versioned: false,
             decode: |bytes| Ok(Value::new(T::decode(bytes)?)),
             parse: |s| maybe_from_str::<T>(s),
             children: None,
+            calls: vec![],
+            queries: vec![],
         }
     }
 
+    /// Marks the type as a struct generated by `#[orga]` for the given state
+    /// version.
+    pub fn versioned(mut self, state_version: u32) -> Self {
+        self.state_version = state_version;
+        self.versioned = true;
+        self
+    }
+
     pub fn named_child_keyop<T: Describe>(
         mut self,
         name: &'static str,
@@ -74,13 +89,45 @@ impl Builder {
         self
     }
 
+    pub fn call_field(mut self, name: &'static str, index: u8) -> Self {
+        self.calls.push(field_variant(name, index));
+        self
+    }
+
+    pub fn call_method(mut self, name: &'static str, index: u8, args: &[ArgDescriptor]) -> Self {
+        self.calls.push(method_variant(name, index, args));
+        self
+    }
+
+    pub fn query_field(mut self, name: &'static str, index: u8) -> Self {
+        self.queries.push(field_variant(name, index));
+        self
+    }
+
+    pub fn query_method(mut self, name: &'static str, index: u8, args: &[ArgDescriptor]) -> Self {
+        self.queries.push(method_variant(name, index, args));
+        self
+    }
+
+    pub fn arg<T: Encode + Decode + 'static>(name: &'static str) -> ArgDescriptor {
+        ArgDescriptor {
+            name: name.to_string(),
+            type_name: type_name::<T>().to_string(),
+            parse: Some(maybe_parse_arg::<T> as ArgParseFn),
+            render: Some(render_arg::<T> as ArgRenderFn),
+        }
+    }
+
     pub fn build(self) -> Descriptor {
         Descriptor {
             type_name: self.type_name,
             state_version: self.state_version,
+            versioned: self.versioned,
             decode: Some(self.decode),
             parse: Some(self.parse),
             children: self.children.unwrap_or_default(),
+            calls: self.calls,
+            queries: self.queries,
         }
     }
 
@@ -104,6 +151,22 @@ impl Builder {
     }
 }
 
+fn field_variant(name: &'static str, index: u8) -> VariantDescriptor {
+    VariantDescriptor {
+        name: name.to_string(),
+        index,
+        kind: VariantKind::Field,
+    }
+}
+
+fn method_variant(name: &'static str, index: u8, args: &[ArgDescriptor]) -> VariantDescriptor {
+    VariantDescriptor {
+        name: name.to_string(),
+        index,
+        kind: VariantKind::Method(args.to_vec()),
+    }
+}
+
 fn maybe_from_str<T>(s: &str) -> Result<Option<Value>> {
     FromStrWrapper::<T>::maybe_from_str(s)
 }
@@ -130,6 +193,39 @@ where
     }
 }
 
+fn maybe_parse_arg<T: Encode>(s: &str) -> Result<Option<Vec<u8>>> {
+    FromStrWrapper::<T>::maybe_parse_arg(s)
+}
+
+trait MaybeParseArg {
+    fn maybe_parse_arg(s: &str) -> Result<Option<Vec<u8>>>;
+}
+
+impl<T: Encode> MaybeParseArg for FromStrWrapper<T> {
+    default fn maybe_parse_arg(_s: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+impl<T: FromStr + Encode> MaybeParseArg for FromStrWrapper<T>
+where
+    Error: From<<T as FromStr>::Err>,
+{
+    fn maybe_parse_arg(s: &str) -> Result<Option<Vec<u8>>> {
+        Ok(Some(T::from_str(s)?.encode()?))
+    }
+}
+
+fn render_arg<T: Decode>(bytes: &mut &[u8]) -> Result<String> {
+    let start = *bytes;
+    let value = T::decode(&mut *bytes)?;
+    let encoding = &start[..start.len() - bytes.len()];
+
+    Ok(MaybeDisplay::maybe_to_string(&DisplayWrapper(&value))
+        .or_else(|| MaybeDebug::maybe_debug(&DebugWrapper(&value), false))
+        .unwrap_or_else(|| format!("0x{}", hex::encode(encoding))))
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
@@ -0,0 +1,728 @@
+//! Exports of app [Descriptor]s for use outside of Rust: a JSON Schema
+//! describing state, calls, and queries, and TypeScript definitions with
+//! helpers for encoding calls.
+
+use super::{ArgDescriptor, Children, Descriptor, VariantDescriptor, VariantKind};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+/// Builds a JSON Schema (draft-07) document for the given descriptor. Each
+/// type reachable from the root gets an entry in `definitions`, keyed by its
+/// full path, along with `<Type>Call` and `<Type>Query` entries for types
+/// which have call or query variants.
+pub fn json_schema(desc: &Descriptor) -> JsonValue {
+    let names = Names::new(desc);
+    let mut defs = Map::new();
+    add_definitions(&names, desc, &mut defs);
+    add_arg_definitions(&names, desc, &mut defs);
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": names.short_name(desc.type_name.as_str()),
+        "$ref": def_ref(names.ident(desc.type_name.as_str()).as_str()),
+        "definitions": defs,
+    })
+}
+
+fn add_definitions(names: &Names, desc: &Descriptor, defs: &mut Map<String, JsonValue>) {
+    let name = names.ident(desc.type_name.as_str());
+    if defs.contains_key(&name) {
+        return;
+    }
+
+    let schema = match desc.children() {
+        Children::None => primitive_schema(desc.type_name.as_str()),
+        Children::Named(children) => {
+            let properties: Map<_, _> = children
+                .iter()
+                .map(|child| {
+                    (
+                        child.name.clone(),
+                        json!({ "$ref": def_ref(names.ident(child.desc.type_name.as_str()).as_str()) }),
+                    )
+                })
+                .collect();
+            json!({ "type": "object", "properties": properties })
+        }
+        Children::Dynamic(child) => json!({
+            "type": "object",
+            "x-key": { "$ref": def_ref(names.ident(child.key_desc().type_name.as_str()).as_str()) },
+            "additionalProperties": {
+                "$ref": def_ref(names.ident(child.value_desc().type_name.as_str()).as_str())
+            },
+        }),
+    };
+    defs.insert(name.clone(), schema);
+
+    let child_descs: Vec<&Descriptor> = match desc.children() {
+        Children::None => vec![],
+        Children::Named(children) => children.iter().map(|child| &child.desc).collect(),
+        Children::Dynamic(child) => vec![child.key_desc(), child.value_desc()],
+    };
+    for child in child_descs.iter() {
+        add_definitions(names, child, defs);
+    }
+
+    if !desc.calls().is_empty() {
+        let schema = variants_schema(names, desc, desc.calls(), "Call");
+        defs.insert(format!("{}Call", name), schema);
+    }
+    if !desc.queries().is_empty() {
+        let schema = variants_schema(names, desc, desc.queries(), "Query");
+        defs.insert(format!("{}Query", name), schema);
+    }
+}
+
+/// Adds definitions for the types of call and query method arguments which
+/// are not part of the state, after the state types have been added.
+fn add_arg_definitions(names: &Names, desc: &Descriptor, defs: &mut Map<String, JsonValue>) {
+    for variant in desc.calls().iter().chain(desc.queries()) {
+        if let VariantKind::Method(args) = &variant.kind {
+            for arg in args {
+                defs.entry(names.ident(arg.type_name.as_str()))
+                    .or_insert_with(|| primitive_schema(arg.type_name.as_str()));
+            }
+        }
+    }
+
+    match desc.children() {
+        Children::None => {}
+        Children::Named(children) => {
+            for child in children.iter() {
+                add_arg_definitions(names, &child.desc, defs);
+            }
+        }
+        Children::Dynamic(child) => {
+            add_arg_definitions(names, child.key_desc(), defs);
+            add_arg_definitions(names, child.value_desc(), defs);
+        }
+    }
+}
+
+fn variants_schema(
+    names: &Names,
+    desc: &Descriptor,
+    variants: &[VariantDescriptor],
+    suffix: &str,
+) -> JsonValue {
+    let one_of: Vec<_> = variants
+        .iter()
+        .map(|variant| match &variant.kind {
+            VariantKind::Method(args) => {
+                let items: Vec<_> = args
+                    .iter()
+                    .map(|arg| {
+                        json!({
+                            "title": arg.name,
+                            "$ref": def_ref(names.ident(arg.type_name.as_str()).as_str()),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "type": "object",
+                    "required": ["method", "args"],
+                    "properties": {
+                        "method": { "const": variant.name },
+                        "index": { "const": variant.index },
+                        "args": {
+                            "type": "array",
+                            "items": items,
+                            "minItems": args.len(),
+                            "maxItems": args.len(),
+                        },
+                    },
+                })
+            }
+            VariantKind::Field => {
+                let child = child_type_name(desc, variant.name.as_str()).map(|type_name| {
+                    def_ref(format!("{}{}", names.ident(type_name), suffix).as_str())
+                });
+                let mut properties = Map::new();
+                properties.insert("field".into(), json!({ "const": variant.name }));
+                properties.insert("index".into(), json!({ "const": variant.index }));
+                if let Some(child) = child {
+                    properties.insert(suffix.to_lowercase(), json!({ "$ref": child }));
+                }
+                json!({
+                    "type": "object",
+                    "required": ["field"],
+                    "properties": properties,
+                })
+            }
+        })
+        .collect();
+
+    json!({ "oneOf": one_of })
+}
+
+/// Builds TypeScript definitions for the given descriptor: an interface per
+/// state type, and an `encode_<Type>_<Method>` function per call and query
+/// method which returns the encoded call or query bytes.
+pub fn typescript(desc: &Descriptor) -> String {
+    let mut out = String::new();
+    out.push_str(TS_PRELUDE);
+
+    let names = Names::new(desc);
+    let mut types = BTreeMap::new();
+    collect_types(&names, desc, &mut types);
+    for (name, desc) in types.iter() {
+        write_ts_type(&names, &mut out, name, desc);
+    }
+
+    out
+}
+
+fn collect_types<'a>(
+    names: &Names,
+    desc: &'a Descriptor,
+    types: &mut BTreeMap<String, &'a Descriptor>,
+) {
+    if let Children::None = desc.children() {
+        if desc.calls().is_empty() && desc.queries().is_empty() {
+            return;
+        }
+    }
+
+    let name = names.ident(desc.type_name.as_str());
+    if types.contains_key(&name) {
+        return;
+    }
+    types.insert(name, desc);
+
+    match desc.children() {
+        Children::None => {}
+        Children::Named(children) => {
+            for child in children.iter() {
+                collect_types(names, &child.desc, types);
+            }
+        }
+        Children::Dynamic(child) => {
+            collect_types(names, child.key_desc(), types);
+            collect_types(names, child.value_desc(), types);
+        }
+    }
+}
+
+fn write_ts_type(names: &Names, out: &mut String, name: &str, desc: &Descriptor) {
+    match desc.children() {
+        Children::None => {
+            writeln!(out, "export type {} = Uint8Array;\n", name).unwrap();
+        }
+        Children::Named(children) => {
+            writeln!(out, "export interface {} {{", name).unwrap();
+            for child in children.iter() {
+                writeln!(
+                    out,
+                    "  {}: {};",
+                    child.name,
+                    ts_type(names, child.desc.type_name.as_str(), &child.desc)
+                )
+                .unwrap();
+            }
+            writeln!(out, "}}\n").unwrap();
+        }
+        Children::Dynamic(child) => {
+            writeln!(
+                out,
+                "export type {} = Map<{}, {}>;\n",
+                name,
+                ts_type(names, child.key_desc().type_name.as_str(), child.key_desc()),
+                ts_type(
+                    names,
+                    child.value_desc().type_name.as_str(),
+                    child.value_desc()
+                ),
+            )
+            .unwrap();
+        }
+    }
+
+    write_ts_encoders(names, out, name, desc, desc.calls(), "Call");
+    write_ts_encoders(names, out, name, desc, desc.queries(), "Query");
+}
+
+fn write_ts_encoders(
+    names: &Names,
+    out: &mut String,
+    name: &str,
+    desc: &Descriptor,
+    variants: &[VariantDescriptor],
+    suffix: &str,
+) {
+    for variant in variants {
+        let fn_name = format!("encode_{}_{}{}", name, camel_case(&variant.name), suffix);
+        match &variant.kind {
+            VariantKind::Method(args) => {
+                let params: Vec<_> = args
+                    .iter()
+                    .map(|arg| format!("{}: {}", arg.name, ts_arg_type(arg)))
+                    .collect();
+                writeln!(
+                    out,
+                    "export function {}({}): Uint8Array {{",
+                    fn_name,
+                    params.join(", ")
+                )
+                .unwrap();
+                writeln!(out, "  return concat([").unwrap();
+                writeln!(out, "    Uint8Array.of({}),", variant.index).unwrap();
+                for arg in args {
+                    writeln!(out, "    {},", ts_arg_encoder(arg)).unwrap();
+                }
+                writeln!(out, "  ]);\n}}\n").unwrap();
+            }
+            VariantKind::Field => {
+                let child = child_type_name(desc, variant.name.as_str())
+                    .map(|type_name| names.ident(type_name))
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "/** Routes an encoded `{}` {} to the `{}` field. */",
+                    child,
+                    suffix.to_lowercase(),
+                    variant.name
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "export function {}(inner: Uint8Array): Uint8Array {{",
+                    fn_name
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "  return concat([Uint8Array.of({}), inner]);\n}}\n",
+                    variant.index
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+const TS_PRELUDE: &str = r#"// Generated from an orga app descriptor. Do not edit.
+
+function concat(parts: Uint8Array[]): Uint8Array {
+  const out = new Uint8Array(parts.reduce((n, p) => n + p.length, 0));
+  let offset = 0;
+  for (const part of parts) {
+    out.set(part, offset);
+    offset += part.length;
+  }
+  return out;
+}
+
+function encodeUint(value: number | bigint, bytes: number): Uint8Array {
+  const out = new Uint8Array(bytes);
+  let n = BigInt.asUintN(bytes * 8, BigInt(value));
+  for (let i = bytes - 1; i >= 0; i--) {
+    out[i] = Number(n & 0xffn);
+    n >>= 8n;
+  }
+  return out;
+}
+
+function encodeInt(value: number | bigint, bytes: number): Uint8Array {
+  return encodeUint(BigInt.asUintN(bytes * 8, BigInt(value)), bytes);
+}
+
+function encodeBool(value: boolean): Uint8Array {
+  return Uint8Array.of(value ? 1 : 0);
+}
+
+const BECH32_CHARSET = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+function bech32Polymod(values: number[]): number {
+  const gen = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+  let chk = 1;
+  for (const value of values) {
+    const top = chk >>> 25;
+    chk = ((chk & 0x1ffffff) << 5) ^ value;
+    for (let i = 0; i < 5; i++) {
+      if ((top >>> i) & 1) {
+        chk ^= gen[i];
+      }
+    }
+  }
+  return chk;
+}
+
+function encodeAddress(value: string): Uint8Array {
+  const address = value.toLowerCase();
+  const sep = address.lastIndexOf("1");
+  const hrp = address.slice(0, sep);
+  const data = [...address.slice(sep + 1)].map((c) => BECH32_CHARSET.indexOf(c));
+  const hrpValues = [...hrp].map((c) => c.charCodeAt(0) >> 5);
+  hrpValues.push(0, ...[...hrp].map((c) => c.charCodeAt(0) & 31));
+  if (
+    hrp !== "nomic" ||
+    data.length !== 38 ||
+    data.includes(-1) ||
+    bech32Polymod(hrpValues.concat(data)) !== 1
+  ) {
+    throw new Error("Address must be a bech32 address");
+  }
+
+  const out = new Uint8Array(20);
+  let acc = 0;
+  let bits = 0;
+  let i = 0;
+  for (const value of data.slice(0, -6)) {
+    acc = ((acc << 5) | value) & 0xfff;
+    bits += 5;
+    if (bits >= 8) {
+      bits -= 8;
+      out[i++] = (acc >> bits) & 0xff;
+    }
+  }
+  return out;
+}
+
+function encodeAmount(value: number | bigint): Uint8Array {
+  return concat([Uint8Array.of(1), encodeUint(value, 16)]);
+}
+
+"#;
+
+#[derive(Debug, PartialEq)]
+enum Primitive {
+    Uint(usize),
+    Int(usize),
+    Bool,
+    Address,
+    Amount,
+}
+
+fn primitive(type_name: &str) -> Option<Primitive> {
+    use Primitive::*;
+    let base = type_name.split('<').next().unwrap_or(type_name);
+    Some(match base.rsplit("::").next().unwrap_or(base) {
+        "u8" => Uint(1),
+        "u16" => Uint(2),
+        "u32" => Uint(4),
+        "u64" => Uint(8),
+        "u128" => Uint(16),
+        "i8" => Int(1),
+        "i16" => Int(2),
+        "i32" => Int(4),
+        "i64" => Int(8),
+        "i128" => Int(16),
+        "bool" => Bool,
+        "Address" if type_name.starts_with("orga::") => Address,
+        "Amount" if type_name.starts_with("orga::") => Amount,
+        _ => return None,
+    })
+}
+
+fn primitive_schema(type_name: &str) -> JsonValue {
+    match primitive(type_name) {
+        Some(Primitive::Bool) => json!({ "type": "boolean" }),
+        Some(Primitive::Uint(bytes)) | Some(Primitive::Int(bytes)) if bytes <= 4 => {
+            json!({ "type": "integer" })
+        }
+        // 128-bit values lose precision as JSON numbers in most parsers
+        Some(Primitive::Uint(16)) | Some(Primitive::Int(16)) | Some(Primitive::Amount) => {
+            json!({ "type": "string", "pattern": "^-?[0-9]+$" })
+        }
+        Some(Primitive::Uint(_)) | Some(Primitive::Int(_)) => {
+            json!({ "type": ["integer", "string"], "pattern": "^-?[0-9]+$" })
+        }
+        // addresses are serialized as bech32 strings
+        Some(Primitive::Address) => json!({
+            "type": "string",
+            "pattern": "^nomic1[02-9ac-hj-np-z]{38}$",
+        }),
+        None => json!({
+            "type": "string",
+            "contentEncoding": "base64",
+            "description": format!("Encoded {}", type_name),
+        }),
+    }
+}
+
+fn ts_type(names: &Names, type_name: &str, desc: &Descriptor) -> String {
+    match primitive(type_name) {
+        Some(Primitive::Bool) => "boolean".into(),
+        Some(Primitive::Uint(bytes)) | Some(Primitive::Int(bytes)) if bytes <= 4 => "number".into(),
+        Some(Primitive::Uint(_)) | Some(Primitive::Int(_)) | Some(Primitive::Amount) => {
+            "bigint".into()
+        }
+        Some(Primitive::Address) => "string".into(),
+        None => match desc.children() {
+            Children::None if desc.calls().is_empty() && desc.queries().is_empty() => {
+                "Uint8Array".into()
+            }
+            _ => names.ident(type_name),
+        },
+    }
+}
+
+fn ts_arg_type(arg: &ArgDescriptor) -> &'static str {
+    match primitive(arg.type_name.as_str()) {
+        Some(Primitive::Bool) => "boolean",
+        Some(Primitive::Uint(_)) | Some(Primitive::Int(_)) | Some(Primitive::Amount) => {
+            "number | bigint"
+        }
+        Some(Primitive::Address) => "string",
+        None => "Uint8Array",
+    }
+}
+
+fn ts_arg_encoder(arg: &ArgDescriptor) -> String {
+    let name = arg.name.as_str();
+    match primitive(arg.type_name.as_str()) {
+        Some(Primitive::Bool) => format!("encodeBool({})", name),
+        Some(Primitive::Uint(bytes)) => format!("encodeUint({}, {})", name, bytes),
+        Some(Primitive::Int(bytes)) => format!("encodeInt({}, {})", name, bytes),
+        Some(Primitive::Address) => format!("encodeAddress({})", name),
+        Some(Primitive::Amount) => format!("encodeAmount({})", name),
+        // pre-encoded bytes for types we don't know how to encode
+        None => name.to_string(),
+    }
+}
+
+fn child_type_name<'a>(desc: &'a Descriptor, name: &str) -> Option<&'a str> {
+    match desc.children() {
+        Children::Named(children) => children
+            .iter()
+            .find(|child| child.name == name)
+            .map(|child| child.desc.type_name.as_str()),
+        _ => None,
+    }
+}
+
+fn def_ref(name: &str) -> String {
+    format!("#/definitions/{}", name)
+}
+
+/// Names types in exports. Types are named by their full path so that types
+/// with the same name in different modules don't collide, without the version
+/// suffix of the structs generated by `#[orga]`.
+struct Names {
+    versioned: HashSet<String>,
+}
+
+impl Names {
+    fn new(desc: &Descriptor) -> Self {
+        let mut names = Names {
+            versioned: HashSet::new(),
+        };
+        names.add_versioned(desc);
+        names
+    }
+
+    fn add_versioned(&mut self, desc: &Descriptor) {
+        if desc.versioned {
+            let type_name = desc.type_name.as_str();
+            let base = type_name.split('<').next().unwrap_or(type_name);
+            self.versioned.insert(base.to_string());
+        }
+
+        match desc.children() {
+            Children::None => {}
+            Children::Named(children) => {
+                for child in children.iter() {
+                    self.add_versioned(&child.desc);
+                }
+            }
+            Children::Dynamic(child) => {
+                self.add_versioned(child.key_desc());
+                self.add_versioned(child.value_desc());
+            }
+        }
+    }
+
+    /// Strips the generic parameters from a type name, along with the
+    /// version suffix of `#[orga]` structs, e.g.
+    /// `orga::plugins::fee::FeeParamsV0` becomes `orga::plugins::fee::FeeParams`.
+    fn path<'a>(&self, type_name: &'a str) -> &'a str {
+        let base = type_name.split('<').next().unwrap_or(type_name);
+        if !self.versioned.contains(base) {
+            return base;
+        }
+        match base.rfind('V') {
+            Some(i) => &base[..i],
+            None => base,
+        }
+    }
+
+    /// The name of a type without its module path, e.g. `FeeParams`.
+    fn short_name(&self, type_name: &str) -> String {
+        let path = self.path(type_name);
+        path.rsplit("::").next().unwrap_or(path).to_string()
+    }
+
+    /// Turns a Rust type name, including generic parameters, into an
+    /// identifier usable in JSON Schema definitions and TypeScript, e.g.
+    /// `orga::collections::map::Map<orga::coins::address::Address, u64>`
+    /// becomes `orga_collections_map_Map_orga_coins_address_Address_u64`.
+    fn ident(&self, type_name: &str) -> String {
+        let mut parts = vec![];
+        let mut current = String::new();
+        for c in type_name.chars() {
+            match c {
+                '<' | '>' | ',' | ' ' | '(' | ')' | '[' | ']' | ';' | '&' => {
+                    if !current.is_empty() {
+                        parts.push(self.path(current.as_str()).to_string());
+                        current.clear();
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            parts.push(self.path(current.as_str()).to_string());
+        }
+
+        parts
+            .into_iter()
+            .map(|part| {
+                part.replace("::", "_")
+                    .replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_")
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+pub(crate) fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_idents() {
+        let names = Names {
+            versioned: ["orga::params::ParamStoreV0".to_string()].into(),
+        };
+        assert_eq!(names.ident("u64"), "u64");
+        assert_eq!(
+            names.ident("orga::collections::map::Map<orga::coins::Address, u64>"),
+            "orga_collections_map_Map_orga_coins_Address_u64"
+        );
+        assert_eq!(
+            names.ident("orga::params::ParamStoreV0<my_app::net::IPV4>"),
+            "orga_params_ParamStore_my_app_net_IPV4"
+        );
+        assert_eq!(names.short_name("orga::params::ParamStoreV0"), "ParamStore");
+        assert_ne!(
+            names.ident("orga::coins::staking::Params"),
+            names.ident("orga::coins::mint::Params")
+        );
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(primitive("u32"), Some(Primitive::Uint(4)));
+        assert_eq!(primitive("i128"), Some(Primitive::Int(16)));
+        assert_eq!(
+            primitive("orga::coins::address::Address"),
+            Some(Primitive::Address)
+        );
+        assert_eq!(
+            primitive("orga::coins::amount::Amount"),
+            Some(Primitive::Amount)
+        );
+        assert_eq!(primitive("my_app::Amount"), None);
+        assert_eq!(camel_case("claim_all"), "ClaimAll");
+    }
+
+    #[test]
+    fn wide_integers() {
+        assert_eq!(
+            primitive_schema("orga::coins::amount::Amount"),
+            json!({ "type": "string", "pattern": "^-?[0-9]+$" })
+        );
+        assert_eq!(primitive_schema("u128")["type"], "string");
+        assert_eq!(
+            primitive_schema("u64")["type"],
+            json!(["integer", "string"])
+        );
+        assert!(TS_PRELUDE.contains("concat([Uint8Array.of(1), encodeUint(value, 16)])"));
+    }
+
+    #[cfg(feature = "abci")]
+    #[test]
+    fn derived_descriptor() {
+        use crate::describe::Describe;
+        use crate::params::ParamStore;
+        use crate::plugins::FeeParams;
+
+        fn refs<'a>(value: &'a JsonValue, out: &mut Vec<&'a str>) {
+            match value {
+                JsonValue::Object(map) => {
+                    for (key, value) in map {
+                        match (key.as_str(), value.as_str()) {
+                            ("$ref", Some(reference)) => out.push(reference),
+                            _ => refs(value, out),
+                        }
+                    }
+                }
+                JsonValue::Array(values) => values.iter().for_each(|value| refs(value, out)),
+                _ => {}
+            }
+        }
+
+        let desc = ParamStore::<FeeParams>::describe();
+
+        let schema = json_schema(&desc);
+        assert_eq!(schema["title"], "ParamStore");
+        assert_eq!(
+            schema["$ref"],
+            "#/definitions/orga_params_ParamStore_orga_plugins_fee_FeeParams"
+        );
+        let defs = schema["definitions"].as_object().unwrap();
+        assert_eq!(
+            defs["orga_params_ParamStore_orga_plugins_fee_FeeParams"]["properties"]["params"]
+                ["$ref"],
+            "#/definitions/orga_plugins_fee_FeeParams"
+        );
+        assert_eq!(
+            defs["orga_coins_Address"],
+            primitive_schema("orga::coins::Address")
+        );
+        let set_admin = defs["orga_params_ParamStore_orga_plugins_fee_FeeParamsCall"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|variant| variant["properties"]["method"]["const"] == "set_admin")
+            .unwrap();
+        assert_eq!(
+            set_admin["properties"]["args"]["items"][0]["$ref"],
+            "#/definitions/orga_coins_Address"
+        );
+
+        let mut references = vec![];
+        refs(&schema, &mut references);
+        for reference in references {
+            let name = reference.trim_start_matches("#/definitions/");
+            assert!(defs.contains_key(name), "missing definition {}", name);
+        }
+
+        let ts = typescript(&desc);
+        let name = "orga_params_ParamStore_orga_plugins_fee_FeeParams";
+        assert!(ts.contains(format!("export interface {} {{", name).as_str()));
+        assert!(ts.contains("export interface orga_plugins_fee_FeeParams {"));
+        assert!(ts.contains(
+            format!(
+                "export function encode_{}_SetAdminCall(admin: string): Uint8Array {{",
+                name
+            )
+            .as_str()
+        ));
+        assert!(ts.contains("    encodeAddress(admin),"));
+    }
+}
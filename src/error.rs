@@ -10,10 +10,20 @@ pub enum Error {
     ABCI2(#[from] abci2::Error),
     #[error("App Error: {0}")]
     App(String),
+    #[error("Authorization Error: {0}")]
+    Authorization(String),
+    #[error(transparent)]
+    Bech32(#[from] bech32::Error),
     #[error("Call Error: {0}")]
     Call(String),
     #[error("Client Error: {0}")]
     Client(String),
+    #[error("{log}")]
+    Coded {
+        codespace: String,
+        code: u32,
+        log: String,
+    },
     #[error("Coins Error: {0}")]
     Coins(String),
     #[error(transparent)]
@@ -74,5 +84,215 @@ pub enum Error {
     Unknown,
 }
 
+impl From<std::convert::Infallible> for Error {
+    fn from(err: std::convert::Infallible) -> Self {
+        match err {}
+    }
+}
+
 /// A result type bound to the standard orga error type.
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// The codespace of errors defined by orga.
+pub const ORGA_CODESPACE: &str = "orga";
+
+/// An app-defined error which maps to a stable numeric code within the app's
+/// codespace. Convert it into an orga [Error] with [Error::app] to return it
+/// from a call, query, or ABCI handler.
+pub trait AppError: std::error::Error {
+    const CODESPACE: &'static str;
+
+    /// The code of the error, which must not be 0 since that signals success.
+    /// Errors with code 0 are reported with the code of [Error::Unknown] in
+    /// the orga codespace instead.
+    fn code(&self) -> u32;
+}
+
+impl Error {
+    pub fn app<E: AppError>(err: E) -> Self {
+        Error::Coded {
+            codespace: E::CODESPACE.to_string(),
+            code: err.code(),
+            log: err.to_string(),
+        }
+    }
+
+    pub fn codespace(&self) -> &str {
+        match self {
+            Error::Coded { code: 0, .. } => ORGA_CODESPACE,
+            Error::Coded { codespace, .. } => codespace.as_str(),
+            _ => ORGA_CODESPACE,
+        }
+    }
+
+    /// The stable numeric code for the error, used in ABCI responses. Codes of
+    /// existing variants must never change. A code of 0 signals success
+    /// rather than an error, so [Error::Coded] errors with code 0 are reported
+    /// with the code of [Error::Unknown] in the orga codespace.
+    pub fn code(&self) -> u32 {
+        match self {
+            #[cfg(feature = "abci")]
+            Error::ABCI(_) => 1,
+            #[cfg(feature = "abci")]
+            Error::ABCI2(_) => 2,
+            Error::App(_) => 3,
+            Error::Call(_) => 4,
+            Error::Client(_) => 5,
+            Error::Coins(_) => 6,
+            Error::Dalek(_) => 7,
+            Error::Decimal(_) => 8,
+            Error::DivideByZero => 9,
+            Error::Downcast(_) => 10,
+            Error::Ed(_) => 11,
+            Error::Ibc(_) => 12,
+            Error::InvalidID => 13,
+            Error::IO(_) => 14,
+            #[cfg(feature = "merk")]
+            Error::Merk(_) => 15,
+            Error::Migrate(_) => 16,
+            Error::Nonce(_) => 17,
+            Error::Overflow => 18,
+            Error::ParseInt(_) => 19,
+            Error::Poison(_) => 20,
+            Error::Tendermint(_) => 21,
+            #[cfg(feature = "abci")]
+            Error::TendermintRPC(_) => 22,
+            #[cfg(feature = "merk-full")]
+            Error::RocksDB(_) => 23,
+            Error::Secp256k1(_) => 24,
+            Error::SerdeJson(_) => 25,
+            Error::Signer(_) => 26,
+            Error::Store(_) => 27,
+            Error::State(_) => 28,
+            Error::Test(_) => 29,
+            Error::Query(_) => 30,
+            #[cfg(feature = "abci")]
+            Error::Upgrade(_) => 31,
+            Error::Unknown => 32,
+            Error::Bech32(_) => 33,
+            Error::Authorization(_) => 34,
+            Error::Coded { code: 0, .. } => Error::Unknown.code(),
+            Error::Coded { code, .. } => *code,
+        }
+    }
+
+    /// Reconstructs an error from the codespace, code, and log of an ABCI
+    /// response. Errors which can not be rebuilt as their original variant,
+    /// e.g. because they wrap another error type, are returned as
+    /// [Error::Coded].
+    pub fn from_code(codespace: &str, code: u32, log: &str) -> Self {
+        let coded = || Error::Coded {
+            codespace: codespace.to_string(),
+            code,
+            log: log.to_string(),
+        };
+        if codespace != ORGA_CODESPACE {
+            return coded();
+        }
+
+        let msg = |prefix: &str| log.strip_prefix(prefix).unwrap_or(log).to_string();
+        match code {
+            #[cfg(feature = "abci")]
+            1 => Error::ABCI(msg("ABCI Error: ")),
+            3 => Error::App(msg("App Error: ")),
+            4 => Error::Call(msg("Call Error: ")),
+            5 => Error::Client(msg("Client Error: ")),
+            6 => Error::Coins(msg("Coins Error: ")),
+            9 => Error::DivideByZero,
+            10 => Error::Downcast(msg("Downcast Error: ")),
+            12 => Error::Ibc(msg("Ibc Error: ")),
+            13 => Error::InvalidID,
+            16 => Error::Migrate(msg("Migration Error: ")),
+            17 => Error::Nonce(msg("Nonce Error: ")),
+            18 => Error::Overflow,
+            20 => Error::Poison(msg("Poison Error: ")),
+            21 => Error::Tendermint(msg("Tendermint Error: ")),
+            26 => Error::Signer(msg("Signer Error: ")),
+            27 => Error::Store(msg("Store Error: ")),
+            28 => Error::State(msg("State Error: ")),
+            29 => Error::Test(msg("Test Error: ")),
+            30 => Error::Query(msg("Query Error: ")),
+            32 => Error::Unknown,
+            34 => Error::Authorization(msg("Authorization Error: ")),
+            _ => coded(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("Not enough widgets")]
+    struct NotEnoughWidgets;
+
+    impl AppError for NotEnoughWidgets {
+        const CODESPACE: &'static str = "widgets";
+
+        fn code(&self) -> u32 {
+            7
+        }
+    }
+
+    #[test]
+    fn code_roundtrip() {
+        let err = Error::Nonce("Nonce is not valid".to_string());
+        let rebuilt = Error::from_code(err.codespace(), err.code(), &err.to_string());
+        assert!(matches!(rebuilt, Error::Nonce(msg) if msg == "Nonce is not valid"));
+
+        let err = Error::Overflow;
+        let rebuilt = Error::from_code(err.codespace(), err.code(), &err.to_string());
+        assert!(matches!(rebuilt, Error::Overflow));
+
+        let err = Error::IO(std::io::Error::new(std::io::ErrorKind::Other, "oops"));
+        let rebuilt = Error::from_code(err.codespace(), err.code(), &err.to_string());
+        assert!(matches!(rebuilt, Error::Coded { code: 14, .. }));
+    }
+
+    #[test]
+    fn app_error() {
+        let err = Error::app(NotEnoughWidgets);
+        assert_eq!(err.codespace(), "widgets");
+        assert_eq!(err.code(), 7);
+
+        let rebuilt = Error::from_code("widgets", 7, &err.to_string());
+        assert!(matches!(
+            rebuilt,
+            Error::Coded { codespace, code: 7, log } if codespace == "widgets" && log == "Not enough widgets"
+        ));
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("Widget factory exploded")]
+    struct ZeroCode;
+
+    impl AppError for ZeroCode {
+        const CODESPACE: &'static str = "widgets";
+
+        fn code(&self) -> u32 {
+            0
+        }
+    }
+
+    #[test]
+    fn zero_code() {
+        let err = Error::app(ZeroCode);
+        assert_eq!(err.codespace(), ORGA_CODESPACE);
+        assert_eq!(err.code(), Error::Unknown.code());
+        assert_eq!(err.to_string(), "Widget factory exploded");
+
+        let rebuilt = Error::from_code("widgets", 0, "Widget factory exploded");
+        assert_eq!(rebuilt.codespace(), ORGA_CODESPACE);
+        assert_ne!(rebuilt.code(), 0);
+        assert_eq!(rebuilt.to_string(), "Widget factory exploded");
+
+        let err = Error::Coded {
+            codespace: "widgets".to_string(),
+            code: 0,
+            log: "Widget factory exploded".to_string(),
+        };
+        assert_eq!(err.codespace(), ORGA_CODESPACE);
+        assert_eq!(err.code(), Error::Unknown.code());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Error, Result};
+use std::str::FromStr;
+
+pub use crate::macros::Event;
+
+/// A typed event which can be emitted from a call and decoded back from the
+/// events of a transaction result.
+///
+/// Each field becomes an attribute of the ABCI event, using its `Display`
+/// implementation for the value and its `FromStr` implementation when
+/// decoding.
+pub trait Event: Sized {
+    /// The ABCI event type. When derived, this defaults to the snake-cased
+    /// name of the type and can be set with `#[event(name = "...")]`.
+    const TYPE: &'static str;
+
+    fn attributes(&self) -> Vec<(String, String)>;
+
+    fn from_attributes(attributes: &[(String, String)]) -> Result<Self>;
+}
+
+pub fn parse_attribute<T: FromStr>(attributes: &[(String, String)], key: &str) -> Result<T>
+where
+    Error: From<T::Err>,
+{
+    let value = attributes
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v)
+        .ok_or_else(|| Error::App(format!("Event is missing attribute '{}'", key)))?;
+
+    Ok(value.parse()?)
+}
+
+/// Emits an event for the transaction, BeginBlock or EndBlock currently being
+/// processed. Events are only included in the response if the request
+/// succeeds, and are ignored outside of ABCI requests.
+pub fn emit<E: Event>(event: E) -> Result<()> {
+    #[cfg(feature = "abci")]
+    if let Some(events) = crate::context::Context::resolve::<crate::plugins::Events>() {
+        events.add(to_abci(&event));
+    }
+
+    #[cfg(not(feature = "abci"))]
+    let _ = event;
+
+    Ok(())
+}
+
+#[cfg(feature = "abci")]
+pub fn to_abci<E: Event>(event: &E) -> tendermint_proto::abci::Event {
+    use tendermint_proto::abci::EventAttribute;
+
+    tendermint_proto::abci::Event {
+        r#type: E::TYPE.to_string(),
+        attributes: event
+            .attributes()
+            .into_iter()
+            .map(|(key, value)| EventAttribute {
+                key: key.as_str().into(),
+                value: value.as_str().into(),
+                index: true,
+            })
+            .collect(),
+    }
+}
+
+/// Decodes all events of type `E` from a list of events, e.g. the
+/// `deliver_tx.events` of a [TxResponse](crate::abci::tendermint_client::TxResponse).
+#[cfg(feature = "abci")]
+pub fn decode_events<E: Event>(events: &[tendermint::abci::Event]) -> Result<Vec<E>> {
+    events
+        .iter()
+        .filter(|event| event.type_str == E::TYPE)
+        .map(|event| {
+            let attributes: Vec<_> = event
+                .attributes
+                .iter()
+                .map(|tag| (tag.key.as_ref().to_string(), tag.value.as_ref().to_string()))
+                .collect();
+            E::from_attributes(attributes.as_slice())
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "abci"))]
+mod tests {
+    use super::*;
+    use crate::coins::{Address, Amount};
+
+    #[derive(Event, Debug, PartialEq)]
+    struct Transfer {
+        to: Address,
+        amount: Amount,
+        memo: String,
+    }
+
+    #[test]
+    fn attributes_roundtrip() {
+        let event = Transfer {
+            to: Address::from_pubkey([2; 33]),
+            amount: 100.into(),
+            memo: "rent".to_string(),
+        };
+        assert_eq!(Transfer::TYPE, "transfer");
+
+        let attributes = event.attributes();
+        assert_eq!(attributes[1], ("amount".to_string(), "100".to_string()));
+        assert_eq!(
+            Transfer::from_attributes(attributes.as_slice()).unwrap(),
+            event
+        );
+
+        assert!(Transfer::from_attributes(&attributes[..1]).is_err());
+    }
+}
@@ -1,11 +1,12 @@
 use ibc_proto::cosmos::{
     bank::v1beta1::{
-        query_server::Query as BankQuery, QueryAllBalancesRequest, QueryAllBalancesResponse,
-        QueryBalanceRequest, QueryBalanceResponse, QueryDenomMetadataRequest,
-        QueryDenomMetadataResponse, QueryDenomsMetadataRequest, QueryDenomsMetadataResponse,
-        QueryParamsRequest, QueryParamsResponse, QuerySpendableBalancesRequest,
-        QuerySpendableBalancesResponse, QuerySupplyOfRequest, QuerySupplyOfResponse,
-        QueryTotalSupplyRequest, QueryTotalSupplyResponse,
+        query_server::Query as BankQuery, DenomUnit as RawDenomUnit, Metadata as RawMetadata,
+        QueryAllBalancesRequest, QueryAllBalancesResponse, QueryBalanceRequest,
+        QueryBalanceResponse, QueryDenomMetadataRequest, QueryDenomMetadataResponse,
+        QueryDenomsMetadataRequest, QueryDenomsMetadataResponse, QueryParamsRequest,
+        QueryParamsResponse, QuerySpendableBalancesRequest, QuerySpendableBalancesResponse,
+        QuerySupplyOfRequest, QuerySupplyOfResponse, QueryTotalSupplyRequest,
+        QueryTotalSupplyResponse,
     },
     base::v1beta1::Coin as RawCoin,
 };
@@ -13,6 +14,7 @@ use ibc_proto::cosmos::{
 use super::Ibc;
 use crate::abci::tendermint_client::TendermintAdapter;
 use crate::client::{AsyncQuery, Client};
+use crate::coins::DenomMetadata;
 use crate::query::Query;
 use std::rc::Rc;
 use tonic::{Request, Response, Status};
@@ -70,16 +72,46 @@ where
 
     async fn denom_metadata(
         &self,
-        _request: Request<QueryDenomMetadataRequest>,
+        request: Request<QueryDenomMetadataRequest>,
     ) -> Result<Response<QueryDenomMetadataResponse>, Status> {
-        unimplemented!()
+        let denom = request
+            .into_inner()
+            .denom
+            .into_bytes()
+            .try_into()
+            .map_err(|_| Status::invalid_argument("invalid denom"))?;
+
+        let metadata = self
+            .ibc
+            .transfers
+            .denoms
+            .metadata(denom)
+            .await??
+            .ok_or_else(|| Status::not_found("denom metadata not found"))?;
+
+        Ok(Response::new(QueryDenomMetadataResponse {
+            metadata: Some(metadata.into()),
+        }))
     }
 
     async fn denoms_metadata(
         &self,
         _request: Request<QueryDenomsMetadataRequest>,
     ) -> Result<Response<QueryDenomsMetadataResponse>, Status> {
-        unimplemented!()
+        let metadatas = self
+            .ibc
+            .transfers
+            .denoms
+            .all_metadata()
+            .await??
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        Ok(Response::new(QueryDenomsMetadataResponse {
+            metadatas,
+            pagination: None,
+        }))
     }
 
     async fn spendable_balances(
@@ -89,3 +121,28 @@ where
         unimplemented!()
     }
 }
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+impl From<DenomMetadata> for RawMetadata {
+    fn from(metadata: DenomMetadata) -> Self {
+        RawMetadata {
+            description: text(&metadata.description),
+            denom_units: metadata
+                .denom_units
+                .iter()
+                .map(|unit| RawDenomUnit {
+                    denom: text(&unit.denom),
+                    exponent: unit.exponent,
+                    aliases: vec![],
+                })
+                .collect(),
+            base: text(&metadata.base),
+            display: text(&metadata.display),
+            name: text(&metadata.name),
+            symbol: text(&metadata.symbol),
+        }
+    }
+}
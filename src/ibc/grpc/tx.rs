@@ -1,13 +1,15 @@
+use ibc_proto::cosmos::base::abci::v1beta1::{GasInfo, Result as AbciResult};
 use ibc_proto::cosmos::tx::v1beta1::service_server::Service as TxService;
 use ibc_proto::cosmos::tx::v1beta1::{
     BroadcastTxRequest, BroadcastTxResponse, GetBlockWithTxsRequest, GetBlockWithTxsResponse,
     GetTxRequest, GetTxResponse, GetTxsEventRequest, GetTxsEventResponse, SimulateRequest,
-    SimulateResponse,
+    SimulateResponse, Tx,
 };
 
 use super::Ibc;
 use crate::abci::tendermint_client::TendermintAdapter;
 use crate::client::{AsyncQuery, Client};
+use prost::Message;
 use std::rc::Rc;
 use tonic::{Request, Response, Status};
 
@@ -23,11 +25,51 @@ where
 {
     async fn simulate(
         &self,
-        _request: Request<SimulateRequest>,
+        request: Request<SimulateRequest>,
     ) -> Result<Response<SimulateResponse>, Status> {
+        let request = request.into_inner();
+        let mut tx = match request.tx {
+            Some(tx) if request.tx_bytes.is_empty() => tx,
+            _ => Tx::decode(request.tx_bytes.as_slice())
+                .map_err(|_| Status::invalid_argument("invalid tx bytes"))?,
+        };
+
+        // Simulated transactions are usually unsigned, so signatures are not
+        // verified and missing ones are filled with placeholders
+        if tx.signatures.is_empty() {
+            tx.signatures.push(vec![]);
+        }
+        for signature in tx.signatures.iter_mut() {
+            if signature.is_empty() {
+                *signature = vec![0; 64];
+            }
+        }
+
+        let gas_wanted = tx
+            .auth_info
+            .as_ref()
+            .and_then(|auth_info| auth_info.fee.as_ref())
+            .map_or(0, |fee| fee.gas_limit);
+
+        let res = self
+            .tm_client
+            .simulate(tx.encode_to_vec(), true)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if res.code != 0 {
+            return Err(Status::invalid_argument(res.log));
+        }
+
         Ok(Response::new(SimulateResponse {
-            gas_info: None,
-            result: None,
+            gas_info: Some(GasInfo {
+                gas_wanted,
+                gas_used: res.gas_used as u64,
+            }),
+            result: Some(AbciResult {
+                data: res.data,
+                log: res.log,
+                events: res.events,
+            }),
         }))
     }
 
@@ -242,7 +242,7 @@ impl Ibc {
     #[call]
     pub fn transfer(&mut self, opts: TransferOpts) -> Result<()> {
         let signer = self.signer()?;
-        let amt: u64 = opts.amount.into();
+        let amt: u128 = opts.amount.into();
         let msg_transfer = MsgTransfer {
             token: Coin {
                 amount: amt.to_string(),
@@ -1,4 +1,4 @@
-use crate::coins::{Address, Amount};
+use crate::coins::{Address, Amount, DenomRegistry};
 use crate::collections::{Deque, Map};
 use crate::describe::Describe;
 use crate::encoding::{Decode, Encode, LengthVec};
@@ -44,6 +44,7 @@ pub struct TransferModule {
     pub(super) bank: Bank,
     #[call]
     pub height: u64,
+    pub denoms: DenomRegistry,
 }
 
 unsafe impl Send for TransferModule {}
@@ -97,6 +98,9 @@ impl BankKeeper for TransferModule {
 
         let amount: Amount = amt.amount.try_into().map_err(|_| Error::invalid_token())?;
 
+        if let Err(err) = self.denoms.ensure_ibc(&amt.denom.to_string()) {
+            log::warn!("Failed to register metadata for {}: {}", amt.denom, err);
+        }
         self.bank
             .mint(*account, amount, denom)
             .map_err(|_| Error::invalid_token())?;
@@ -6,7 +6,6 @@
 #![feature(specialization)]
 #![feature(try_trait_v2)]
 #![feature(never_type)]
-#![feature(adt_const_params)]
 #![feature(fn_traits)]
 #![feature(async_closure)]
 #![feature(local_key_cell_methods)]
@@ -20,6 +19,11 @@ pub mod abci;
 
 pub mod call;
 
+/// Generic command-line interface driven by app descriptors (gated by `abci`
+/// feature).
+#[cfg(feature = "abci")]
+pub mod cli;
+
 pub mod client;
 
 /// Data structures which implement the [`state::State`](state/trait.State.html)
@@ -34,6 +38,9 @@ pub mod describe;
 /// crate.
 pub mod encoding;
 
+/// Typed events emitted from calls.
+pub mod events;
+
 /// Integration with [merk](https://docs.rs/merk) (gated by `merk` feature).
 #[cfg(feature = "merk")]
 pub mod merk;
@@ -61,6 +68,9 @@ pub mod plugins;
 #[cfg(any(target_arch = "wasm32", feature = "abci"))]
 pub mod coins;
 
+#[cfg(any(target_arch = "wasm32", feature = "abci"))]
+pub mod params;
+
 pub mod context;
 
 #[cfg(feature = "feat-ibc")]
@@ -98,6 +108,8 @@ pub mod prelude {
     #[cfg(merk)]
     pub use crate::merk;
     #[cfg(any(target_arch = "wasm32", feature = "abci"))]
+    pub use crate::params::*;
+    #[cfg(any(target_arch = "wasm32", feature = "abci"))]
     pub use crate::plugins::*;
     pub use crate::query::*;
     pub use crate::state::*;
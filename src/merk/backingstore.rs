@@ -1,6 +1,6 @@
 #[cfg(feature = "merk-full")]
 use super::{MerkStore, ProofBuilder};
-use crate::store::{BufStore, MapStore, NullStore, Read, Shared, Write, KV};
+use crate::store::{BufStore, MapStore, NullStore, Read, Shared, Stage, Write, KV};
 use crate::{Error, Result};
 use merk::proofs::query::Map as ProofMap;
 use std::ops::Bound;
@@ -19,6 +19,7 @@ pub enum BackingStore {
     MapStore(Shared<MapStore>),
     ProofMap(Shared<ProofStore>),
     Null(NullStore),
+    Staged(Shared<BufStore<BackingStore>>),
 }
 
 impl Default for BackingStore {
@@ -39,6 +40,7 @@ impl Read for BackingStore {
             BackingStore::MapStore(ref store) => store.get(key),
             BackingStore::ProofMap(ref map) => map.get(key),
             BackingStore::Null(ref null) => null.get(key),
+            BackingStore::Staged(ref store) => store.get(key),
         }
     }
 
@@ -53,6 +55,7 @@ impl Read for BackingStore {
             BackingStore::MapStore(ref store) => store.get_next(key),
             BackingStore::ProofMap(ref map) => map.get_next(key),
             BackingStore::Null(ref null) => null.get_next(key),
+            BackingStore::Staged(ref store) => store.get_next(key),
         }
     }
 }
@@ -73,6 +76,7 @@ impl Write for BackingStore {
                 panic!("put() is not implemented for ProofMap")
             }
             BackingStore::Null(ref mut store) => store.put(key, value),
+            BackingStore::Staged(ref mut store) => store.put(key, value),
         }
     }
     fn delete(&mut self, key: &[u8]) -> Result<()> {
@@ -91,10 +95,37 @@ impl Write for BackingStore {
                 panic!("delete() is not implemented for ProofMap")
             }
             BackingStore::Null(ref mut store) => store.delete(key),
+            BackingStore::Staged(ref mut store) => store.delete(key),
         }
     }
 }
 
+impl Stage for BackingStore {
+    fn stage(&self) -> Self {
+        BackingStore::Staged(Shared::new(BufStore::wrap(self.clone())))
+    }
+
+    fn commit(&mut self, staged: &Self) -> Result<()> {
+        let staged = match staged {
+            BackingStore::Staged(store) => store.borrow(),
+            _ => {
+                return Err(Error::Store(
+                    "Can only commit a staged backing store".into(),
+                ))
+            }
+        };
+
+        for (key, value) in staged.map() {
+            match value {
+                Some(value) => self.put(key.clone(), value.clone())?,
+                None => self.delete(key.as_slice())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl BackingStore {
     #[cfg(feature = "merk-full")]
     pub fn into_proof_builder(self) -> Result<ProofBuilder> {
@@ -0,0 +1,294 @@
+//! Typed module parameters which can only be changed by an authority.
+//!
+//! Each module declares its parameters as a type implementing [Params], and
+//! keeps them in a [ParamStore]. Parameters can be read by queries, and are
+//! changed one key at a time with [ParamStore::update], which is only allowed
+//! while governance is executing a passed proposal (signalled by the
+//! [GovernanceAuthority] context) or when signed by the store's configured
+//! admin. Every change is validated as a whole and emits a
+//! [ParamChangeEvent].
+
+use crate::coins::Address;
+use crate::context::Context;
+use crate::describe::Describe;
+use crate::encoding::{Decode, Encode, LengthVec, Terminated};
+use crate::events::{emit, Event};
+use crate::migrate::MigrateFrom;
+use crate::orga;
+use crate::plugins::Signer;
+use crate::state::State;
+use crate::{Error, Result};
+use std::ops::Deref;
+
+/// The parameters of a module.
+pub trait Params:
+    State + MigrateFrom + Describe + Clone + Default + std::fmt::Debug + Send + 'static
+{
+    /// The prefix of the keys of this module's parameters in a
+    /// [ParamChange], e.g. `"staking"` for `"staking/max_validators"`.
+    const MODULE: &'static str;
+
+    fn validate(&self) -> Result<()>;
+
+    /// Sets a single parameter from its encoded value. Unknown keys are an
+    /// error.
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()>;
+}
+
+/// Decodes a parameter value, rejecting any trailing bytes.
+pub fn decode_param<T: Decode>(mut value: &[u8]) -> Result<T> {
+    let decoded = T::decode(&mut value)?;
+    if !value.is_empty() {
+        return Err(Error::App("Unexpected trailing bytes in parameter".into()));
+    }
+
+    Ok(decoded)
+}
+
+/// A change of a single parameter, keyed by `"<module>/<param>"`.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct ParamChange {
+    pub key: LengthVec<u8, u8>,
+    pub value: LengthVec<u16, u8>,
+}
+
+impl Terminated for ParamChange {}
+
+impl ParamChange {
+    /// Splits the key into its module and parameter name.
+    pub fn split_key(&self) -> Result<(&str, &str)> {
+        std::str::from_utf8(&self.key)
+            .map_err(|_| Error::App("Parameter key must be UTF-8".into()))?
+            .split_once('/')
+            .ok_or_else(|| Error::App("Parameter key must be of the form module/param".into()))
+    }
+}
+
+/// Context present while governance executes a passed proposal, which
+/// authorizes parameter changes.
+pub struct GovernanceAuthority;
+
+/// Checks that the current call may change parameters: either governance is
+/// executing a proposal, or the call is signed by `admin`.
+pub fn authorize(admin: Option<Address>) -> Result<()> {
+    if Context::resolve::<GovernanceAuthority>().is_some() {
+        return Ok(());
+    }
+
+    let signer = Context::resolve::<Signer>()
+        .ok_or_else(|| Error::Signer("No Signer context available".into()))?
+        .signer
+        .ok_or_else(|| Error::Coins("Unauthorized account action".into()))?;
+    match admin {
+        Some(admin) if admin == signer => Ok(()),
+        _ => Err(Error::App(
+            "Only the parameter authority may change parameters".into(),
+        )),
+    }
+}
+
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "param_change")]
+pub struct ParamChangeEvent {
+    pub module: String,
+    pub key: String,
+}
+
+/// Emits a [ParamChangeEvent] for a changed parameter of `P`.
+pub fn emit_param_change<P: Params>(key: &str) -> Result<()> {
+    emit(ParamChangeEvent {
+        module: P::MODULE.to_string(),
+        key: key.to_string(),
+    })
+}
+
+/// Holds the parameters of a module, along with an optional admin which may
+/// change them in addition to governance.
+#[orga]
+#[derive(Clone, Describe)]
+pub struct ParamStore<P: Params> {
+    params: P,
+    admin: Option<Address>,
+}
+
+impl<P: Params> ParamStore<P> {
+    /// Replaces all of the parameters, e.g. at genesis. This is not a call, so
+    /// it is only reachable from app code.
+    pub fn set(&mut self, params: P) -> Result<()> {
+        params.validate()?;
+        self.params = params;
+
+        Ok(())
+    }
+
+    /// Sets the admin, e.g. at genesis. Apps without an admin leave parameter
+    /// changes to governance.
+    pub fn set_admin_unchecked(&mut self, admin: Option<Address>) {
+        self.admin = admin;
+    }
+
+    /// Changes a single parameter, if authorized.
+    #[call]
+    pub fn update(&mut self, key: LengthVec<u8, u8>, value: LengthVec<u16, u8>) -> Result<()> {
+        authorize(self.admin)?;
+        let key = std::str::from_utf8(&key)
+            .map_err(|_| Error::App("Parameter key must be UTF-8".into()))?;
+        self.update_param(key, &value)
+    }
+
+    /// Applies a change from a passed parameter change proposal, returning
+    /// `false` if the change is for another module.
+    pub fn apply_change(&mut self, change: &ParamChange) -> Result<bool> {
+        let (module, key) = change.split_key()?;
+        if module != P::MODULE {
+            return Ok(false);
+        }
+        authorize(self.admin)?;
+        self.update_param(key, &change.value)?;
+
+        Ok(true)
+    }
+
+    #[call]
+    pub fn set_admin(&mut self, admin: Address) -> Result<()> {
+        authorize(self.admin)?;
+        self.admin = Some(admin);
+
+        Ok(())
+    }
+
+    #[call]
+    pub fn remove_admin(&mut self) -> Result<()> {
+        authorize(self.admin)?;
+        self.admin = None;
+
+        Ok(())
+    }
+
+    #[query]
+    pub fn params(&self) -> P {
+        self.params.clone()
+    }
+
+    #[query]
+    pub fn admin(&self) -> Option<Address> {
+        self.admin
+    }
+
+    fn update_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let mut params = self.params.clone();
+        params.set_param(key, value)?;
+        params.validate()?;
+        self.params = params;
+
+        emit_param_change::<P>(key)
+    }
+}
+
+impl<P: Params> Deref for ParamStore<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        &self.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[orga]
+    #[derive(Clone, Debug, Describe)]
+    struct TestParams {
+        limit: u64,
+    }
+
+    impl Params for TestParams {
+        const MODULE: &'static str = "test";
+
+        fn validate(&self) -> Result<()> {
+            if self.limit > 100 {
+                return Err(Error::App("Limit too high".into()));
+            }
+            Ok(())
+        }
+
+        fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+            match key {
+                "limit" => self.limit = decode_param(value)?,
+                _ => return Err(Error::App(format!("Unknown parameter {}", key))),
+            }
+            Ok(())
+        }
+    }
+
+    fn change(key: &str, value: u64) -> ParamChange {
+        ParamChange {
+            key: key.as_bytes().to_vec().try_into().unwrap(),
+            value: value.encode().unwrap().try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn authority() -> Result<()> {
+        let admin = Address::from_pubkey([0; 33]);
+        let other = Address::from_pubkey([1; 33]);
+        let mut store: ParamStore<TestParams> = Default::default();
+        store.set(TestParams { limit: 10 })?;
+
+        {
+            let _signer = Context::scope(Signer {
+                signer: Some(other),
+            });
+            assert!(store.apply_change(&change("test/limit", 20)).is_err());
+            store.set_admin_unchecked(Some(admin));
+            assert!(store.apply_change(&change("test/limit", 20)).is_err());
+        }
+
+        {
+            let _signer = Context::scope(Signer {
+                signer: Some(admin),
+            });
+            assert!(store.apply_change(&change("test/limit", 20))?);
+            assert_eq!(store.limit, 20);
+            assert!(!store.apply_change(&change("other/limit", 30))?);
+            assert!(store.apply_change(&change("test/limit", 101)).is_err());
+            assert!(store.apply_change(&change("test/unknown", 1)).is_err());
+            assert_eq!(store.params().limit, 20);
+        }
+
+        {
+            let _authority = Context::scope(GovernanceAuthority);
+            store.remove_admin()?;
+            assert!(store.apply_change(&change("test/limit", 30))?);
+        }
+        assert_eq!(store.admin(), None);
+        assert_eq!(store.limit, 30);
+        assert!(store.apply_change(&change("test/limit", 40)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn describe() -> Result<()> {
+        let desc = ParamStore::<TestParams>::describe();
+        let calls: Vec<_> = desc.calls().iter().map(|call| call.name.as_str()).collect();
+        assert_eq!(calls, ["update", "set_admin", "remove_admin"]);
+
+        let mut store: ParamStore<TestParams> = Default::default();
+        store.set(TestParams { limit: 10 })?;
+        let value = desc.decode(&store.encode()?)?;
+        let limit: u64 = value
+            .child("params")?
+            .unwrap()
+            .child("limit")?
+            .unwrap()
+            .downcast()
+            .unwrap();
+        assert_eq!(limit, 10);
+
+        Ok(())
+    }
+}
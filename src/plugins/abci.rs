@@ -1,3 +1,5 @@
+use crate::coins::{Address, Amount};
+
 pub struct Time {
     pub seconds: i64,
     pub nanos: i32,
@@ -11,18 +13,72 @@ impl Time {
     }
 }
 
+/// Information about the block currently being processed. While checking a
+/// mempool transaction, this instead describes the block the transaction is
+/// expected to be included in, which has not been proposed yet.
+#[derive(Clone, Debug, Default)]
+pub struct BlockInfo {
+    pub height: u64,
+    /// The hash of the block, empty if the block has not been proposed yet.
+    pub hash: Vec<u8>,
+    pub last_block_hash: Vec<u8>,
+    /// The Tendermint address of the proposer's consensus key (the truncated
+    /// SHA-256 hash of its public key), not its operator address. The operator
+    /// address can be looked up with
+    /// [Staking::address_for_tm_hash](crate::coins::Staking::address_for_tm_hash).
+    pub proposer_address: Option<[u8; 20]>,
+    pub chain_id: String,
+}
+
+impl BlockInfo {
+    /// The expected information of the block after this one.
+    fn next(&self) -> Self {
+        Self {
+            height: self.height + 1,
+            hash: vec![],
+            last_block_hash: self.hash.clone(),
+            proposer_address: None,
+            chain_id: self.chain_id.clone(),
+        }
+    }
+}
+
+/// Present in the context while a transaction is being simulated rather than
+/// executed.
+pub struct Simulation {
+    pub skip_signatures: bool,
+}
+
+/// Present in the context while a mempool transaction is rechecked after a
+/// block is committed. Rechecks only need to revalidate nonces and fee
+/// payment, so paid calls are not executed.
+pub struct Recheck;
+
+/// Details of a mempool transaction which the node reports to Tendermint to
+/// order and limit its mempool. The node scopes this around each CheckTx, and
+/// the plugins fill it in as they handle the transaction.
+#[derive(Clone, Debug, Default)]
+pub struct CheckTxInfo {
+    /// The signer of the transaction.
+    pub sender: Option<Address>,
+    /// The fee charged by [FeePlugin](super::FeePlugin), in its fee denom.
+    pub fee: Amount,
+    /// The gas requested by the transaction, if it specified any.
+    pub gas_wanted: Option<u64>,
+}
+
 #[cfg(feature = "abci")]
 mod full {
-    use super::Time;
+    use super::{BlockInfo, Time};
     use crate::abci::{prost::Adapter, AbciQuery, App};
     use crate::call::Call;
     use crate::collections::{Entry, EntryMap, Map};
-    use crate::context::Context;
-    use crate::encoding::{Decode, Encode};
+    use crate::context::{debug_leaks, Context, ContextGuard};
+    use crate::encoding::{Decode, Encode, LengthVec};
     use crate::migrate::{MigrateFrom, MigrateInto};
     use crate::query::Query;
     use crate::state::State;
-    use crate::store::Store;
+    use crate::store::{Read, Store, Write};
     use crate::{compat_mode, Error, Result};
     use std::cell::{Ref, RefCell};
     use std::collections::HashMap;
@@ -51,11 +107,68 @@ mod full {
         pub(crate) validator_updates: Option<HashMap<[u8; 32], ValidatorUpdate>>,
         updates: UpdateMap,
         time: Option<Timestamp>,
+        block_info: Option<BlockInfo>,
+        /// Where the time and block info of the latest block are stored, so
+        /// they are available after a restart until the next BeginBlock.
+        last_block_store: Store,
+        last_block_changed: bool,
         pub(crate) events: Option<Vec<Event>>,
         current_vp: Rc<RefCell<Option<EntryMap<ValidatorEntry>>>>,
         cons_key_by_op_addr: Rc<RefCell<Option<OperatorMap>>>,
     }
 
+    /// The encoding of the time and block info of the latest block. A height
+    /// of 0 means no block has begun yet, e.g. right after InitChain.
+    #[derive(Encode, Decode)]
+    struct LastBlock {
+        seconds: i64,
+        nanos: i32,
+        height: u64,
+        hash: LengthVec<u8, u8>,
+        last_block_hash: LengthVec<u8, u8>,
+        proposer_address: Option<[u8; 20]>,
+        chain_id: LengthVec<u8, u8>,
+    }
+
+    impl LastBlock {
+        fn new(time: &Timestamp, block_info: Option<&BlockInfo>) -> Result<Self> {
+            let default_info = BlockInfo::default();
+            let info = block_info.unwrap_or(&default_info);
+
+            Ok(Self {
+                seconds: time.seconds,
+                nanos: time.nanos,
+                height: info.height,
+                hash: info.hash.clone().try_into()?,
+                last_block_hash: info.last_block_hash.clone().try_into()?,
+                proposer_address: info.proposer_address,
+                chain_id: info.chain_id.clone().into_bytes().try_into()?,
+            })
+        }
+
+        fn time(&self) -> Timestamp {
+            Timestamp {
+                seconds: self.seconds,
+                nanos: self.nanos,
+            }
+        }
+
+        fn block_info(self) -> Result<Option<BlockInfo>> {
+            if self.height == 0 {
+                return Ok(None);
+            }
+
+            Ok(Some(BlockInfo {
+                height: self.height,
+                hash: self.hash.into(),
+                last_block_hash: self.last_block_hash.into(),
+                proposer_address: self.proposer_address,
+                chain_id: String::from_utf8(self.chain_id.into())
+                    .map_err(|_| Error::State("Chain ID is not valid UTF-8".into()))?,
+            }))
+        }
+    }
+
     impl<T1, T2> MigrateFrom<ABCIPlugin<T1>> for ABCIPlugin<T2>
     where
         T1: MigrateInto<T2>,
@@ -66,6 +179,9 @@ mod full {
                 validator_updates: other.validator_updates,
                 updates: other.updates,
                 time: other.time,
+                block_info: other.block_info,
+                last_block_store: other.last_block_store,
+                last_block_changed: other.last_block_changed,
                 events: other.events,
                 current_vp: other.current_vp,
                 cons_key_by_op_addr: other.cons_key_by_op_addr,
@@ -80,6 +196,9 @@ mod full {
                 validator_updates: None,
                 updates: UpdateMap::default(),
                 time: None,
+                block_info: None,
+                last_block_store: Store::default(),
+                last_block_changed: false,
                 events: None,
                 current_vp: Rc::new(RefCell::new(Some(Default::default()))),
                 cons_key_by_op_addr: Rc::new(RefCell::new(Some(Default::default()))),
@@ -151,6 +270,25 @@ mod full {
         }
     }
 
+    impl BeginBlockCtx {
+        fn block_info(&self) -> BlockInfo {
+            let proposer_address = self.header.proposer_address.as_slice().try_into().ok();
+
+            BlockInfo {
+                height: self.height,
+                hash: self.hash.clone(),
+                last_block_hash: self
+                    .header
+                    .last_block_id
+                    .as_ref()
+                    .map(|id| id.hash.clone())
+                    .unwrap_or_default(),
+                proposer_address,
+                chain_id: self.header.chain_id.clone(),
+            }
+        }
+    }
+
     #[cfg_attr(test, derive(Default))]
     pub struct EndBlockCtx {
         pub height: u64,
@@ -311,59 +449,59 @@ mod full {
         type Call = ABCICall<T::Call>;
 
         fn call(&mut self, call: Self::Call) -> Result<()> {
+            // values added during the request are discarded when the frame is
+            // dropped, so no request depends on what an earlier one left behind
+            let frame = Context::frame();
+            let res = self.run(call);
+
+            if debug_leaks() {
+                for type_name in frame.entries(&[]) {
+                    log::warn!("Context value leaked from ABCI request: {}", type_name);
+                }
+            }
+
+            res
+        }
+    }
+
+    type BlockGuards = (Option<ContextGuard<Time>>, Option<ContextGuard<BlockInfo>>);
+
+    impl<T: App> ABCIPlugin<T> {
+        fn run(&mut self, call: ABCICall<T::Call>) -> Result<()> {
             use ABCICall::*;
             let validators =
                 Validators::new(self.current_vp.clone(), self.cons_key_by_op_addr.clone());
-            let context_remover = ContextRemover;
-            Context::add(validators);
-            let create_time_ctx = |time: &Option<Timestamp>| {
-                if let Some(timestamp) = time {
-                    Context::add(Time {
-                        seconds: timestamp.seconds,
-                        nanos: timestamp.nanos,
-                    });
-                }
-            };
-            create_time_ctx(&self.time);
+            let _validators = Context::scope(validators);
 
             match call {
                 InitChain(req) => {
                     let ctx: InitChainCtx = req.into_inner().into();
                     self.time = ctx.time.clone();
-                    create_time_ctx(&self.time);
+                    self.last_block_changed = true;
+                    let _block = self.scope_block(self.block_info.clone());
                     self.inner.init_chain(&ctx)?;
                 }
                 BeginBlock(req) => {
                     let ctx: BeginBlockCtx = req.into_inner().into();
                     self.time = ctx.header.clone().time;
-                    create_time_ctx(&self.time);
-                    self.inner.begin_block(&ctx)?;
+                    self.block_info = Some(ctx.block_info());
+                    self.last_block_changed = true;
+                    let _block = self.scope_block(self.block_info.clone());
+                    self.with_events(|inner| inner.begin_block(&ctx))?;
                 }
                 EndBlock(req) => {
                     let ctx = req.into_inner().into();
-                    self.inner.end_block(&ctx)?;
+                    let _block = self.scope_block(self.block_info.clone());
+                    self.with_events(|inner| inner.end_block(&ctx))?;
                 }
                 DeliverTx(inner_call) => {
-                    Context::add(Events::default());
-                    self.events.replace(vec![]);
-                    let res = self.inner.call(inner_call);
-                    if res.is_ok() {
-                        self.events
-                            .replace(Context::resolve::<Events>().unwrap().events.clone());
-                    }
-                    Context::remove::<Events>();
-                    res?;
+                    let _block = self.scope_block(self.block_info.clone());
+                    self.with_events(|inner| inner.call(inner_call))?;
                 }
                 CheckTx(inner_call) => {
-                    Context::add(Events::default());
-                    self.events.replace(vec![]);
-                    let res = self.inner.call(inner_call);
-                    if res.is_ok() {
-                        self.events
-                            .replace(Context::resolve::<Events>().unwrap().events.clone());
-                    }
-                    Context::remove::<Events>();
-                    res?;
+                    let next = self.block_info.as_ref().map(BlockInfo::next);
+                    let _block = self.scope_block(next);
+                    self.with_events(|inner| inner.call(inner_call))?;
                 }
             };
 
@@ -384,28 +522,39 @@ mod full {
                     })?;
                 }
             }
+            drop(current_vp_ref);
 
-            self.build_updates()?;
-            context_remover.remove();
-            Ok(())
+            self.build_updates()
         }
-    }
 
-    struct ContextRemover;
+        /// Publishes the time of the current block and the given block info
+        /// as context until the returned guards are dropped.
+        fn scope_block(&self, block_info: Option<BlockInfo>) -> BlockGuards {
+            let time = self.time.as_ref().map(|timestamp| {
+                Context::scope(Time {
+                    seconds: timestamp.seconds,
+                    nanos: timestamp.nanos,
+                })
+            });
 
-    impl ContextRemover {
-        fn remove(&self) {
-            Context::remove::<Validators>();
+            (time, block_info.map(Context::scope))
         }
-    }
 
-    impl Drop for ContextRemover {
-        fn drop(&mut self) {
-            self.remove();
+        /// Runs `op` with an [Events] context, keeping the events it emits
+        /// for the response if it succeeds.
+        fn with_events<F>(&mut self, op: F) -> Result<()>
+        where
+            F: FnOnce(&mut T) -> Result<()>,
+        {
+            let _events = Context::scope(Events::default());
+            self.events.replace(vec![]);
+            op(&mut self.inner)?;
+            let events = std::mem::take(&mut Context::resolve::<Events>().unwrap().events);
+            self.events.replace(events);
+
+            Ok(())
         }
-    }
 
-    impl<T: App> ABCIPlugin<T> {
         fn build_updates(&mut self) -> Result<()> {
             let mut update_keys = vec![];
             let mut update_map = HashMap::new();
@@ -437,6 +586,7 @@ mod full {
 
     impl<T: State> State for ABCIPlugin<T> {
         fn attach(&mut self, store: Store) -> Result<()> {
+            self.last_block_store = store.sub(&[4]);
             self.inner.attach(store.sub(&[0]))?;
             self.updates.attach(store.sub(&[1]))?;
             self.current_vp.borrow_mut().attach(store.sub(&[2]))?;
@@ -445,7 +595,14 @@ mod full {
                 .attach(store.sub(&[3]))
         }
 
-        fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
+        fn flush<W: std::io::Write>(mut self, out: &mut W) -> Result<()> {
+            if self.last_block_changed {
+                if let Some(time) = &self.time {
+                    let last_block = LastBlock::new(time, self.block_info.as_ref())?;
+                    self.last_block_store.put(vec![], last_block.encode()?)?;
+                }
+            }
+
             if !compat_mode() {
                 out.write_all(&[0])?;
             }
@@ -456,6 +613,15 @@ mod full {
         }
 
         fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+            let last_block_store = store.sub(&[4]);
+            let (time, block_info) = match last_block_store.get(&[])? {
+                Some(last_block_bytes) => {
+                    let last_block = LastBlock::decode(last_block_bytes.as_slice())?;
+                    (Some(last_block.time()), last_block.block_info()?)
+                }
+                None => (None, None),
+            };
+
             let mut loader = crate::state::Loader::new(store, bytes, 0);
 
             Ok(Self {
@@ -465,7 +631,10 @@ mod full {
                 current_vp: Rc::new(RefCell::new(Some(loader.load_child()?))),
                 cons_key_by_op_addr: Rc::new(RefCell::new(Some(loader.load_child()?))),
                 events: None,
-                time: None,
+                time,
+                block_info,
+                last_block_store,
+                last_block_changed: false,
             })
         }
     }
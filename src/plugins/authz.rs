@@ -0,0 +1,520 @@
+use super::{sdk_compat::sdk::Tx as SdkTx, ConvertSdkTx, PaidCall, Signer, Time};
+use crate::call::Call;
+use crate::client::Client;
+use crate::client::{AsyncCall, AsyncQuery};
+use crate::coins::Address;
+use crate::collections::Map;
+use crate::context::{Context, GetContext};
+use crate::encoding::{Decode, Encode, LengthVec};
+use crate::migrate::{MigrateFrom, MigrateInto};
+use crate::orga;
+use crate::query::Query;
+use crate::state::State;
+use crate::{Error, Result};
+use std::ops::{Deref, DerefMut};
+
+/// A plugin which lets an address authorize another to make calls on its
+/// behalf, e.g. for a bot to claim and restake rewards without holding the
+/// user's key.
+///
+/// The plugin should be placed below `FeePlugin`, so the grantee pays the
+/// fees of the calls it executes.
+#[derive(State, Encode, Decode, Default)]
+pub struct AuthzPlugin<T: State> {
+    grants: Map<Address, Map<Address, Grant>>,
+    inner: T,
+}
+
+/// The calls a grantee may execute on behalf of a granter.
+#[orga]
+#[derive(Clone, Debug)]
+pub struct Grant {
+    /// The time (in seconds since the Unix epoch) after which the grant can no
+    /// longer be used, if any.
+    pub expiration: Option<i64>,
+    /// The number of calls the grantee may still execute, if limited.
+    pub remaining_uses: Option<u64>,
+    /// Prefixes of the encoded calls the grantee may execute, e.g. the
+    /// encoding of a method's path.
+    pub allowed_calls: LengthVec<u8, LengthVec<u16, u8>>,
+}
+
+impl Grant {
+    pub fn allows(&self, call_bytes: &[u8]) -> bool {
+        self.allowed_calls
+            .iter()
+            .any(|prefix| call_bytes.starts_with(prefix.as_slice()))
+    }
+}
+
+impl<T1: State, T2: State> MigrateFrom<AuthzPlugin<T1>> for AuthzPlugin<T2>
+where
+    T1: MigrateInto<T2>,
+{
+    fn migrate_from(other: AuthzPlugin<T1>) -> Result<Self> {
+        Ok(Self {
+            grants: other.grants.migrate_into()?,
+            inner: other.inner.migrate_into()?,
+        })
+    }
+}
+
+impl<T: State> AuthzPlugin<T> {
+    pub fn grant(&self, granter: Address, grantee: Address) -> Result<Option<Grant>> {
+        Ok(match self.grants.get(granter)? {
+            Some(grants) => grants.get(grantee)?.map(|grant| Grant::clone(&grant)),
+            None => None,
+        })
+    }
+
+    fn signer(&mut self) -> Result<Address> {
+        self.context::<Signer>()
+            .ok_or_else(|| Error::Signer("No Signer context available".into()))?
+            .signer
+            .ok_or_else(|| Error::Authorization("Call must be signed".into()))
+    }
+
+    fn check_grant(&mut self, granter: Address, grantee: Address, call_bytes: &[u8]) -> Result<()> {
+        let grant = self
+            .grant(granter, grantee)?
+            .ok_or_else(|| Error::Authorization("No grant found".into()))?;
+
+        if let Some(expiration) = grant.expiration {
+            let now = self
+                .context::<Time>()
+                .ok_or_else(|| Error::Authorization("No Time context available".into()))?
+                .seconds;
+            if now > expiration {
+                return Err(Error::Authorization("Grant has expired".into()));
+            }
+        }
+        if !grant.allows(call_bytes) {
+            return Err(Error::Authorization(
+                "Grant does not allow this call".into(),
+            ));
+        }
+        if grant.remaining_uses == Some(0) {
+            return Err(Error::Authorization("Grant has no remaining uses".into()));
+        }
+
+        Ok(())
+    }
+
+    fn spend_grant(&mut self, granter: Address, grantee: Address) -> Result<()> {
+        let mut grants = self
+            .grants
+            .get_mut(granter)?
+            .ok_or_else(|| Error::Authorization("No grant found".into()))?;
+        let mut grant = grants
+            .get_mut(grantee)?
+            .ok_or_else(|| Error::Authorization("No grant found".into()))?;
+
+        if let Some(n) = grant.remaining_uses {
+            grant.remaining_uses = Some(n - 1);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: State> Deref for AuthzPlugin<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: State> DerefMut for AuthzPlugin<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+pub enum AuthzCall<T> {
+    Inner(T),
+    Grant(Address, Grant),
+    Revoke(Address),
+    Exec(Address, T),
+}
+
+impl<T> Call for AuthzPlugin<T>
+where
+    T: Call + State,
+{
+    type Call = AuthzCall<T::Call>;
+
+    fn call(&mut self, call: Self::Call) -> Result<()> {
+        match call {
+            AuthzCall::Inner(call) => self.inner.call(call),
+            AuthzCall::Grant(grantee, grant) => {
+                let granter = self.signer()?;
+                self.grants
+                    .entry(granter)?
+                    .or_insert_default()?
+                    .insert(grantee, grant)
+            }
+            AuthzCall::Revoke(grantee) => {
+                let granter = self.signer()?;
+                let mut grants = self
+                    .grants
+                    .get_mut(granter)?
+                    .ok_or_else(|| Error::Authorization("No grant found".into()))?;
+                grants
+                    .remove(grantee)?
+                    .ok_or_else(|| Error::Authorization("No grant found".into()))?;
+                Ok(())
+            }
+            AuthzCall::Exec(granter, call) => {
+                let grantee = self.signer()?;
+                self.check_grant(granter, grantee, call.encode()?.as_slice())?;
+
+                {
+                    let _signer = Context::scope(Signer {
+                        signer: Some(granter),
+                    });
+                    self.inner.call(call)?;
+                }
+
+                // failed calls don't use up the grant
+                self.spend_grant(granter, grantee)
+            }
+        }
+    }
+}
+
+#[derive(Encode, Decode, Debug)]
+pub enum AuthzQuery<T> {
+    Grant(Address, Address),
+    Inner(T),
+}
+
+impl<T: State + Query> Query for AuthzPlugin<T> {
+    type Query = AuthzQuery<T::Query>;
+
+    fn query(&self, query: Self::Query) -> Result<()> {
+        match query {
+            AuthzQuery::Grant(granter, grantee) => {
+                self.grant(granter, grantee)?;
+                Ok(())
+            }
+            AuthzQuery::Inner(query) => self.inner.query(query),
+        }
+    }
+}
+
+impl<T> ConvertSdkTx for AuthzPlugin<T>
+where
+    T: State + ConvertSdkTx<Output = PaidCall<T::Call>> + Call,
+{
+    type Output = PaidCall<AuthzCall<T::Call>>;
+
+    fn convert(&self, sdk_tx: &SdkTx) -> Result<Self::Output> {
+        let paid_call = self.inner.convert(sdk_tx)?;
+        Ok(PaidCall {
+            payer: AuthzCall::Inner(paid_call.payer),
+            paid: AuthzCall::Inner(paid_call.paid),
+        })
+    }
+}
+
+pub struct AuthzAdapter<T, U: Clone> {
+    parent: U,
+    marker: std::marker::PhantomData<fn() -> T>,
+}
+
+unsafe impl<T, U: Send + Clone> Send for AuthzAdapter<T, U> {}
+
+impl<T, U: Clone> Clone for AuthzAdapter<T, U> {
+    fn clone(&self) -> Self {
+        AuthzAdapter {
+            parent: self.parent.clone(),
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<T: Call, U: AsyncCall<Call = AuthzCall<T::Call>> + Clone> AsyncCall for AuthzAdapter<T, U>
+where
+    T::Call: Send,
+    U: Send,
+{
+    type Call = T::Call;
+
+    async fn call(&self, call: Self::Call) -> Result<()> {
+        self.parent.call(AuthzCall::Inner(call)).await
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<
+        T: Query + State,
+        U: for<'a> AsyncQuery<
+                Query = AuthzQuery<T::Query>,
+                Response<'a> = std::rc::Rc<AuthzPlugin<T>>,
+            > + Clone,
+    > AsyncQuery for AuthzAdapter<T, U>
+{
+    type Query = T::Query;
+    type Response<'a> = std::rc::Rc<T>;
+
+    async fn query<F, R>(&self, query: Self::Query, mut check: F) -> Result<R>
+    where
+        F: FnMut(Self::Response<'_>) -> Result<R>,
+    {
+        self.parent
+            .query(AuthzQuery::Inner(query), |plugin| {
+                check(std::rc::Rc::new(
+                    std::rc::Rc::try_unwrap(plugin)
+                        .map_err(|_| ())
+                        .unwrap()
+                        .inner,
+                ))
+            })
+            .await
+    }
+}
+
+pub struct AuthzClient<T: Client<AuthzAdapter<T, U>> + State, U: Clone> {
+    inner: T::Client,
+    parent: U,
+}
+
+impl<T: Client<AuthzAdapter<T, U>> + State, U: Clone> Clone for AuthzClient<T, U>
+where
+    T::Client: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            parent: self.parent.clone(),
+        }
+    }
+}
+
+impl<T: Client<AuthzAdapter<T, U>> + State, U: Clone> Deref for AuthzClient<T, U> {
+    type Target = T::Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: Client<AuthzAdapter<T, U>> + State, U: Clone> DerefMut for AuthzClient<T, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+// raw calls and queries are passed through to the inner client
+crate::pass_through_client! {
+    impl[T: Client<AuthzAdapter<T, U>> + State, U: Clone]
+    AuthzClient<T, U> => inner: T::Client
+}
+
+impl<
+        T: Client<AuthzAdapter<T, U>> + State + Query,
+        U: Clone
+            + for<'a> AsyncQuery<
+                Query = AuthzQuery<T::Query>,
+                Response<'a> = std::rc::Rc<AuthzPlugin<T>>,
+            >,
+    > AuthzClient<T, U>
+{
+    pub async fn grant(&self, granter: Address, grantee: Address) -> Result<Option<Grant>> {
+        self.parent
+            .query(AuthzQuery::Grant(granter, grantee), |plugin| {
+                plugin.grant(granter, grantee)
+            })
+            .await
+    }
+}
+
+impl<T, U> AuthzClient<T, U>
+where
+    T: Client<AuthzAdapter<T, U>> + State + Call,
+    T::Call: Send,
+    U: Clone + Send + AsyncCall<Call = AuthzCall<T::Call>>,
+{
+    /// Authorizes the grantee to make the calls allowed by the grant on
+    /// behalf of the signer, replacing any existing grant.
+    pub async fn authorize(&self, grantee: Address, grant: Grant) -> Result<()> {
+        self.parent.call(AuthzCall::Grant(grantee, grant)).await
+    }
+
+    pub async fn revoke(&self, grantee: Address) -> Result<()> {
+        self.parent.call(AuthzCall::Revoke(grantee)).await
+    }
+
+    /// Executes a call on behalf of the granter.
+    pub async fn exec(&self, granter: Address, call: T::Call) -> Result<()> {
+        self.parent.call(AuthzCall::Exec(granter, call)).await
+    }
+}
+
+impl<T: Client<AuthzAdapter<T, U>> + State, U: Clone> Client<U> for AuthzPlugin<T> {
+    type Client = AuthzClient<T, U>;
+
+    fn create_client(parent: U) -> Self::Client {
+        AuthzClient {
+            inner: T::create_client(AuthzAdapter {
+                parent: parent.clone(),
+                marker: std::marker::PhantomData,
+            }),
+            parent,
+        }
+    }
+}
+
+// TODO: Remove dependency on ABCI for this otherwise-pure plugin.
+#[cfg(feature = "abci")]
+mod abci {
+    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::*;
+    use crate::abci::{BeginBlock, EndBlock, InitChain};
+
+    impl<T> BeginBlock for AuthzPlugin<T>
+    where
+        T: BeginBlock + State,
+    {
+        fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
+            self.inner.begin_block(ctx)
+        }
+    }
+
+    impl<T> EndBlock for AuthzPlugin<T>
+    where
+        T: EndBlock + State,
+    {
+        fn end_block(&mut self, ctx: &EndBlockCtx) -> Result<()> {
+            self.inner.end_block(ctx)
+        }
+    }
+
+    impl<T> InitChain for AuthzPlugin<T>
+    where
+        T: InitChain + State + Call,
+    {
+        fn init_chain(&mut self, ctx: &InitChainCtx) -> Result<()> {
+            self.inner.init_chain(ctx)
+        }
+    }
+
+    impl<T> crate::abci::AbciQuery for AuthzPlugin<T>
+    where
+        T: crate::abci::AbciQuery + State + Call,
+    {
+        fn abci_query(
+            &self,
+            request: &tendermint_proto::abci::RequestQuery,
+        ) -> Result<tendermint_proto::abci::ResponseQuery> {
+            self.inner.abci_query(request)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[derive(State, Encode, Decode, Default)]
+    struct Counter {
+        count: u64,
+        last_signer: Option<Address>,
+    }
+
+    impl Call for Counter {
+        type Call = u8;
+
+        fn call(&mut self, call: Self::Call) -> Result<()> {
+            if call == 0 {
+                return Err(Error::App("Nothing to count".into()));
+            }
+            self.count += call as u64;
+            self.last_signer = self.context::<Signer>().unwrap().signer;
+
+            Ok(())
+        }
+    }
+
+    fn sign(address: Address) {
+        Context::add(Signer {
+            signer: Some(address),
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn exec() -> Result<()> {
+        let granter = Address::from_pubkey([2; 33]);
+        let grantee = Address::from_pubkey([3; 33]);
+        let mut state: AuthzPlugin<Counter> = Default::default();
+
+        sign(grantee);
+        assert!(state.call(AuthzCall::Exec(granter, 1)).is_err());
+
+        sign(granter);
+        let grant = Grant {
+            expiration: Some(100),
+            remaining_uses: Some(2),
+            allowed_calls: LengthVec::new(1, vec![LengthVec::new(1, vec![1])]),
+        };
+        state.call(AuthzCall::Grant(grantee, grant))?;
+
+        Context::add(Time::from_seconds(50));
+        sign(grantee);
+        assert!(state.call(AuthzCall::Exec(granter, 2)).is_err());
+        state.call(AuthzCall::Exec(granter, 1))?;
+        assert_eq!(state.count, 1);
+        assert_eq!(state.last_signer, Some(granter));
+        assert_eq!(Context::resolve::<Signer>().unwrap().signer, Some(grantee));
+
+        state.call(AuthzCall::Exec(granter, 1))?;
+        assert!(state.call(AuthzCall::Exec(granter, 1)).is_err());
+        assert_eq!(
+            state.grant(granter, grantee)?.unwrap().remaining_uses,
+            Some(0)
+        );
+
+        sign(granter);
+        let grant = Grant {
+            expiration: Some(100),
+            remaining_uses: None,
+            allowed_calls: LengthVec::new(1, vec![LengthVec::new(1, vec![1])]),
+        };
+        state.call(AuthzCall::Grant(grantee, grant))?;
+        Context::add(Time::from_seconds(101));
+        sign(grantee);
+        assert!(state.call(AuthzCall::Exec(granter, 1)).is_err());
+
+        // failed calls don't use up the grant
+        sign(granter);
+        let grant = Grant {
+            expiration: None,
+            remaining_uses: Some(1),
+            allowed_calls: LengthVec::new(1, vec![LengthVec::new(0, vec![])]),
+        };
+        state.call(AuthzCall::Grant(grantee, grant))?;
+        sign(grantee);
+        assert!(state.call(AuthzCall::Exec(granter, 0)).is_err());
+        assert_eq!(
+            state.grant(granter, grantee)?.unwrap().remaining_uses,
+            Some(1)
+        );
+        state.call(AuthzCall::Exec(granter, 1))?;
+        assert!(state.call(AuthzCall::Exec(granter, 1)).is_err());
+
+        sign(granter);
+        state.call(AuthzCall::Revoke(grantee))?;
+        assert!(state.grant(granter, grantee)?.is_none());
+        assert_eq!(state.count, 3);
+
+        Context::remove::<Signer>();
+        Context::remove::<Time>();
+
+        Ok(())
+    }
+}
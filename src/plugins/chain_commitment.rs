@@ -1,44 +1,122 @@
+use super::eip712::{Eip712Call, Message};
 use super::{sdk_compat::sdk::Tx as SdkTx, ConvertSdkTx};
 use crate::call::Call as CallTrait;
 use crate::client::{AsyncCall, AsyncQuery, Client as ClientTrait};
+use crate::compat_mode;
 use crate::context::Context;
-use crate::encoding::{Decode, Encode};
+use crate::encoding::{Decode, Encode, LengthVec};
 use crate::migrate::{MigrateFrom, MigrateInto};
 use crate::query::Query;
 use crate::state::State;
+use crate::store::Store;
 use crate::{Error, Result};
 use std::marker::PhantomData;
 use std::ops::Deref;
-
-#[derive(Encode, Decode, Default, State)]
-#[state(transparent)]
-pub struct ChainCommitmentPlugin<T, const ID: &'static str> {
+use std::sync::{Arc, Mutex};
+
+/// The state version which added the stored chain ID. Version 0 is the layout
+/// of the plugin when the chain ID was a const generic parameter, which only
+/// contained the inner state.
+const VERSION: u8 = 1;
+
+/// The start of the error message for calls which are not committed to the
+/// chain ID, which clients check for to detect a changed chain ID.
+const INVALID_CHAIN_ID: &str = "Invalid chain ID";
+
+/// Commits calls to a chain ID, so that calls signed for one chain can not be
+/// replayed on another.
+///
+/// The chain ID is stored in state when the chain is initialized, and is kept
+/// in sync with the chain ID of block headers, so a chain ID changed by a
+/// coordinated upgrade takes effect at the first block after the upgrade.
+/// State migrated from the const generic layout has no chain ID until its first
+/// block.
+#[derive(Default)]
+pub struct ChainCommitmentPlugin<T> {
+    chain_id: String,
     inner: T,
 }
 
-impl<T1, T2, const ID1: &'static str, const ID2: &'static str>
-    MigrateFrom<ChainCommitmentPlugin<T1, ID1>> for ChainCommitmentPlugin<T2, ID2>
+// The inner state is attached to the plugin's store, as in the const generic
+// layout, so the chain ID is stored in the plugin's encoding instead of a
+// child store.
+impl<T: State> State for ChainCommitmentPlugin<T> {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        self.inner.attach(store)
+    }
+
+    // Compat mode uses the version 0 layout, which has no chain ID.
+    fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
+        if !compat_mode() {
+            out.write_all(&[VERSION])?;
+            let id_bytes = self.chain_id.into_bytes();
+            let len = id_bytes
+                .len()
+                .try_into()
+                .map_err(|_| Error::App("Chain ID is too long".into()))?;
+            LengthVec::<u8, u8>::new(len, id_bytes).flush(out)?;
+        }
+        self.inner.flush(out)
+    }
+
+    fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+        let version = if compat_mode() {
+            0
+        } else {
+            let version = *bytes
+                .first()
+                .ok_or_else(|| Error::State("Unexpected EOF".into()))?;
+            *bytes = &bytes[1..];
+            version
+        };
+
+        let chain_id = match version {
+            0 => String::new(),
+            VERSION => {
+                let id_bytes: Vec<u8> = LengthVec::<u8, u8>::load(store.clone(), bytes)?.into();
+                String::from_utf8(id_bytes)
+                    .map_err(|_| Error::State("Chain ID is not valid UTF-8".into()))?
+            }
+            _ => {
+                return Err(Error::State(format!(
+                    "Expected version {}, got {} for ChainCommitmentPlugin",
+                    VERSION, version
+                )))
+            }
+        };
+
+        Ok(Self {
+            chain_id,
+            inner: T::load(store, bytes)?,
+        })
+    }
+}
+
+impl<T1, T2> MigrateFrom<ChainCommitmentPlugin<T1>> for ChainCommitmentPlugin<T2>
 where
     T1: MigrateInto<T2>,
 {
-    fn migrate_from(other: ChainCommitmentPlugin<T1, ID1>) -> Result<Self> {
+    fn migrate_from(other: ChainCommitmentPlugin<T1>) -> Result<Self> {
         Ok(Self {
+            chain_id: other.chain_id,
             inner: other.inner.migrate_into()?,
         })
     }
 }
 
-pub struct ChainId(pub &'static str);
+/// The chain ID which calls are committed to, available as context while a
+/// call is processed.
+pub struct ChainId(pub String);
 
 impl Deref for ChainId {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        self.0
+        self.0.as_str()
     }
 }
 
-impl<T, const ID: &'static str> Deref for ChainCommitmentPlugin<T, ID> {
+impl<T> Deref for ChainCommitmentPlugin<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -46,46 +124,104 @@ impl<T, const ID: &'static str> Deref for ChainCommitmentPlugin<T, ID> {
     }
 }
 
-impl<T: CallTrait, const ID: &'static str> CallTrait for ChainCommitmentPlugin<T, ID> {
+impl<T> ChainCommitmentPlugin<T> {
+    /// The chain ID which calls must be committed to, or an empty string if it
+    /// has not been set yet.
+    pub fn chain_id(&self) -> &str {
+        self.chain_id.as_str()
+    }
+
+    /// Replaces the chain ID, e.g. from an upgrade handler. Calls committed to
+    /// the previous chain ID are rejected from then on.
+    pub fn set_chain_id(&mut self, chain_id: String) -> Result<()> {
+        if chain_id.is_empty() {
+            return Err(Error::App("Chain ID must not be empty".into()));
+        }
+        if chain_id.len() > u8::MAX as usize {
+            return Err(Error::App("Chain ID is too long".into()));
+        }
+        self.chain_id = chain_id;
+
+        Ok(())
+    }
+
+    fn add_context(&self) -> Result<()> {
+        if self.chain_id.is_empty() {
+            return Err(Error::App("Chain ID has not been initialized".into()));
+        }
+        Context::add(ChainId(self.chain_id.clone()));
+
+        Ok(())
+    }
+}
+
+impl<T: CallTrait> CallTrait for ChainCommitmentPlugin<T> {
     type Call = Vec<u8>;
 
     fn call(&mut self, call: Self::Call) -> Result<()> {
-        Context::add(ChainId(ID));
-        let expected_id: Vec<u8> = ID.bytes().collect();
+        self.add_context()?;
+        let inner_call = Decode::decode(self.strip_chain_id(call.as_slice())?)?;
+        self.inner.call(inner_call)
+    }
+}
+
+impl<T> ChainCommitmentPlugin<T> {
+    /// Checks that a call is committed to the chain ID, returning the inner
+    /// call bytes which follow it.
+    fn strip_chain_id<'a>(&self, call: &'a [u8]) -> Result<&'a [u8]> {
+        let expected_id = self.chain_id.as_bytes();
         if call.len() < expected_id.len() {
-            return Err(Error::App("Invalid chain ID length".into()));
+            return Err(Error::App(format!("{} length", INVALID_CHAIN_ID)));
         }
-        let chain_id = &call[..expected_id.len()];
+        let (chain_id, inner_call) = call.split_at(expected_id.len());
         if chain_id != expected_id {
             return Err(Error::App(format!(
-                "Invalid chain ID (expected {}, got {})",
-                ID,
+                "{} (expected {}, got {})",
+                INVALID_CHAIN_ID,
+                self.chain_id,
                 String::from_utf8(chain_id.to_vec()).unwrap_or_default()
             )));
         }
 
-        let inner_call = Decode::decode(&call[chain_id.len()..])?;
-        self.inner.call(inner_call)
+        Ok(inner_call)
     }
 }
 
-impl<T: Query, const ID: &'static str> Query for ChainCommitmentPlugin<T, ID> {
-    type Query = T::Query;
+impl<T: Eip712Call> Eip712Call for ChainCommitmentPlugin<T> {
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        *bytes = self.strip_chain_id(bytes)?;
+        self.inner.eip712_call(bytes, message)
+    }
+}
+
+#[derive(Encode, Decode, Debug)]
+pub enum ChainCommitmentQuery<T> {
+    ChainId,
+    Inner(T),
+}
+
+impl<T: Query> Query for ChainCommitmentPlugin<T> {
+    type Query = ChainCommitmentQuery<T::Query>;
 
     fn query(&self, query: Self::Query) -> Result<()> {
-        self.inner.query(query)
+        match query {
+            // the chain ID is part of the plugin's encoding, so it is always
+            // included in the query result
+            ChainCommitmentQuery::ChainId => Ok(()),
+            ChainCommitmentQuery::Inner(query) => self.inner.query(query),
+        }
     }
 }
 
-impl<T, const ID: &'static str> ConvertSdkTx for ChainCommitmentPlugin<T, ID>
+impl<T> ConvertSdkTx for ChainCommitmentPlugin<T>
 where
     T: ConvertSdkTx<Output = T::Call> + CallTrait,
 {
     type Output = Vec<u8>;
 
     fn convert(&self, sdk_tx: &SdkTx) -> Result<Vec<u8>> {
-        Context::add(ChainId(ID));
-        let id_bytes = ID.as_bytes();
+        self.add_context()?;
+        let id_bytes = self.chain_id.as_bytes();
         let inner_call = self.inner.convert(sdk_tx)?;
 
         let mut call_bytes = Vec::with_capacity(id_bytes.len() + inner_call.encoding_length()?);
@@ -96,39 +232,85 @@ where
     }
 }
 
-pub struct Client<T, U: Clone, const ID: &'static str> {
+pub struct Client<T, U: Clone> {
     parent: U,
+    /// The chain ID queried from the chain, shared by clones of the client.
+    chain_id: Arc<Mutex<Option<String>>>,
     marker: std::marker::PhantomData<fn() -> T>,
 }
 
-impl<T, U: Clone, const ID: &'static str> Clone for Client<T, U, ID> {
+impl<T, U: Clone> Clone for Client<T, U> {
     fn clone(&self) -> Self {
         Client {
             parent: self.parent.clone(),
+            chain_id: self.chain_id.clone(),
             marker: PhantomData,
         }
     }
 }
 
-unsafe impl<T, U: Clone + Send, const ID: &'static str> Send for Client<T, U, ID> {}
+unsafe impl<T, U: Clone + Send> Send for Client<T, U> {}
+
+impl<T, U> Client<T, U>
+where
+    T: Query + 'static,
+    U: for<'a> AsyncQuery<
+            Query = ChainCommitmentQuery<T::Query>,
+            Response<'a> = std::rc::Rc<ChainCommitmentPlugin<T>>,
+        > + Clone,
+{
+    /// Queries the chain ID from the chain's state and caches it for later
+    /// calls.
+    async fn query_chain_id(&self) -> Result<String> {
+        let chain_id = self
+            .parent
+            .query(ChainCommitmentQuery::ChainId, |plugin| {
+                Ok(plugin.chain_id.clone())
+            })
+            .await?;
+        *self.chain_id.lock().unwrap() = Some(chain_id.clone());
+
+        Ok(chain_id)
+    }
+}
 
 #[async_trait::async_trait(?Send)]
-impl<T: CallTrait, U: AsyncCall<Call = Vec<u8>> + Clone, const ID: &'static str> AsyncCall
-    for Client<T, U, ID>
+impl<T, U> AsyncCall for Client<T, U>
 where
+    T: CallTrait + Query + 'static,
     T::Call: Send,
-    U: Send,
+    U: AsyncCall<Call = Vec<u8>>
+        + for<'a> AsyncQuery<
+            Query = ChainCommitmentQuery<T::Query>,
+            Response<'a> = std::rc::Rc<ChainCommitmentPlugin<T>>,
+        > + Clone
+        + Send,
 {
     type Call = T::Call;
 
     async fn call(&self, call: Self::Call) -> Result<()> {
-        let id_bytes = ID.as_bytes();
-
-        let mut call_bytes = Vec::with_capacity(id_bytes.len() + call.encoding_length()?);
-        call_bytes.extend_from_slice(id_bytes);
-        call.encode_into(&mut call_bytes)?;
-
-        self.parent.call(call_bytes).await
+        let call = call.encode()?;
+        let commit = |chain_id: &str| [chain_id.as_bytes(), call.as_slice()].concat();
+
+        let cached = self.chain_id.lock().unwrap().clone();
+        let chain_id = match cached {
+            Some(chain_id) => chain_id,
+            None => self.query_chain_id().await?,
+        };
+
+        match self.parent.call(commit(&chain_id)).await {
+            // the chain ID may have changed in an upgrade since it was cached,
+            // so it is queried again and the call is retried once with the new
+            // chain ID
+            Err(Error::App(msg)) if msg.starts_with(INVALID_CHAIN_ID) => {
+                let new_chain_id = self.query_chain_id().await?;
+                if new_chain_id == chain_id {
+                    return Err(Error::App(msg));
+                }
+                self.parent.call(commit(&new_chain_id)).await
+            }
+            res => res,
+        }
     }
 }
 
@@ -136,11 +318,10 @@ where
 impl<
         T: Query + 'static,
         U: for<'a> AsyncQuery<
-                Query = T::Query,
-                Response<'a> = std::rc::Rc<ChainCommitmentPlugin<T, ID>>,
+                Query = ChainCommitmentQuery<T::Query>,
+                Response<'a> = std::rc::Rc<ChainCommitmentPlugin<T>>,
             > + Clone,
-        const ID: &'static str,
-    > AsyncQuery for Client<T, U, ID>
+    > AsyncQuery for Client<T, U>
 {
     type Query = T::Query;
     type Response<'a> = std::rc::Rc<T>;
@@ -150,7 +331,7 @@ impl<
         F: FnMut(Self::Response<'_>) -> Result<R>,
     {
         self.parent
-            .query(query, |plugin| {
+            .query(ChainCommitmentQuery::Inner(query), |plugin| {
                 check(std::rc::Rc::new(
                     std::rc::Rc::try_unwrap(plugin)
                         .map_err(|_| ())
@@ -162,20 +343,19 @@ impl<
     }
 }
 
-impl<T: ClientTrait<Client<T, U, ID>>, U: Clone, const ID: &'static str> ClientTrait<U>
-    for ChainCommitmentPlugin<T, ID>
-{
+impl<T: ClientTrait<Client<T, U>>, U: Clone> ClientTrait<U> for ChainCommitmentPlugin<T> {
     type Client = T::Client;
 
     fn create_client(parent: U) -> Self::Client {
         T::create_client(Client {
             parent,
+            chain_id: Arc::new(Mutex::new(None)),
             marker: std::marker::PhantomData,
         })
     }
 }
 
-// impl<T, const ID: &'static str> Describe for ChainCommitmentPlugin<T, ID>
+// impl<T> Describe for ChainCommitmentPlugin<T>
 // where
 //     T: State + Describe + 'static,
 // {
@@ -198,16 +378,24 @@ mod abci {
     use super::*;
     use crate::abci::{BeginBlock, EndBlock, InitChain};
 
-    impl<T, const ID: &'static str> BeginBlock for ChainCommitmentPlugin<T, ID>
+    impl<T> BeginBlock for ChainCommitmentPlugin<T>
     where
         T: BeginBlock + State,
     {
         fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
+            let header_id = &ctx.header.chain_id;
+            if !header_id.is_empty() && self.chain_id != *header_id {
+                self.set_chain_id(header_id.clone())?;
+            }
+            if !self.chain_id.is_empty() {
+                self.add_context()?;
+            }
+
             self.inner.begin_block(ctx)
         }
     }
 
-    impl<T, const ID: &'static str> EndBlock for ChainCommitmentPlugin<T, ID>
+    impl<T> EndBlock for ChainCommitmentPlugin<T>
     where
         T: EndBlock + State,
     {
@@ -216,16 +404,19 @@ mod abci {
         }
     }
 
-    impl<T, const ID: &'static str> InitChain for ChainCommitmentPlugin<T, ID>
+    impl<T> InitChain for ChainCommitmentPlugin<T>
     where
         T: InitChain + State,
     {
         fn init_chain(&mut self, ctx: &InitChainCtx) -> Result<()> {
+            self.set_chain_id(ctx.chain_id.clone())?;
+            self.add_context()?;
+
             self.inner.init_chain(ctx)
         }
     }
 
-    impl<T, const ID: &'static str> crate::abci::AbciQuery for ChainCommitmentPlugin<T, ID>
+    impl<T> crate::abci::AbciQuery for ChainCommitmentPlugin<T>
     where
         T: crate::abci::AbciQuery + State,
     {
@@ -237,3 +428,148 @@ mod abci {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[derive(State, Encode, Decode, Default)]
+    struct Counter {
+        pub count: u64,
+    }
+
+    #[derive(Debug, Encode, Decode)]
+    enum CounterCall {
+        Increment,
+    }
+
+    impl CallTrait for Counter {
+        type Call = CounterCall;
+
+        fn call(&mut self, _call: Self::Call) -> Result<()> {
+            self.count += 1;
+            Ok(())
+        }
+    }
+
+    impl Query for Counter {
+        type Query = ();
+
+        fn query(&self, _query: Self::Query) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn committed_call(chain_id: &str) -> Vec<u8> {
+        let mut bytes = chain_id.as_bytes().to_vec();
+        CounterCall::Increment.encode_into(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    #[serial]
+    fn migrate_and_verify_chain_id() -> Result<()> {
+        let store = Store::default();
+
+        // the const generic layout only contained the inner state
+        let mut legacy_bytes = vec![0];
+        Counter { count: 5 }.flush(&mut legacy_bytes)?;
+        let mut plugin: ChainCommitmentPlugin<Counter> =
+            State::load(store.clone(), &mut legacy_bytes.as_slice())?;
+        assert_eq!(plugin.chain_id(), "");
+        assert_eq!(plugin.count, 5);
+        assert!(plugin.call(committed_call("")).is_err());
+
+        plugin.set_chain_id("testchain".to_string())?;
+        let mut bytes = vec![];
+        plugin.flush(&mut bytes)?;
+        let mut plugin: ChainCommitmentPlugin<Counter> = State::load(store, &mut bytes.as_slice())?;
+        assert_eq!(plugin.chain_id(), "testchain");
+
+        assert!(plugin.call(committed_call("otherchain")).is_err());
+        plugin.call(committed_call("testchain"))?;
+        assert_eq!(plugin.count, 6);
+        assert_eq!(&**Context::resolve::<ChainId>().unwrap(), "testchain");
+
+        Context::remove::<ChainId>();
+
+        Ok(())
+    }
+    /// Processes calls and queries on a shared plugin, counting the chain ID
+    /// queries.
+    #[derive(Clone)]
+    struct MockParent {
+        plugin: Arc<Mutex<ChainCommitmentPlugin<Counter>>>,
+        chain_id_queries: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl AsyncCall for MockParent {
+        type Call = Vec<u8>;
+
+        async fn call(&self, call: Self::Call) -> Result<()> {
+            self.plugin.lock().unwrap().call(call)
+        }
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl AsyncQuery for MockParent {
+        type Query = ChainCommitmentQuery<()>;
+        type Response<'a> = std::rc::Rc<ChainCommitmentPlugin<Counter>>;
+
+        async fn query<F, R>(&self, query: Self::Query, mut check: F) -> Result<R>
+        where
+            F: FnMut(Self::Response<'_>) -> Result<R>,
+        {
+            if let ChainCommitmentQuery::ChainId = query {
+                *self.chain_id_queries.lock().unwrap() += 1;
+            }
+            let plugin = self.plugin.lock().unwrap();
+            check(std::rc::Rc::new(ChainCommitmentPlugin {
+                chain_id: plugin.chain_id.clone(),
+                inner: Counter {
+                    count: plugin.count,
+                },
+            }))
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn client_caches_chain_id() -> Result<()> {
+        use futures_lite::future::block_on;
+
+        let mut plugin = ChainCommitmentPlugin::<Counter>::default();
+        plugin.set_chain_id("testchain".to_string())?;
+        let parent = MockParent {
+            plugin: Arc::new(Mutex::new(plugin)),
+            chain_id_queries: Arc::new(Mutex::new(0)),
+        };
+        let client: Client<Counter, _> = Client {
+            parent: parent.clone(),
+            chain_id: Arc::new(Mutex::new(None)),
+            marker: PhantomData,
+        };
+
+        block_on(client.call(CounterCall::Increment))?;
+        block_on(client.clone().call(CounterCall::Increment))?;
+        assert_eq!(*parent.chain_id_queries.lock().unwrap(), 1);
+
+        // the first call after the chain ID changes fails, then the chain ID
+        // is queried again and the call is retried
+        parent
+            .plugin
+            .lock()
+            .unwrap()
+            .set_chain_id("testchain-2".to_string())?;
+        block_on(client.call(CounterCall::Increment))?;
+        block_on(client.call(CounterCall::Increment))?;
+        assert_eq!(*parent.chain_id_queries.lock().unwrap(), 2);
+        assert_eq!(parent.plugin.lock().unwrap().count, 4);
+
+        Context::remove::<ChainId>();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,591 @@
+//! EIP-712 typed data, used to present calls to Ethereum wallets as
+//! human-readable fields rather than an opaque `personal_sign` blob.
+
+use super::sdk_compat::sdk;
+use crate::describe::{schema::camel_case, Children, Describe, Descriptor, VariantKind};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha3::{Digest, Keccak256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The EIP-712 version of the signing domain, bumped if the message types
+/// change.
+pub const DOMAIN_VERSION: &str = "1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// A struct value under construction, along with the types of its fields.
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    types: BTreeMap<String, Vec<Field>>,
+    fields: Vec<Field>,
+    values: Map<String, Value>,
+}
+
+impl Message {
+    /// Adds a `string` field.
+    pub fn add_string(&mut self, name: &str, value: impl ToString) -> Result<()> {
+        self.add(name, "string", Value::String(value.to_string()))
+    }
+
+    /// Adds a `bytes` field.
+    pub fn add_bytes(&mut self, name: &str, value: &[u8]) -> Result<()> {
+        let value = format!("0x{}", hex::encode(value));
+        self.add(name, "bytes", Value::String(value))
+    }
+
+    /// Adds a field holding `message` as a struct of type `type_name`.
+    pub fn add_struct(&mut self, name: &str, type_name: &str, message: Message) -> Result<()> {
+        let value = self.add_struct_type(type_name, message)?;
+        self.add(name, type_name, value)
+    }
+
+    /// Adds a field holding a JSON value, with a type inferred from the value.
+    /// Objects become structs named after `type_name` and their keys, arrays
+    /// must hold items of a single type, and numbers and nulls are rendered as
+    /// strings.
+    pub fn add_json(&mut self, name: &str, type_name: &str, value: &Value) -> Result<()> {
+        let (type_, value) = self.json_type(type_name, value)?;
+        self.add(name, &type_, value)
+    }
+
+    fn json_type(&mut self, type_name: &str, value: &Value) -> Result<(String, Value)> {
+        Ok(match value {
+            Value::Object(object) => {
+                let mut keys: Vec<_> = object.keys().collect();
+                keys.sort();
+
+                let mut message = Message::default();
+                for key in keys {
+                    let key_type_name = format!("{}{}", type_name, camel_case(key));
+                    message.add_json(key, &key_type_name, &object[key])?;
+                }
+                let value = self.add_struct_type(type_name, message)?;
+                (type_name.to_string(), value)
+            }
+            Value::Array(items) => {
+                let mut item_type: Option<String> = None;
+                let mut values = vec![];
+                for item in items {
+                    let (type_, value) = self.json_type(type_name, item)?;
+                    if *item_type.get_or_insert_with(|| type_.clone()) != type_ {
+                        return Err(Error::Signer(
+                            "EIP-712 arrays must contain items of a single type".into(),
+                        ));
+                    }
+                    values.push(value);
+                }
+                let item_type = item_type.unwrap_or_else(|| "string".to_string());
+                (format!("{}[]", item_type), Value::Array(values))
+            }
+            Value::Bool(_) => ("bool".to_string(), value.clone()),
+            Value::String(_) => ("string".to_string(), value.clone()),
+            Value::Number(_) | Value::Null => {
+                ("string".to_string(), Value::String(value.to_string()))
+            }
+        })
+    }
+
+    /// Defines `type_name` as the type of `message` and returns its value.
+    fn add_struct_type(&mut self, type_name: &str, message: Message) -> Result<Value> {
+        for (dep, fields) in message.types {
+            self.add_type(&dep, fields)?;
+        }
+        self.add_type(type_name, message.fields)?;
+        Ok(Value::Object(message.values))
+    }
+
+    fn add_type(&mut self, name: &str, fields: Vec<Field>) -> Result<()> {
+        match self.types.get(name) {
+            Some(existing) if *existing != fields => Err(Error::Signer(format!(
+                "Conflicting definitions of EIP-712 type '{}'",
+                name
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                self.types.insert(name.to_string(), fields);
+                Ok(())
+            }
+        }
+    }
+
+    fn add(&mut self, name: &str, type_: &str, value: Value) -> Result<()> {
+        if self.values.contains_key(name) {
+            return Err(Error::Signer(format!("Duplicate EIP-712 field '{}'", name)));
+        }
+        self.fields.push(Field {
+            name: name.to_string(),
+            type_: type_.to_string(),
+        });
+        self.values.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Renders encoded calls as message fields, so that wallets can show what a
+/// call does rather than its encoding.
+///
+/// Types implementing [Describe] are rendered from their call descriptors as a
+/// `method` path and its `args`, and plugins render their own part of the call
+/// before passing the rest to their inner type. Other calls are rendered as a
+/// `call` bytes field.
+pub trait Eip712Call {
+    /// Consumes a call from the front of `bytes` and adds its fields to
+    /// `message`.
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()>;
+}
+
+impl<T> Eip712Call for T {
+    default fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        message.add_bytes("call", bytes)?;
+        *bytes = &[];
+        Ok(())
+    }
+}
+
+impl<T: Describe> Eip712Call for T {
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        describe_call(&T::describe(), &mut vec![], bytes, message)
+    }
+}
+
+/// Renders the whole of an encoded call, failing if any bytes are left over.
+pub fn render_call<T: Eip712Call>(app: &T, mut bytes: &[u8], message: &mut Message) -> Result<()> {
+    app.eip712_call(&mut bytes, message)?;
+    if !bytes.is_empty() {
+        return Err(Error::Signer("Call has trailing bytes".into()));
+    }
+    Ok(())
+}
+
+fn describe_call(
+    desc: &Descriptor,
+    path: &mut Vec<String>,
+    bytes: &mut &[u8],
+    message: &mut Message,
+) -> Result<()> {
+    // children without described calls, e.g. collections, are rendered as
+    // bytes under the path of fields leading to them
+    if desc.calls().is_empty() {
+        if !path.is_empty() {
+            message.add_string("field", path.join("."))?;
+        }
+        message.add_bytes("call", bytes)?;
+        *bytes = &[];
+        return Ok(());
+    }
+
+    let (&index, rest) = bytes
+        .split_first()
+        .ok_or_else(|| Error::Signer("Unexpected end of call".into()))?;
+    *bytes = rest;
+
+    if index == 0 {
+        path.push("noop".to_string());
+        return message.add_string("method", path.join("."));
+    }
+
+    let variant = desc
+        .calls()
+        .iter()
+        .find(|variant| variant.index == index)
+        .ok_or_else(|| {
+            Error::Signer(format!(
+                "Unknown call variant {} for {}",
+                index, desc.type_name
+            ))
+        })?;
+    path.push(variant.name.clone());
+
+    match &variant.kind {
+        VariantKind::Field => {
+            let child = match desc.children() {
+                Children::Named(children) => children.iter().find(|c| c.name == variant.name),
+                _ => None,
+            }
+            .ok_or_else(|| Error::Signer(format!("Unknown call field {}", variant.name)))?;
+            describe_call(&child.desc, path, bytes, message)
+        }
+        VariantKind::Method(args) => {
+            message.add_string("method", path.join("."))?;
+            if !args.is_empty() {
+                let mut args_message = Message::default();
+                for arg in args {
+                    args_message.add_string(&arg.name, arg.render(bytes)?)?;
+                }
+                let type_name: String = path.iter().map(|name| camel_case(name)).collect();
+                message.add_struct("args", &format!("{}Args", type_name), args_message)?;
+            }
+            if !bytes.is_empty() {
+                message.add_bytes("subcall", bytes)?;
+                *bytes = &[];
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A typed data payload, serialized in the JSON format accepted by
+/// `eth_signTypedData_v4`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<Field>>,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+impl TypedData {
+    /// The typed data for a native call, with the call rendered as fields by
+    /// the app's [Eip712Call] implementation. The encoded call is included as
+    /// well, so the signature commits to the exact call even where the
+    /// rendering is lossy.
+    pub fn native<T: Eip712Call>(chain_id: &str, app: &T, call_bytes: &[u8]) -> Result<Self> {
+        let mut message = Message::default();
+        message.add_string("chain_id", chain_id)?;
+        render_call(app, call_bytes, &mut message)?;
+        message.add_bytes("call_bytes", call_bytes)?;
+
+        Ok(Self::new(chain_id, "Call", message))
+    }
+
+    /// The typed data for an amino SDK transaction, with the same fields as
+    /// its amino JSON sign doc except that each message is a separate
+    /// `msg<i>` field. Message types are inferred from their JSON values (see
+    /// [Message::add_json]).
+    pub fn sdk(tx: &sdk::Tx, chain_id: &str, nonce: u64) -> Result<Self> {
+        let tx = match tx {
+            sdk::Tx::Amino(tx) => tx,
+            sdk::Tx::Protobuf(_) => {
+                return Err(Error::Signer(
+                    "EIP-712 signing is only supported for amino transactions".into(),
+                ))
+            }
+        };
+
+        let mut message = Message::default();
+        message.add_type("Fee", fields(&[("amount", "Coin[]"), ("gas", "string")]))?;
+        message.add_type("Coin", fields(&[("amount", "string"), ("denom", "string")]))?;
+
+        message.add_string("account_number", "0")?;
+        message.add_string("chain_id", chain_id)?;
+        message.add("fee", "Fee", json!(tx.fee))?;
+        message.add_string("memo", &tx.memo)?;
+        for (i, msg) in tx.msg.iter().enumerate() {
+            let mut msg_message = Message::default();
+            msg_message.add_string("type", &msg.type_)?;
+            msg_message.add_json("value", &format!("Msg{}Value", i), &msg.value)?;
+            message.add_struct(&format!("msg{}", i), &format!("Msg{}", i), msg_message)?;
+        }
+        message.add_string("sequence", nonce)?;
+
+        Ok(Self::new(chain_id, "Tx", message))
+    }
+
+    fn new(chain_id: &str, primary_type: &str, message: Message) -> Self {
+        let mut types = domain_types();
+        types.extend(message.types);
+        types.insert(primary_type.to_string(), message.fields);
+
+        TypedData {
+            types,
+            primary_type: primary_type.to_string(),
+            domain: domain(chain_id),
+            message: Value::Object(message.values),
+        }
+    }
+
+    /// The hash signed by the wallet, `keccak256(0x1901 ‖ domainSeparator ‖
+    /// hashStruct(message))`.
+    pub fn hash(&self) -> Result<[u8; 32]> {
+        let mut hasher = Keccak256::new();
+        hasher.update([0x19, 0x01]);
+        hasher.update(self.hash_struct("EIP712Domain", &self.domain)?);
+        hasher.update(self.hash_struct(&self.primary_type, &self.message)?);
+        Ok(hasher.finalize().into())
+    }
+
+    fn type_fields(&self, name: &str) -> Result<&[Field]> {
+        self.types
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::Signer(format!("Unknown EIP-712 type '{}'", name)))
+    }
+
+    fn dependencies(&self, name: &str, deps: &mut BTreeSet<String>) {
+        let name = name.trim_end_matches("[]");
+        if deps.contains(name) {
+            return;
+        }
+        if let Some(fields) = self.types.get(name) {
+            deps.insert(name.to_string());
+            for field in fields {
+                self.dependencies(&field.type_, deps);
+            }
+        }
+    }
+
+    fn encode_type(&self, name: &str) -> Result<String> {
+        let mut deps = BTreeSet::new();
+        self.dependencies(name, &mut deps);
+        deps.remove(name);
+
+        let mut encoded = String::new();
+        for dep in std::iter::once(name).chain(deps.iter().map(String::as_str)) {
+            let fields: Vec<_> = self
+                .type_fields(dep)?
+                .iter()
+                .map(|field| format!("{} {}", field.type_, field.name))
+                .collect();
+            encoded.push_str(&format!("{}({})", dep, fields.join(",")));
+        }
+
+        Ok(encoded)
+    }
+
+    fn hash_struct(&self, name: &str, value: &Value) -> Result<[u8; 32]> {
+        let mut hasher = Keccak256::new();
+        hasher.update(Keccak256::digest(self.encode_type(name)?.as_bytes()));
+        for field in self.type_fields(name)? {
+            let value = value
+                .get(&field.name)
+                .ok_or_else(|| Error::Signer(format!("Missing EIP-712 field '{}'", field.name)))?;
+            hasher.update(self.encode_value(&field.type_, value)?);
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    fn encode_value(&self, type_: &str, value: &Value) -> Result<[u8; 32]> {
+        let invalid = || Error::Signer(format!("Invalid EIP-712 value for type '{}'", type_));
+
+        if let Some(item_type) = type_.strip_suffix("[]") {
+            let items = value.as_array().ok_or_else(invalid)?;
+            let mut hasher = Keccak256::new();
+            for item in items {
+                hasher.update(self.encode_value(item_type, item)?);
+            }
+            return Ok(hasher.finalize().into());
+        }
+
+        if self.types.contains_key(type_) {
+            return self.hash_struct(type_, value);
+        }
+
+        let mut word = [0; 32];
+        match type_ {
+            "string" => {
+                let s = value.as_str().ok_or_else(invalid)?;
+                word = Keccak256::digest(s.as_bytes()).into();
+            }
+            "bytes" => {
+                word = Keccak256::digest(parse_hex(value).ok_or_else(invalid)?).into();
+            }
+            "bool" => {
+                word[31] = value.as_bool().ok_or_else(invalid)? as u8;
+            }
+            "address" => {
+                let bytes = parse_hex(value)
+                    .filter(|b| b.len() == 20)
+                    .ok_or_else(invalid)?;
+                word[12..].copy_from_slice(bytes.as_slice());
+            }
+            _ if type_.starts_with("uint") => {
+                let n: u128 = match value {
+                    Value::Number(n) => n.as_u64().map(Into::into),
+                    Value::String(s) => s.parse().ok(),
+                    _ => None,
+                }
+                .ok_or_else(invalid)?;
+                word[16..].copy_from_slice(&n.to_be_bytes());
+            }
+            _ => {
+                return Err(Error::Signer(format!(
+                    "Unsupported EIP-712 type '{}'",
+                    type_
+                )))
+            }
+        }
+
+        Ok(word)
+    }
+}
+
+fn fields(fields: &[(&str, &str)]) -> Vec<Field> {
+    fields
+        .iter()
+        .map(|(name, type_)| Field {
+            name: name.to_string(),
+            type_: type_.to_string(),
+        })
+        .collect()
+}
+
+/// The domain omits `chainId`, since wallets require it to match the
+/// connected Ethereum network. The chain ID is included as the domain name
+/// instead.
+fn domain(chain_id: &str) -> Value {
+    json!({ "name": chain_id, "version": DOMAIN_VERSION })
+}
+
+fn domain_types() -> BTreeMap<String, Vec<Field>> {
+    let mut types = BTreeMap::new();
+    types.insert(
+        "EIP712Domain".to_string(),
+        fields(&[("name", "string"), ("version", "string")]),
+    );
+    types
+}
+
+fn parse_hex(value: &Value) -> Option<Vec<u8>> {
+    let s = value.as_str()?;
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call::Call;
+    use crate::coins::Address;
+    use crate::encoding::Encode;
+    use crate::params::ParamStore;
+    use crate::plugins::FeeParams;
+
+    #[test]
+    fn spec_example() {
+        // the example from the EIP-712 specification
+        let mut types = BTreeMap::new();
+        types.insert(
+            "EIP712Domain".to_string(),
+            fields(&[
+                ("name", "string"),
+                ("version", "string"),
+                ("chainId", "uint256"),
+                ("verifyingContract", "address"),
+            ]),
+        );
+        types.insert(
+            "Person".to_string(),
+            fields(&[("name", "string"), ("wallet", "address")]),
+        );
+        types.insert(
+            "Mail".to_string(),
+            fields(&[("from", "Person"), ("to", "Person"), ("contents", "string")]),
+        );
+        let data = TypedData {
+            types,
+            primary_type: "Mail".to_string(),
+            domain: json!({
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC",
+            }),
+            message: json!({
+                "from": {
+                    "name": "Cow",
+                    "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826",
+                },
+                "to": {
+                    "name": "Bob",
+                    "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB",
+                },
+                "contents": "Hello, Bob!",
+            }),
+        };
+
+        assert_eq!(
+            data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            hex::encode(data.hash().unwrap()),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+
+    #[test]
+    fn native() {
+        let admin = Address::from_pubkey([2; 33]);
+        type ParamsCall = <ParamStore<FeeParams> as Call>::Call;
+        let call_bytes = ParamsCall::MethodSetAdmin(admin, vec![]).encode().unwrap();
+        let app = ParamStore::<FeeParams>::default();
+
+        let data = TypedData::native("testchain", &app, &call_bytes).unwrap();
+        assert_eq!(data.message["method"], "set_admin");
+        assert_eq!(data.message["args"]["admin"], admin.to_string());
+        assert_eq!(
+            data.message["call_bytes"],
+            format!("0x{}", hex::encode(&call_bytes))
+        );
+        assert_eq!(data.types["SetAdminArgs"], fields(&[("admin", "string")]));
+        assert_eq!(
+            data.encode_type("Call").unwrap(),
+            "Call(string chain_id,string method,SetAdminArgs args,bytes call_bytes)SetAdminArgs(string admin)"
+        );
+        assert_ne!(
+            data.hash().unwrap(),
+            TypedData::native("otherchain", &app, &call_bytes)
+                .unwrap()
+                .hash()
+                .unwrap()
+        );
+
+        let noop = TypedData::native("testchain", &app, &[0]).unwrap();
+        assert_eq!(noop.message["method"], "noop");
+        assert!(TypedData::native("testchain", &app, &[0, 0]).is_err());
+
+        // types without call descriptors are rendered as bytes
+        let data = TypedData::native("testchain", &(), &[1, 2, 3]).unwrap();
+        assert_eq!(data.message["call"], "0x010203");
+    }
+
+    #[test]
+    fn sdk() {
+        let tx = sdk::Tx::Amino(sdk::AminoTx {
+            msg: vec![sdk::Msg {
+                type_: "cosmos-sdk/MsgSend".to_string(),
+                value: json!({
+                    "from_address": "nomic1a",
+                    "to_address": "nomic1b",
+                    "amount": [{ "amount": "100", "denom": "unom" }],
+                }),
+            }],
+            fee: sdk::Fee {
+                amount: vec![],
+                gas: "10000".to_string(),
+            },
+            memo: String::new(),
+            signatures: vec![],
+        });
+
+        let data = TypedData::sdk(&tx, "testchain", 1).unwrap();
+        assert_eq!(
+            data.encode_type("Msg0").unwrap(),
+            "Msg0(string type,Msg0Value value)\
+             Msg0Value(Msg0ValueAmount[] amount,string from_address,string to_address)\
+             Msg0ValueAmount(string amount,string denom)"
+        );
+        assert_eq!(data.message["msg0"]["value"]["to_address"], "nomic1b");
+        assert_eq!(data.message["sequence"], "1");
+        data.hash().unwrap();
+
+        let mut message = Message::default();
+        assert!(message
+            .add_json("value", "Value", &json!([{ "a": "1" }, { "b": "2" }]))
+            .is_err());
+        assert!(message
+            .add_json("value", "Value", &json!([1, true]))
+            .is_err());
+        message
+            .add_json("value", "Value", &json!({ "n": 1, "x": null }))
+            .unwrap();
+        assert_eq!(message.values["value"], json!({ "n": "1", "x": "null" }));
+    }
+}
@@ -1,29 +1,216 @@
 use serde::{Deserialize, Serialize};
 
+use super::eip712::{Eip712Call, Message};
 use super::sdk_compat::{sdk::Tx as SdkTx, ConvertSdkTx};
-use super::Paid;
+use super::{CallPath, CheckTxInfo, Paid, Recheck};
 use crate::call::Call;
 use crate::client::{AsyncCall, AsyncQuery, Client};
-use crate::coins::{Coin, Symbol};
+use crate::coins::{Amount, Coin, Symbol};
+use crate::compat_mode;
 use crate::context::{Context, GetContext};
+use crate::describe::Describe;
 use crate::encoding::{Decode, Encode};
 use crate::migrate::{MigrateFrom, MigrateInto};
+use crate::orga;
+use crate::params::{decode_param, ParamStore, Params};
 use crate::query::Query;
 use crate::state::State;
+use crate::store::{Read, Store, Write};
 use crate::{Error, Result};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
-pub const MIN_FEE: u64 = 10_000;
+/// Returns the fee parameters of the enclosing [FeePlugin], which are
+/// available as context while its inner app handles calls and blocks. Apps can
+/// change them through this, e.g. from a call which forwards to
+/// [ParamStore::update].
+pub fn fee_params<'a>() -> Option<&'a mut ParamStore<FeeParams>> {
+    Context::resolve::<ParamStore<FeeParams>>()
+}
+
+/// The fee charged for each call: the `min_fee` parameter of the enclosing
+/// [FeePlugin], or its default outside of one.
+pub fn min_fee() -> u64 {
+    fee_params().map_or_else(|| FeeParams::default().min_fee, |params| params.min_fee)
+}
+
+#[orga(skip(Default))]
+#[derive(Clone, Debug, Describe)]
+pub struct FeeParams {
+    pub min_fee: u64,
+}
+
+// The minimum fee is used until the fee parameters are changed.
+impl Default for FeeParams {
+    fn default() -> Self {
+        Self { min_fee: 10_000 }
+    }
+}
+
+impl Params for FeeParams {
+    const MODULE: &'static str = "fee";
+
+    fn validate(&self) -> Result<()> {
+        if self.min_fee == 0 {
+            return Err(Error::Coins("Minimum fee must be positive".into()));
+        }
+
+        Ok(())
+    }
+
+    fn set_param(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match key {
+            "min_fee" => self.min_fee = decode_param(value)?,
+            _ => return Err(Error::Coins(format!("Unknown fee parameter {}", key))),
+        }
+
+        Ok(())
+    }
+}
+
+/// The state version which added the fee parameters. Version 1 added the fees
+/// collected in the current block, and version 0 is the transparent layout
+/// from when fees were burned.
+const VERSION: u8 = 2;
+
+/// Where the collected fees and parameters are kept in compat mode, whose
+/// layout holds only the inner state. The inner state must not use this key,
+/// e.g. by using a field index it does not have.
+const COMPAT_KEY: &[u8] = &[0xff];
 
-#[derive(Encode, Decode, Default, Serialize, Deserialize, State)]
-#[state(transparent)]
+/// Charges the `min_fee` of its [FeeParams] for each call.
+///
+/// Fees are collected during the block and handed to the app in the next
+/// BeginBlock as the [CollectedFees] context, e.g. for
+/// [Distribution](crate::coins::Distribution) to pay them out. Fees which are
+/// not taken from the context are burned.
+///
+/// The parameters are stored with the plugin, and are published as context
+/// while the inner app runs (see [fee_params]), so changes from governance or
+/// the app persist with the plugin's state.
+#[derive(Encode, Decode, Default, Serialize, Deserialize)]
 pub struct FeePlugin<S, T> {
-    #[state(skip)]
     _symbol: PhantomData<S>,
+    #[serde(skip)]
+    collected: Amount,
+    #[serde(skip)]
+    params: ParamStore<FeeParams>,
+    #[serde(skip)]
+    store: Store,
     inner: T,
 }
 
+impl<S, T: State> State for FeePlugin<S, T> {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        self.store = store.clone();
+        self.inner.attach(store)
+    }
+
+    // Compat mode uses the version 0 layout, which has no collected fees or
+    // parameters, so they are kept under COMPAT_KEY instead.
+    fn flush<W: std::io::Write>(mut self, out: &mut W) -> Result<()> {
+        if compat_mode() {
+            let mut bytes = vec![];
+            self.collected.flush(&mut bytes)?;
+            self.params.flush(&mut bytes)?;
+            self.store.put(COMPAT_KEY.to_vec(), bytes)?;
+        } else {
+            out.write_all(&[VERSION])?;
+            self.collected.flush(out)?;
+            self.params.flush(out)?;
+        }
+        self.inner.flush(out)
+    }
+
+    fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+        let version = if compat_mode() {
+            0
+        } else {
+            let version = *bytes
+                .first()
+                .ok_or_else(|| Error::State("Unexpected EOF".into()))?;
+            *bytes = &bytes[1..];
+            version
+        };
+
+        let (collected, params) = match version {
+            0 if compat_mode() => match store.get(COMPAT_KEY)? {
+                Some(bytes) => {
+                    let mut bytes = bytes.as_slice();
+                    (
+                        Amount::load(store.clone(), &mut bytes)?,
+                        ParamStore::load(store.clone(), &mut bytes)?,
+                    )
+                }
+                None => Default::default(),
+            },
+            0 => Default::default(),
+            1 => (Amount::load(store.clone(), bytes)?, Default::default()),
+            VERSION => (
+                Amount::load(store.clone(), bytes)?,
+                ParamStore::load(store.clone(), bytes)?,
+            ),
+            _ => {
+                return Err(Error::State(format!(
+                    "Expected version {}, got {} for FeePlugin",
+                    VERSION, version
+                )))
+            }
+        };
+
+        Ok(Self {
+            _symbol: PhantomData,
+            collected,
+            params,
+            store: store.clone(),
+            inner: T::load(store, bytes)?,
+        })
+    }
+}
+
+impl<S, T> FeePlugin<S, T> {
+    /// Replaces the fee parameters, e.g. at genesis.
+    pub fn set_params(&mut self, params: FeeParams) -> Result<()> {
+        self.params.set(params)
+    }
+
+    pub fn params(&self) -> &ParamStore<FeeParams> {
+        &self.params
+    }
+
+    /// Runs `op` on the inner app with the parameters published as context,
+    /// keeping any changes made to them.
+    fn with_params<R>(&mut self, op: impl FnOnce(&mut T) -> Result<R>) -> Result<R> {
+        let guard = Context::scope(std::mem::take(&mut self.params));
+        let res = op(&mut self.inner);
+        if let Some(params) = fee_params() {
+            self.params = std::mem::take(params);
+        }
+        drop(guard);
+
+        res
+    }
+}
+
+/// The fees collected by [FeePlugin] in the previous block, available as
+/// context during BeginBlock.
+#[derive(Default)]
+pub struct CollectedFees {
+    map: HashMap<u8, Amount>,
+}
+
+impl CollectedFees {
+    /// Takes all of the collected fees of the given symbol.
+    pub fn take<S: Symbol>(&mut self) -> Coin<S> {
+        S::mint(self.map.remove(&S::INDEX).unwrap_or_default())
+    }
+
+    pub fn balance<S: Symbol>(&self) -> Amount {
+        self.map.get(&S::INDEX).copied().unwrap_or_default()
+    }
+}
+
 impl<S1, S2, T1, T2> MigrateFrom<FeePlugin<S1, T1>> for FeePlugin<S2, T2>
 where
     T1: MigrateInto<T2>,
@@ -31,6 +218,9 @@ where
     fn migrate_from(other: FeePlugin<S1, T1>) -> Result<Self> {
         Ok(Self {
             _symbol: other._symbol.migrate_into()?,
+            collected: other.collected,
+            params: other.params,
+            store: other.store,
             inner: other.inner.migrate_into()?,
         })
     }
@@ -62,16 +252,37 @@ impl<S: Symbol, T: Call + State> Call for FeePlugin<S, T> {
     type Call = T::Call;
 
     fn call(&mut self, call: Self::Call) -> Result<()> {
+        let min_fee = self.params.min_fee;
         let paid = self
             .context::<Paid>()
             .ok_or_else(|| Error::Coins("Minimum fee not paid".into()))?;
 
-        if !paid.running_payer && !paid.fee_disabled {
-            let fee_payment: Coin<S> = paid.take(MIN_FEE)?;
-            fee_payment.burn();
+        let running_payer = paid.running_payer;
+        if !running_payer && !paid.fee_disabled {
+            let fee_payment: Coin<S> = paid.take_fee(min_fee)?;
+            self.collected = (self.collected + fee_payment.amount)?;
+            if let Some(info) = Context::resolve::<CheckTxInfo>() {
+                info.fee = (info.fee + fee_payment.amount)?;
+            }
+        }
+
+        if !running_payer && self.context::<Recheck>().is_some() {
+            return Ok(());
         }
 
-        self.inner.call(call)
+        self.with_params(|inner| inner.call(call))
+    }
+}
+
+impl<S, T: Eip712Call> Eip712Call for FeePlugin<S, T> {
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        self.inner.eip712_call(bytes, message)
+    }
+}
+
+impl<S, T: CallPath> CallPath for FeePlugin<S, T> {
+    fn call_path(&self, call_bytes: &[u8]) -> Option<String> {
+        self.inner.call_path(call_bytes)
     }
 }
 
@@ -183,7 +394,12 @@ mod abci {
         T: BeginBlock + State,
     {
         fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
-            self.inner.begin_block(ctx)
+            let mut fees = CollectedFees::default();
+            fees.map
+                .insert(S::INDEX, std::mem::take(&mut self.collected));
+            let _fees = Context::scope(fees);
+
+            self.with_params(|inner| inner.begin_block(ctx))
         }
     }
 
@@ -193,7 +409,7 @@ mod abci {
         T: EndBlock + State,
     {
         fn end_block(&mut self, ctx: &EndBlockCtx) -> Result<()> {
-            self.inner.end_block(ctx)
+            self.with_params(|inner| inner.end_block(ctx))
         }
     }
 
@@ -203,7 +419,7 @@ mod abci {
         T: InitChain + State + Call,
     {
         fn init_chain(&mut self, ctx: &InitChainCtx) -> Result<()> {
-            self.inner.init_chain(ctx)
+            self.with_params(|inner| inner.init_chain(ctx))
         }
     }
 
@@ -220,3 +436,41 @@ mod abci {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[orga]
+    #[derive(Clone, Debug)]
+    struct Simp;
+    impl Symbol for Simp {
+        const INDEX: u8 = 0;
+    }
+
+    #[test]
+    #[serial]
+    fn compat_keeps_fees_and_params() -> Result<()> {
+        let store = Store::default();
+        let mut plugin: FeePlugin<Simp, ()> = Default::default();
+        plugin.attach(store.clone())?;
+        plugin.collected = Amount::new(100);
+        plugin.set_params(FeeParams { min_fee: 5 })?;
+
+        crate::set_compat_mode(true);
+        let mut bytes = vec![];
+        let res = plugin
+            .flush(&mut bytes)
+            .and_then(|()| FeePlugin::<Simp, ()>::load(store, &mut bytes.as_slice()));
+        crate::set_compat_mode(false);
+
+        // the inner state's encoding is unchanged
+        assert!(bytes.is_empty());
+        let plugin = res?;
+        assert_eq!(plugin.collected, Amount::new(100));
+        assert_eq!(plugin.params().min_fee, 5);
+
+        Ok(())
+    }
+}
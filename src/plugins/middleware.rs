@@ -0,0 +1,400 @@
+use super::sdk_compat::{sdk::Tx as SdkTx, ConvertSdkTx};
+use crate::call::Call;
+use crate::client::{AsyncCall, AsyncQuery, Client};
+use crate::migrate::{MigrateFrom, MigrateInto};
+use crate::query::Query;
+use crate::state::State;
+use crate::store::{Read, Store, Write};
+use crate::{Error, Result};
+use std::ops::{Deref, DerefMut};
+
+/// Hooks which run around the calls of an app, added to a plugin stack with
+/// [MiddlewarePlugin].
+///
+/// Hooks can read and add [Context](crate::context::Context) values through
+/// [GetContext](crate::context::GetContext), and can keep state of their own,
+/// stored under [Middleware::KEY] rather than changing the layout of the state
+/// they wrap. Stateless middleware can use a unit struct.
+pub trait Middleware<T: Call>: State + Default {
+    /// The key in the wrapped state's store where the middleware's state is
+    /// kept. It must not be used by the wrapped state, e.g. by using a field
+    /// index the wrapped state does not have.
+    const KEY: &'static [u8];
+
+    /// Runs before the inner call. Returning an error rejects the call without
+    /// running it.
+    fn before_call(&mut self, _call: &T::Call) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the inner call succeeded.
+    fn after_call(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs after the inner call failed, with its error. Returning `Ok`
+    /// recovers from the error, otherwise the returned error is the result of
+    /// the call.
+    ///
+    /// Failed calls are not rolled back: whatever the inner call changed
+    /// before it failed is written with the rest of the transaction, whether
+    /// or not this hook recovers. Only recover from errors of calls which fail
+    /// before changing state.
+    fn on_error(&mut self, error: Error) -> Result<()> {
+        Err(error)
+    }
+}
+
+/// Runs the hooks of a [Middleware] around the calls of the inner state,
+/// passing everything else through unchanged.
+///
+/// Middleware can be stacked, e.g. `MiddlewarePlugin<RateLimit,
+/// MiddlewarePlugin<CallLog, App>>`, as long as their keys differ.
+#[derive(Default)]
+pub struct MiddlewarePlugin<M, T> {
+    middleware: M,
+    inner: T,
+    store: Store,
+}
+
+impl<M, T> MiddlewarePlugin<M, T> {
+    pub fn middleware(&self) -> &M {
+        &self.middleware
+    }
+
+    pub fn middleware_mut(&mut self) -> &mut M {
+        &mut self.middleware
+    }
+}
+
+impl<M: Middleware<T>, T: Call + State> State for MiddlewarePlugin<M, T> {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        self.middleware.attach(store.sub(M::KEY))?;
+        self.inner.attach(store.clone())?;
+        self.store = store;
+
+        Ok(())
+    }
+
+    fn flush<W: std::io::Write>(mut self, out: &mut W) -> Result<()> {
+        let mut middleware_bytes = vec![];
+        self.middleware.flush(&mut middleware_bytes)?;
+        self.store.put(M::KEY.to_vec(), middleware_bytes)?;
+
+        self.inner.flush(out)
+    }
+
+    fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+        let middleware_store = store.sub(M::KEY);
+        let middleware = match store.get(M::KEY)? {
+            Some(middleware_bytes) => M::load(middleware_store, &mut middleware_bytes.as_slice())?,
+            None => {
+                let mut middleware = M::default();
+                middleware.attach(middleware_store)?;
+                middleware
+            }
+        };
+
+        Ok(Self {
+            middleware,
+            inner: T::load(store.clone(), bytes)?,
+            store,
+        })
+    }
+}
+
+impl<M1, M2, T1, T2> MigrateFrom<MiddlewarePlugin<M1, T1>> for MiddlewarePlugin<M2, T2>
+where
+    M1: MigrateInto<M2>,
+    T1: MigrateInto<T2>,
+{
+    fn migrate_from(other: MiddlewarePlugin<M1, T1>) -> Result<Self> {
+        Ok(Self {
+            middleware: other.middleware.migrate_into()?,
+            inner: other.inner.migrate_into()?,
+            store: other.store,
+        })
+    }
+}
+
+impl<M: Middleware<T>, T: Call> Call for MiddlewarePlugin<M, T> {
+    type Call = T::Call;
+
+    fn call(&mut self, call: Self::Call) -> Result<()> {
+        self.middleware.before_call(&call)?;
+
+        match self.inner.call(call) {
+            Ok(()) => self.middleware.after_call(),
+            Err(err) => self.middleware.on_error(err),
+        }
+    }
+}
+
+impl<M, T: Query> Query for MiddlewarePlugin<M, T> {
+    type Query = T::Query;
+
+    fn query(&self, query: Self::Query) -> Result<()> {
+        self.inner.query(query)
+    }
+}
+
+impl<M, T: ConvertSdkTx> ConvertSdkTx for MiddlewarePlugin<M, T> {
+    type Output = T::Output;
+
+    fn convert(&self, sdk_tx: &SdkTx) -> Result<T::Output> {
+        self.inner.convert(sdk_tx)
+    }
+}
+
+pub struct MiddlewareAdapter<M, T, U: Clone> {
+    parent: U,
+    marker: std::marker::PhantomData<fn(M, T)>,
+}
+
+unsafe impl<M, T, U: Send + Clone> Send for MiddlewareAdapter<M, T, U> {}
+
+impl<M, T, U: Clone> Clone for MiddlewareAdapter<M, T, U> {
+    fn clone(&self) -> Self {
+        MiddlewareAdapter {
+            parent: self.parent.clone(),
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<M, T: Call, U: AsyncCall<Call = T::Call> + Clone> AsyncCall for MiddlewareAdapter<M, T, U>
+where
+    T::Call: Send,
+    U: Send,
+{
+    type Call = T::Call;
+
+    async fn call(&self, call: Self::Call) -> Result<()> {
+        self.parent.call(call).await
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<
+        M,
+        T: Query + State,
+        U: for<'a> AsyncQuery<Query = T::Query, Response<'a> = std::rc::Rc<MiddlewarePlugin<M, T>>>
+            + Clone,
+    > AsyncQuery for MiddlewareAdapter<M, T, U>
+{
+    type Query = T::Query;
+    type Response<'a> = std::rc::Rc<T>;
+
+    async fn query<F, R>(&self, query: Self::Query, mut check: F) -> Result<R>
+    where
+        F: FnMut(Self::Response<'_>) -> Result<R>,
+    {
+        self.parent
+            .query(query, |plugin| {
+                check(std::rc::Rc::new(
+                    std::rc::Rc::try_unwrap(plugin)
+                        .map_err(|_| ())
+                        .unwrap()
+                        .inner,
+                ))
+            })
+            .await
+    }
+}
+
+impl<M, T: Client<MiddlewareAdapter<M, T, U>>, U: Clone> Client<U> for MiddlewarePlugin<M, T> {
+    type Client = T::Client;
+
+    fn create_client(parent: U) -> Self::Client {
+        T::create_client(MiddlewareAdapter {
+            parent,
+            marker: std::marker::PhantomData,
+        })
+    }
+}
+
+impl<M, T> Deref for MiddlewarePlugin<M, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<M, T> DerefMut for MiddlewarePlugin<M, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+// TODO: Remove dependency on ABCI for this otherwise-pure plugin.
+#[cfg(feature = "abci")]
+mod abci {
+    use super::super::{BeginBlockCtx, EndBlockCtx, InitChainCtx};
+    use super::*;
+    use crate::abci::{BeginBlock, EndBlock, InitChain};
+
+    impl<M, T> BeginBlock for MiddlewarePlugin<M, T>
+    where
+        T: BeginBlock + State,
+    {
+        fn begin_block(&mut self, ctx: &BeginBlockCtx) -> Result<()> {
+            self.inner.begin_block(ctx)
+        }
+    }
+
+    impl<M, T> EndBlock for MiddlewarePlugin<M, T>
+    where
+        T: EndBlock + State,
+    {
+        fn end_block(&mut self, ctx: &EndBlockCtx) -> Result<()> {
+            self.inner.end_block(ctx)
+        }
+    }
+
+    impl<M, T> InitChain for MiddlewarePlugin<M, T>
+    where
+        T: InitChain + State,
+    {
+        fn init_chain(&mut self, ctx: &InitChainCtx) -> Result<()> {
+            self.inner.init_chain(ctx)
+        }
+    }
+
+    impl<M, T> crate::abci::AbciQuery for MiddlewarePlugin<M, T>
+    where
+        T: crate::abci::AbciQuery + State,
+    {
+        fn abci_query(
+            &self,
+            request: &tendermint_proto::abci::RequestQuery,
+        ) -> Result<tendermint_proto::abci::ResponseQuery> {
+            self.inner.abci_query(request)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Signer;
+    use super::*;
+    use crate::context::{Context, GetContext};
+    use crate::encoding::{Decode, Encode};
+    use crate::orga;
+    use serial_test::serial;
+
+    #[derive(State, Encode, Decode, Default)]
+    struct Counter {
+        pub count: u64,
+    }
+
+    #[derive(Debug, Encode, Decode)]
+    enum CounterCall {
+        Increment,
+        Fail,
+        IncrementAndFail,
+    }
+
+    impl Call for Counter {
+        type Call = CounterCall;
+
+        fn call(&mut self, call: Self::Call) -> Result<()> {
+            match call {
+                CounterCall::Increment => self.count += 1,
+                CounterCall::Fail => return Err(Error::App("Call failed".into())),
+                CounterCall::IncrementAndFail => {
+                    self.count += 1;
+                    return Err(Error::App("Call failed".into()));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(State, Encode, Decode, Default)]
+    struct CallLog {
+        succeeded: u64,
+        failed: u64,
+    }
+
+    impl Middleware<Counter> for CallLog {
+        const KEY: &'static [u8] = &[0xff];
+
+        fn before_call(&mut self, _call: &CounterCall) -> Result<()> {
+            match self.context::<Signer>() {
+                Some(Signer { signer: Some(_) }) => Ok(()),
+                _ => Err(Error::App("Unsigned call".into())),
+            }
+        }
+
+        fn after_call(&mut self) -> Result<()> {
+            self.succeeded += 1;
+            Ok(())
+        }
+
+        fn on_error(&mut self, error: Error) -> Result<()> {
+            self.failed += 1;
+            Err(error)
+        }
+    }
+
+    #[orga]
+    struct Recover;
+
+    impl Middleware<Counter> for Recover {
+        const KEY: &'static [u8] = &[0xfe];
+
+        fn on_error(&mut self, _error: Error) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn hooks_and_state() -> Result<()> {
+        let store = Store::default();
+        let mut plugin: MiddlewarePlugin<CallLog, Counter> = Default::default();
+        plugin.attach(store.clone())?;
+
+        Context::add(Signer { signer: None });
+        assert!(plugin.call(CounterCall::Increment).is_err());
+        assert_eq!(plugin.count, 0);
+
+        Context::add(Signer {
+            signer: Some([0; 20].into()),
+        });
+        plugin.call(CounterCall::Increment)?;
+        assert!(plugin.call(CounterCall::Fail).is_err());
+        assert_eq!(plugin.count, 1);
+
+        // the middleware state does not change the wrapped state's encoding
+        let mut bytes = vec![];
+        plugin.flush(&mut bytes)?;
+        let mut counter_bytes = vec![];
+        Counter { count: 1 }.flush(&mut counter_bytes)?;
+        assert_eq!(bytes, counter_bytes);
+
+        let plugin: MiddlewarePlugin<CallLog, Counter> = State::load(store, &mut bytes.as_slice())?;
+        assert_eq!(plugin.middleware().succeeded, 1);
+        assert_eq!(plugin.middleware().failed, 1);
+
+        Context::remove::<Signer>();
+
+        Ok(())
+    }
+
+    #[test]
+    fn recovering_keeps_partial_changes() -> Result<()> {
+        let mut plugin: MiddlewarePlugin<Recover, Counter> = Default::default();
+        plugin.attach(Store::default())?;
+
+        plugin.call(CounterCall::Fail)?;
+        assert_eq!(plugin.count, 0);
+
+        plugin.call(CounterCall::IncrementAndFail)?;
+        assert_eq!(plugin.count, 1);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,11 @@
 mod signer;
 pub use signer::*;
 
+mod multisig;
+pub use multisig::*;
+
+pub mod eip712;
+
 mod nonce;
 pub use nonce::*;
 
@@ -13,8 +18,14 @@ pub use payable::*;
 mod fee;
 pub use fee::*;
 
+mod authz;
+pub use authz::*;
+
+mod middleware;
+pub use middleware::*;
+
 pub mod chain_commitment;
-pub use chain_commitment::{ChainCommitmentPlugin, ChainId};
+pub use chain_commitment::{ChainCommitmentPlugin, ChainCommitmentQuery, ChainId};
 
 pub mod sdk_compat;
 pub use sdk_compat::{ConvertSdkTx, SdkCompatPlugin};
@@ -29,10 +40,10 @@ macro_rules! type_chain {
     };
 }
 
-pub type DefaultPlugins<S, T, const ID: &'static str> = type_chain! {
+pub type DefaultPlugins<S, T> = type_chain! {
     SdkCompatPlugin<S, _>,
     SignerPlugin<_>,
-    ChainCommitmentPlugin<_, ID>,
+    ChainCommitmentPlugin<_>,
     NoncePlugin<_>,
     PayablePlugin<_>,
     FeePlugin<S, _>,
@@ -0,0 +1,241 @@
+use super::{SigType, SignerCall};
+use crate::coins::Address;
+use crate::encoding::{Decode, Encode, LengthVec};
+use crate::{Error, Result};
+use secp256k1::hashes::sha256;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+/// Amino prefix of `tendermint/PubKeyMultisigThreshold`.
+const AMINO_MULTISIG_PREFIX: [u8; 4] = [0x22, 0xc1, 0xf7, 0xe2];
+/// Amino prefix of `tendermint/PubKeySecp256k1`.
+const AMINO_SECP256K1_PREFIX: [u8; 4] = [0xeb, 0x5a, 0xe9, 0x87];
+
+/// A k-of-n threshold multisig key set.
+///
+/// Keys are sorted by their individual addresses, so the same set of keys and
+/// threshold always results in the same multisig address. The address is
+/// derived the same way as the cosmos `LegacyAminoPubKey`, so it matches a
+/// multisig created with `--multisig` in cosmos-sdk's key tooling.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+pub struct Multisig {
+    pub threshold: u8,
+    pub pubkeys: LengthVec<u8, [u8; 33]>,
+}
+
+/// The signatures for a call from a multisig address, one optional signature
+/// per key in the same order as the multisig's keys.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct MultisigSignatures {
+    pub multisig: Multisig,
+    pub signatures: LengthVec<u8, Option<[u8; 64]>>,
+}
+
+/// A signature from one member of a multisig, collected offline and combined
+/// with [Multisig::combine] before broadcasting.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct PartialSignature {
+    pub pubkey: [u8; 33],
+    pub signature: [u8; 64],
+}
+
+impl Multisig {
+    pub fn new(threshold: u8, mut pubkeys: Vec<[u8; 33]>) -> Result<Self> {
+        pubkeys.sort_by_key(|pubkey| Address::from_pubkey(*pubkey).bytes());
+        pubkeys.dedup();
+
+        let len: u8 = pubkeys
+            .len()
+            .try_into()
+            .map_err(|_| Error::Signer("Too many multisig keys".into()))?;
+        let multisig = Multisig {
+            threshold,
+            pubkeys: LengthVec::new(len, pubkeys),
+        };
+        multisig.validate()?;
+
+        Ok(multisig)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.threshold == 0 || self.threshold as usize > self.pubkeys.len() {
+            return Err(Error::Signer(
+                "Multisig threshold must be between 1 and the number of keys".into(),
+            ));
+        }
+
+        let sorted = self.pubkeys.windows(2).all(|pair| {
+            Address::from_pubkey(pair[0]).bytes() < Address::from_pubkey(pair[1]).bytes()
+        });
+        if !sorted {
+            return Err(Error::Signer(
+                "Multisig keys must be unique and sorted by address".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn address(&self) -> Address {
+        let mut bytes = AMINO_MULTISIG_PREFIX.to_vec();
+        bytes.push(0x08);
+        write_uvarint(&mut bytes, self.threshold as u64);
+        for pubkey in self.pubkeys.iter() {
+            bytes.push(0x12);
+            write_uvarint(&mut bytes, (AMINO_SECP256K1_PREFIX.len() + 1 + 33) as u64);
+            bytes.extend_from_slice(&AMINO_SECP256K1_PREFIX);
+            bytes.push(33);
+            bytes.extend_from_slice(pubkey);
+        }
+
+        let hash = Sha256::digest(bytes.as_slice());
+        let mut addr = [0; Address::LENGTH];
+        addr.copy_from_slice(&hash[..Address::LENGTH]);
+        addr.into()
+    }
+
+    /// Verifies that at least `threshold` of the keys signed the call bytes,
+    /// returning the multisig address.
+    pub fn verify(&self, call_bytes: &[u8], signatures: &[Option<[u8; 64]>]) -> Result<Address> {
+        self.validate()?;
+        if signatures.len() != self.pubkeys.len() {
+            return Err(Error::Signer(
+                "Number of signatures does not match number of multisig keys".into(),
+            ));
+        }
+
+        let secp = Secp256k1::verification_only();
+        let msg = Message::from_hashed_data::<sha256::Hash>(call_bytes);
+        let mut count = 0;
+        for (pubkey, signature) in self.pubkeys.iter().zip(signatures) {
+            let signature = match signature {
+                Some(signature) => Signature::from_compact(signature)?,
+                None => continue,
+            };
+            let pubkey = PublicKey::from_slice(pubkey)?;
+            #[cfg(not(fuzzing))]
+            secp.verify_ecdsa(&msg, &signature, &pubkey)?;
+            count += 1;
+        }
+
+        if count < self.threshold {
+            return Err(Error::Signer(format!(
+                "Multisig requires {} signatures, got {}",
+                self.threshold, count
+            )));
+        }
+
+        Ok(self.address())
+    }
+
+    /// Combines partial signatures of the call bytes into a call signed by the
+    /// multisig.
+    pub fn combine(
+        &self,
+        call_bytes: Vec<u8>,
+        partials: &[PartialSignature],
+    ) -> Result<SignerCall> {
+        let mut signatures = vec![None; self.pubkeys.len()];
+        for partial in partials {
+            let index = self
+                .pubkeys
+                .iter()
+                .position(|pubkey| *pubkey == partial.pubkey)
+                .ok_or_else(|| Error::Signer("Signature is from a non-member key".into()))?;
+            signatures[index] = Some(partial.signature);
+        }
+
+        self.verify(call_bytes.as_slice(), signatures.as_slice())?;
+
+        Ok(SignerCall {
+            signature: None,
+            pubkey: None,
+            sigtype: SigType::Multisig(MultisigSignatures {
+                multisig: self.clone(),
+                signatures: LengthVec::new(self.pubkeys.len() as u8, signatures),
+            }),
+            call_bytes,
+        })
+    }
+}
+
+/// Signs call bytes as one member of a multisig.
+pub fn sign_partial(privkey: &secp256k1::SecretKey, call_bytes: &[u8]) -> PartialSignature {
+    let secp = Secp256k1::signing_only();
+    let msg = Message::from_hashed_data::<sha256::Hash>(call_bytes);
+
+    PartialSignature {
+        pubkey: PublicKey::from_secret_key(&secp, privkey).serialize(),
+        signature: secp.sign_ecdsa(&msg, privkey).serialize_compact(),
+    }
+}
+
+fn write_uvarint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::SecretKey;
+
+    fn keys() -> Vec<SecretKey> {
+        (1..=3)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect()
+    }
+
+    fn pubkeys(keys: &[SecretKey]) -> Vec<[u8; 33]> {
+        let secp = Secp256k1::signing_only();
+        keys.iter()
+            .map(|key| PublicKey::from_secret_key(&secp, key).serialize())
+            .collect()
+    }
+
+    #[test]
+    fn address_is_order_independent() {
+        let mut pubkeys = pubkeys(&keys());
+        let a = Multisig::new(2, pubkeys.clone()).unwrap();
+        pubkeys.reverse();
+        let b = Multisig::new(2, pubkeys.clone()).unwrap();
+        assert_eq!(a.address(), b.address());
+
+        let c = Multisig::new(3, pubkeys).unwrap();
+        assert_ne!(a.address(), c.address());
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        assert!(Multisig::new(0, pubkeys(&keys())).is_err());
+        assert!(Multisig::new(4, pubkeys(&keys())).is_err());
+    }
+
+    #[test]
+    fn combine() {
+        let keys = keys();
+        let multisig = Multisig::new(2, pubkeys(&keys)).unwrap();
+        let call_bytes = vec![1, 2, 3];
+
+        let partials = vec![sign_partial(&keys[0], &call_bytes)];
+        assert!(multisig.combine(call_bytes.clone(), &partials).is_err());
+
+        let partials = vec![
+            sign_partial(&keys[2], &call_bytes),
+            sign_partial(&keys[0], &call_bytes),
+        ];
+        let call = multisig.combine(call_bytes.clone(), &partials).unwrap();
+        assert_eq!(call.address().unwrap(), multisig.address());
+
+        let other = SecretKey::from_slice(&[9; 32]).unwrap();
+        let partials = vec![
+            sign_partial(&keys[0], &call_bytes),
+            sign_partial(&other, &call_bytes),
+        ];
+        assert!(multisig.combine(call_bytes, &partials).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use super::{sdk_compat::sdk::Tx as SdkTx, ConvertSdkTx, Signer};
+use super::eip712::{render_call, Eip712Call, Message};
+use super::{sdk_compat::sdk::Tx as SdkTx, BlockInfo, ConvertSdkTx, Signer, Time};
 use crate::call::Call;
 use crate::client::Client;
 use crate::client::{AsyncCall, AsyncQuery};
@@ -8,16 +9,56 @@ use crate::context::GetContext;
 use crate::encoding::{Decode, Encode};
 use crate::migrate::{MigrateFrom, MigrateInto};
 use crate::query::Query;
-use crate::state::State;
+use crate::state::{Attacher, Flusher, Loader, State};
+use crate::store::Store;
 use crate::{Error, Result};
+use sha2::{Digest, Sha256};
 use std::ops::{Deref, DerefMut};
 
 const NONCE_INCREASE_LIMIT: u64 = 1000;
 
-#[derive(State, Encode, Decode, Default)]
+/// The furthest in the future (in seconds) the timeout of an unordered call can
+/// be, which bounds how long its hash must be remembered.
+pub const MAX_UNORDERED_TIMEOUT_SECONDS: i64 = 10 * 60;
+
+#[derive(Default)]
 pub struct NoncePlugin<T: State> {
     map: Map<Address, u64>,
     inner: T,
+    unordered: Map<(i64, [u8; 32]), ()>,
+}
+
+// The unordered call set is stored after the inner state so that the existing
+// fields keep their store prefixes.
+impl<T: State> State for NoncePlugin<T> {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        Attacher::new(store)
+            .attach_child(&mut self.map)?
+            .attach_child(&mut self.inner)?
+            .attach_child(&mut self.unordered)?;
+        Ok(())
+    }
+
+    fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
+        Flusher::new(out)
+            .version(0)?
+            .flush_child(self.map)?
+            .flush_child(self.inner)?
+            .flush_child(self.unordered)?;
+        Ok(())
+    }
+
+    fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+        let mut loader = Loader::new(store.clone(), bytes, 0);
+        let mut value = Self {
+            map: loader.load_child()?,
+            inner: loader.load_child()?,
+            unordered: loader.load_child()?,
+        };
+        value.attach(store)?;
+
+        Ok(value)
+    }
 }
 
 impl<T1: State, T2: State> MigrateFrom<NoncePlugin<T1>> for NoncePlugin<T2>
@@ -28,6 +69,7 @@ where
         Ok(Self {
             map: other.map.migrate_into()?,
             inner: other.inner.migrate_into()?,
+            unordered: other.unordered.migrate_into()?,
         })
     }
 }
@@ -36,6 +78,78 @@ impl<T: State> NoncePlugin<T> {
     pub fn nonce(&self, address: Address) -> Result<u64> {
         Ok(*self.map.get_or_default(address)?)
     }
+
+    fn now(&mut self) -> Result<i64> {
+        self.context::<Time>()
+            .map(|time| time.seconds)
+            .ok_or_else(|| Error::Nonce("No Time context available".into()))
+    }
+
+    fn check_timeout(&mut self, timeout: Timeout) -> Result<()> {
+        let expired = match timeout {
+            Timeout::Height(height) => {
+                let current = self
+                    .context::<BlockInfo>()
+                    .ok_or_else(|| Error::Nonce("No BlockInfo context available".into()))?
+                    .height;
+                current > height
+            }
+            Timeout::Time(seconds) => self.now()? > seconds,
+        };
+
+        if expired {
+            return Err(Error::Nonce("Call has timed out".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Records an unordered call by the hash of its signer and encoding,
+    /// failing if the same call has already been processed before its timeout.
+    fn record_unordered<C: Encode>(
+        &mut self,
+        signer: Address,
+        timeout: i64,
+        call: &NonceCall<C>,
+    ) -> Result<()> {
+        if timeout > self.now()? + MAX_UNORDERED_TIMEOUT_SECONDS {
+            return Err(Error::Nonce(format!(
+                "Unordered call timeout must be at most {} seconds in the future",
+                MAX_UNORDERED_TIMEOUT_SECONDS
+            )));
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(signer.bytes());
+        hasher.update(call.encode()?);
+        let key = (timeout, hasher.finalize().into());
+
+        if self.unordered.contains_key(key)? {
+            return Err(Error::Nonce(
+                "Unordered call has already been processed".into(),
+            ));
+        }
+        self.unordered.insert(key, ())
+    }
+
+    /// Forgets unordered calls whose timeout has passed, since they can no
+    /// longer be replayed.
+    fn prune_unordered(&mut self, now: i64) -> Result<()> {
+        let mut expired = vec![];
+        for entry in self.unordered.iter()? {
+            let (key, _) = entry?;
+            if key.0 >= now {
+                break;
+            }
+            expired.push(*key);
+        }
+
+        for key in expired {
+            self.unordered.remove(key)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl<T: State> Deref for NoncePlugin<T> {
@@ -66,10 +180,12 @@ where
         let address = sdk_tx.sender_address()?;
         let nonce = self.nonce(address)? + 1;
         let inner_call = self.inner.convert(sdk_tx)?;
+        let timeout = sdk_tx.timeout_height().map(Timeout::Height);
 
         Ok(NonceCall {
             inner_call,
             nonce: Some(nonce),
+            timeout,
         })
     }
 }
@@ -94,12 +210,84 @@ impl<T: State + Query> Query for NoncePlugin<T> {
     }
 }
 
-#[derive(Debug, Encode, Decode)]
+/// The point after which a call can no longer be processed.
+#[derive(Debug, Encode, Decode, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// The last block height at which the call is valid.
+    Height(u64),
+    /// The last time (in seconds since the Unix epoch) at which the call is
+    /// valid.
+    Time(i64),
+}
+
+/// A call with replay protection.
+///
+/// Signed calls either include the signer's next nonce, or are unordered: they
+/// have no nonce and a [Timeout::Time] at most
+/// [MAX_UNORDERED_TIMEOUT_SECONDS] in the future, and are deduplicated by
+/// hash until the timeout passes. This lets a signer send calls in parallel
+/// without coordinating nonces.
+///
+/// Calls without a timeout keep the original encoding, an optional nonce
+/// followed by the inner call, so existing clients and signatures are
+/// unaffected. Calls with a timeout start with [TIMEOUT_CALL_TAG] instead of
+/// the nonce's `Option` tag.
+#[derive(Debug)]
 pub struct NonceCall<T> {
     pub nonce: Option<u64>,
+    pub timeout: Option<Timeout>,
     pub inner_call: T,
 }
 
+/// The first byte of the encoding of a [NonceCall] with a timeout, which is
+/// followed by the optional nonce, the timeout and the inner call.
+pub const TIMEOUT_CALL_TAG: u8 = 2;
+
+impl<T: Encode> Encode for NonceCall<T> {
+    fn encoding_length(&self) -> ed::Result<usize> {
+        let timeout_length = match self.timeout {
+            Some(timeout) => 1 + timeout.encoding_length()?,
+            None => 0,
+        };
+
+        Ok(timeout_length + self.nonce.encoding_length()? + self.inner_call.encoding_length()?)
+    }
+
+    fn encode_into<W: std::io::Write>(&self, dest: &mut W) -> ed::Result<()> {
+        match self.timeout {
+            Some(timeout) => {
+                dest.write_all(&[TIMEOUT_CALL_TAG])?;
+                self.nonce.encode_into(dest)?;
+                timeout.encode_into(dest)?;
+            }
+            None => self.nonce.encode_into(dest)?,
+        }
+        self.inner_call.encode_into(dest)
+    }
+}
+
+impl<T: Decode> Decode for NonceCall<T> {
+    fn decode<R: std::io::Read>(mut reader: R) -> ed::Result<Self> {
+        let mut tag = [0];
+        reader.read_exact(&mut tag)?;
+        let (nonce, timeout) = match tag[0] {
+            0 => (None, None),
+            1 => (Some(u64::decode(&mut reader)?), None),
+            TIMEOUT_CALL_TAG => (
+                Option::<u64>::decode(&mut reader)?,
+                Some(Timeout::decode(&mut reader)?),
+            ),
+            byte => return Err(ed::Error::UnexpectedByte(byte)),
+        };
+
+        Ok(Self {
+            nonce,
+            timeout,
+            inner_call: T::decode(reader)?,
+        })
+    }
+}
+
 impl<T> Call for NoncePlugin<T>
 where
     T: Call + State,
@@ -108,7 +296,7 @@ where
 
     fn call(&mut self, call: Self::Call) -> Result<()> {
         let signer = match self.context::<Signer>() {
-            Some(signer) => signer,
+            Some(signer) => signer.signer,
             None => {
                 return Err(Error::Nonce(
                     "Nonce could not resolve the Signer context".into(),
@@ -116,9 +304,13 @@ where
             }
         };
 
-        match (signer.signer, call.nonce) {
+        if let Some(timeout) = call.timeout {
+            self.check_timeout(timeout)?;
+        }
+
+        match (signer, call.nonce, call.timeout) {
             // Happy paths:
-            (Some(pub_key), Some(nonce)) => {
+            (Some(pub_key), Some(nonce), _) => {
                 let mut expected_nonce = self.map.entry(pub_key)?.or_default()?;
                 if nonce <= *expected_nonce {
                     return Err(Error::Nonce(format!(
@@ -139,17 +331,39 @@ where
                 *expected_nonce = nonce;
                 self.inner.call(call.inner_call)
             }
-            (None, None) => self.inner.call(call.inner_call),
+            (Some(address), None, Some(Timeout::Time(timeout))) => {
+                self.record_unordered(address, timeout, &call)?;
+                self.inner.call(call.inner_call)
+            }
+            (None, None, _) => self.inner.call(call.inner_call),
 
             // Unhappy paths:
-            (Some(_), None) => Err(Error::Nonce("Signed calls must include a nonce".into())),
-            (None, Some(_)) => Err(Error::Nonce(
+            (Some(_), None, _) => Err(Error::Nonce(
+                "Signed calls must include a nonce or a time timeout".into(),
+            )),
+            (None, Some(_), _) => Err(Error::Nonce(
                 "Unsigned calls must not include a nonce".into(),
             )),
         }
     }
 }
 
+// The nonce and timeout are rendered as fields alongside the inner call.
+impl<T: State> Eip712Call for NoncePlugin<T> {
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        let call = NonceCall::<Vec<u8>>::decode(&mut *bytes)?;
+        if let Some(nonce) = call.nonce {
+            message.add_string("nonce", nonce)?;
+        }
+        match call.timeout {
+            Some(Timeout::Height(height)) => message.add_string("timeout_height", height)?,
+            Some(Timeout::Time(time)) => message.add_string("timeout_time", time)?,
+            None => {}
+        }
+        render_call(&self.inner, call.inner_call.as_slice(), message)
+    }
+}
+
 pub struct NonceAdapter<T, U: Clone> {
     parent: U,
     marker: std::marker::PhantomData<fn() -> T>,
@@ -181,6 +395,7 @@ where
         let res = self.parent.call(NonceCall {
             inner_call: call,
             nonce: Some(nonce),
+            timeout: None,
         });
 
         // Increment the local nonce
@@ -250,6 +465,12 @@ impl<T: Client<NonceAdapter<T, U>> + State, U: Clone> DerefMut for NonceClient<T
     }
 }
 
+// raw calls and queries are passed through to the inner client
+crate::pass_through_client! {
+    impl[T: Client<NonceAdapter<T, U>> + State, U: Clone]
+    NonceClient<T, U> => inner: T::Client
+}
+
 impl<
         T: Client<NonceAdapter<T, U>> + State + Query,
         U: Clone
@@ -359,6 +580,10 @@ mod abci {
         T: EndBlock + State,
     {
         fn end_block(&mut self, ctx: &EndBlockCtx) -> Result<()> {
+            if let Some(now) = self.context::<Time>().map(|time| time.seconds) {
+                self.prune_unordered(now)?;
+            }
+
             self.inner.end_block(ctx)
         }
     }
@@ -390,6 +615,7 @@ mod tests {
     use super::super::Signer;
     use super::*;
     use crate::context::Context;
+    use serial_test::serial;
 
     #[derive(State, Encode, Decode, Default)]
     struct Counter {
@@ -420,6 +646,7 @@ mod tests {
     fn nonced_call(n: u64) -> NonceCall<CounterCall> {
         NonceCall {
             nonce: Some(n),
+            timeout: None,
             inner_call: CounterCall::Increment,
         }
     }
@@ -427,11 +654,52 @@ mod tests {
     fn unnonced_call() -> NonceCall<CounterCall> {
         NonceCall {
             nonce: None,
+            timeout: None,
+            inner_call: CounterCall::Increment,
+        }
+    }
+
+    fn unordered_call(timeout: i64) -> NonceCall<CounterCall> {
+        NonceCall {
+            nonce: None,
+            timeout: Some(Timeout::Time(timeout)),
             inner_call: CounterCall::Increment,
         }
     }
 
     #[test]
+    fn call_encoding() -> Result<()> {
+        // calls without a timeout keep the original encoding
+        let mut bytes = vec![1];
+        bytes.extend_from_slice(&5u64.to_be_bytes());
+        bytes.push(0);
+        assert_eq!(nonced_call(5).encode()?, bytes);
+        assert_eq!(unnonced_call().encode()?, vec![0, 0]);
+
+        let call = NonceCall {
+            nonce: Some(5),
+            timeout: Some(Timeout::Height(20)),
+            inner_call: CounterCall::Increment,
+        };
+        let bytes = call.encode()?;
+        assert_eq!(bytes[0], TIMEOUT_CALL_TAG);
+        assert_eq!(bytes.len(), call.encoding_length()?);
+        let decoded = NonceCall::<CounterCall>::decode(bytes.as_slice())?;
+        assert_eq!(decoded.nonce, Some(5));
+        assert_eq!(decoded.timeout, Some(Timeout::Height(20)));
+
+        let bytes = unordered_call(200).encode()?;
+        let decoded = NonceCall::<CounterCall>::decode(bytes.as_slice())?;
+        assert_eq!(decoded.nonce, None);
+        assert_eq!(decoded.timeout, Some(Timeout::Time(200)));
+
+        assert!(NonceCall::<CounterCall>::decode([3, 0].as_slice()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
     fn nonced_calls() {
         let mut state: NoncePlugin<Counter> = Default::default();
 
@@ -465,4 +733,55 @@ mod tests {
         assert!(state.call(unnonced_call()).is_err());
         Context::remove::<Signer>();
     }
+
+    #[test]
+    #[serial]
+    fn timeouts_and_unordered_calls() {
+        let mut state: NoncePlugin<Counter> = Default::default();
+        Context::add(Signer {
+            signer: Some(Address::from_pubkey([0; 33])),
+        });
+        Context::add(Time::from_seconds(100));
+        Context::add(BlockInfo {
+            height: 10,
+            ..Default::default()
+        });
+
+        // Unordered, new call
+        state.call(unordered_call(200)).unwrap();
+        assert_eq!(state.inner.count, 1);
+
+        // Unordered, replayed call
+        assert!(state.call(unordered_call(200)).is_err());
+
+        // Unordered, timeout too far in the future
+        assert!(state.call(unordered_call(1000)).is_err());
+
+        // Unordered, timed out
+        assert!(state.call(unordered_call(99)).is_err());
+
+        // Nonced, with height timeouts
+        let mut call = nonced_call(1);
+        call.timeout = Some(Timeout::Height(9));
+        assert!(state.call(call).is_err());
+        let mut call = nonced_call(1);
+        call.timeout = Some(Timeout::Height(10));
+        state.call(call).unwrap();
+        assert_eq!(state.inner.count, 2);
+
+        // Signed, no nonce, height timeout
+        let mut call = unnonced_call();
+        call.timeout = Some(Timeout::Height(10));
+        assert!(state.call(call).is_err());
+
+        // Pruned calls are forgotten once timed out
+        state.prune_unordered(200).unwrap();
+        assert!(state.call(unordered_call(200)).is_err());
+        state.prune_unordered(201).unwrap();
+        assert!(state.unordered.iter().unwrap().next().is_none());
+
+        Context::remove::<Signer>();
+        Context::remove::<Time>();
+        Context::remove::<BlockInfo>();
+    }
 }
@@ -1,28 +1,60 @@
+use super::eip712::{render_call, Eip712Call, Message};
 use super::sdk_compat::{sdk::Tx as SdkTx, ConvertSdkTx};
 use crate::call::Call;
 use crate::client::{AsyncCall, AsyncQuery, Client};
 use crate::coins::{Amount, Coin, Symbol};
 use crate::context::{Context, GetContext};
-use crate::encoding::{Decode, Encode};
+use crate::describe::Describe;
+use crate::encoding::{Decode, Encode, Terminated};
 use crate::migrate::{MigrateFrom, MigrateInto};
 use crate::query::Query;
-use crate::state::State;
+use crate::state::{Attacher, Flusher, Loader, State};
+use crate::store::Store;
 use crate::{Error, Result};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::ops::{Deref, DerefMut};
 
-#[derive(State, Encode, Decode, Default)]
+#[derive(Encode, Decode, Default)]
 pub struct PayablePlugin<T> {
+    /// The store of the inner state, kept to run paid call batches against a
+    /// staged copy of it.
+    store: Store,
     inner: T,
 }
 
+impl<T: State> State for PayablePlugin<T> {
+    fn attach(&mut self, store: Store) -> Result<()> {
+        self.store = store.sub(&[0]);
+        Attacher::new(store).attach_child(&mut self.inner)?;
+
+        Ok(())
+    }
+
+    fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
+        Flusher::new(out).version(0)?.flush_child(self.inner)?;
+
+        Ok(())
+    }
+
+    fn load(store: Store, bytes: &mut &[u8]) -> Result<Self> {
+        let inner_store = store.sub(&[0]);
+        let mut loader = Loader::new(store, bytes, 0);
+
+        Ok(Self {
+            store: inner_store,
+            inner: loader.load_child()?,
+        })
+    }
+}
+
 impl<T1, T2> MigrateFrom<PayablePlugin<T1>> for PayablePlugin<T2>
 where
     T1: MigrateInto<T2>,
 {
     fn migrate_from(other: PayablePlugin<T1>) -> Result<Self> {
         Ok(Self {
+            store: other.store,
             inner: other.inner.migrate_into()?,
         })
     }
@@ -41,6 +73,14 @@ pub struct Paid {
     map: HashMap<u8, Amount>,
     pub running_payer: bool,
     pub fee_disabled: bool,
+    /// The encoding of the paid call, available while the payer call runs so
+    /// payers can restrict which calls they fund.
+    pub paid_call: Vec<u8>,
+    /// The path of the method invoked by the paid call (e.g.
+    /// `accounts.transfer`), if the app describes its calls. See [CallPath].
+    pub paid_call_path: Option<String>,
+    /// Funding which can only be spent on fees, e.g. from a fee allowance.
+    fee_map: HashMap<u8, Amount>,
 }
 
 impl Paid {
@@ -75,6 +115,34 @@ impl Paid {
         Ok(())
     }
 
+    /// Gives funding which can only be taken by [Paid::take_fee].
+    pub fn give_fee<S: Symbol, A: Into<Amount>>(&mut self, amount: A) -> Result<()> {
+        let entry = self.fee_map.entry(S::INDEX).or_insert_with(|| 0.into());
+        *entry = (*entry + amount.into())?;
+
+        Ok(())
+    }
+
+    /// Takes funding for a fee, using funding given by [Paid::give_fee] before
+    /// any other funding.
+    pub fn take_fee<S: Symbol, A: Into<Amount>>(&mut self, amount: A) -> Result<Coin<S>> {
+        let amount = amount.into();
+        let fee_funding = match self.fee_map.get(&S::INDEX) {
+            Some(amt) => *amt,
+            None => 0.into(),
+        };
+        let from_fee_funding = if fee_funding < amount {
+            fee_funding
+        } else {
+            amount
+        };
+        self.take_denom((amount - from_fee_funding)?, S::INDEX)?;
+        self.fee_map
+            .insert(S::INDEX, (fee_funding - from_fee_funding)?);
+
+        Ok(S::mint(amount))
+    }
+
     pub fn balance<S: Symbol>(&self) -> Result<Amount> {
         let entry = match self.map.get(&S::INDEX) {
             Some(amt) => *amt,
@@ -85,6 +153,29 @@ impl Paid {
     }
 }
 
+/// Finds the path of the method invoked by an encoded call, for payers which
+/// restrict the calls they fund (see [Paid::paid_call_path]).
+///
+/// Types implementing [Describe] find it from their call descriptors (see
+/// [Descriptor::call_path](crate::describe::Descriptor::call_path)), and
+/// plugins between the payable plugin and the app pass it through to their
+/// inner type. Other types have no paths.
+pub trait CallPath {
+    fn call_path(&self, call_bytes: &[u8]) -> Option<String>;
+}
+
+impl<T> CallPath for T {
+    default fn call_path(&self, _call_bytes: &[u8]) -> Option<String> {
+        None
+    }
+}
+
+impl<T: Describe> CallPath for T {
+    fn call_path(&self, call_bytes: &[u8]) -> Option<String> {
+        T::describe().call_path(call_bytes).ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct PaidCall<T> {
     pub payer: T,
@@ -132,10 +223,34 @@ impl<T: Decode> Decode for PaidCall<T> {
     }
 }
 
+// Each half of a paid call is length-prefixed, so its encoding is terminated.
+impl<T> Terminated for PaidCall<T> {}
+
 #[derive(Debug, Encode, Decode)]
 pub enum PayableCall<T> {
     Paid(PaidCall<T>),
     Unpaid(T),
+    /// Several paid calls executed atomically, e.g. the messages of a
+    /// multi-message SDK transaction. Every payer call runs before any paid
+    /// call, and the batch fails on the first failing call without keeping
+    /// the changes of the calls before it.
+    PaidBatch(Vec<PaidCall<T>>),
+}
+
+impl<T> From<PaidCall<T>> for PayableCall<T> {
+    fn from(call: PaidCall<T>) -> Self {
+        PayableCall::Paid(call)
+    }
+}
+
+impl<T> From<Vec<PaidCall<T>>> for PayableCall<T> {
+    fn from(mut calls: Vec<PaidCall<T>>) -> Self {
+        if calls.len() == 1 {
+            PayableCall::Paid(calls.remove(0))
+        } else {
+            PayableCall::PaidBatch(calls)
+        }
+    }
 }
 
 #[allow(clippy::non_send_fields_in_send_ty)]
@@ -143,7 +258,7 @@ unsafe impl<T> Send for PayableCall<T> {}
 
 impl<T> Call for PayablePlugin<T>
 where
-    T: Call + State,
+    T: Call + State + Default,
 {
     type Call = PayableCall<T::Call>;
 
@@ -152,8 +267,11 @@ where
         match call {
             PayableCall::Unpaid(call) => self.inner.call(call),
             PayableCall::Paid(calls) => {
+                let paid_call = calls.paid.encode()?;
                 let ctx = Paid {
                     running_payer: true,
+                    paid_call_path: self.inner.call_path(paid_call.as_slice()),
+                    paid_call,
                     ..Default::default()
                 };
                 Context::add(ctx);
@@ -164,10 +282,110 @@ where
                 self.inner.call(calls.paid)?;
                 Ok(())
             }
+            PayableCall::PaidBatch(calls) => {
+                if calls.is_empty() {
+                    return Err(Error::App("Paid call batch is empty".into()));
+                }
+
+                Context::add(Paid {
+                    running_payer: true,
+                    ..Default::default()
+                });
+                self.call_atomically(|inner| {
+                    let mut paid_calls = Vec::with_capacity(calls.len());
+                    for call in calls {
+                        let paid_call = call.paid.encode()?;
+                        let paid_call_path = inner.call_path(paid_call.as_slice());
+                        let ctx = inner.context::<Paid>().unwrap();
+                        ctx.paid_call = paid_call;
+                        ctx.paid_call_path = paid_call_path;
+                        inner.call(call.payer)?;
+                        paid_calls.push(call.paid);
+                    }
+
+                    let ctx = inner.context::<Paid>().unwrap();
+                    ctx.running_payer = false;
+                    ctx.paid_call = vec![];
+                    ctx.paid_call_path = None;
+                    for call in paid_calls {
+                        inner.call(call)?;
+                    }
+                    Ok(())
+                })
+            }
+        }
+    }
+}
+
+impl<T: State + Default> PayablePlugin<T> {
+    /// Runs `op` on the inner state loaded from a staged copy of its store
+    /// (see [Store::stage]), so its changes are only kept if it succeeds.
+    /// Failed calls are otherwise not rolled back, since the state of a failed
+    /// transaction is still written.
+    fn call_atomically<F>(&mut self, op: F) -> Result<()>
+    where
+        F: FnOnce(&mut T) -> Result<()>,
+    {
+        let mut bytes = vec![];
+        std::mem::take(&mut self.inner).flush(&mut bytes)?;
+
+        let staged_store = self.store.stage();
+        let mut staged = T::load(staged_store.clone(), &mut bytes.as_slice())?;
+        let res = op(&mut staged).and_then(|()| {
+            let mut staged_bytes = vec![];
+            staged.flush(&mut staged_bytes)?;
+            self.store.commit(staged_store)?;
+            Ok(staged_bytes)
+        });
+
+        let bytes = match res {
+            Ok(ref staged_bytes) => staged_bytes,
+            Err(_) => &bytes,
+        };
+        self.inner = T::load(self.store.clone(), &mut bytes.as_slice())?;
+
+        res.map(|_| ())
+    }
+}
+
+// The payer and paid halves of paid calls are rendered as separate structs,
+// suffixed with their index in a batch.
+impl<T: Eip712Call> Eip712Call for PayablePlugin<T> {
+    fn eip712_call(&self, bytes: &mut &[u8], message: &mut Message) -> Result<()> {
+        match PayableCall::<Vec<u8>>::decode(&mut *bytes)? {
+            PayableCall::Unpaid(call) => render_call(&self.inner, call.as_slice(), message),
+            PayableCall::Paid(call) => self.render_paid_call(call, "", message),
+            PayableCall::PaidBatch(calls) => {
+                for (i, call) in calls.into_iter().enumerate() {
+                    self.render_paid_call(call, &i.to_string(), message)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+impl<T: Eip712Call> PayablePlugin<T> {
+    fn render_paid_call(
+        &self,
+        call: PaidCall<Vec<u8>>,
+        suffix: &str,
+        message: &mut Message,
+    ) -> Result<()> {
+        let mut payer = Message::default();
+        render_call(&self.inner, call.payer.as_slice(), &mut payer)?;
+        message.add_struct(
+            &format!("payer{}", suffix),
+            &format!("Payer{}", suffix),
+            payer,
+        )?;
+
+        let mut paid = Message::default();
+        render_call(&self.inner, call.paid.as_slice(), &mut paid)?;
+        message.add_struct(&format!("paid{}", suffix), &format!("Paid{}", suffix), paid)
+    }
+}
+
 impl<T: Query + State> Query for PayablePlugin<T> {
     type Query = T::Query;
 
@@ -176,15 +394,17 @@ impl<T: Query + State> Query for PayablePlugin<T> {
     }
 }
 
+// Apps convert SDK transactions into a single paid call, or into one paid call
+// per message (e.g. with a `MsgRegistry`) which are executed as a batch.
 impl<T> ConvertSdkTx for PayablePlugin<T>
 where
-    T: State + ConvertSdkTx<Output = PaidCall<T::Call>> + Call,
+    T: State + ConvertSdkTx + Call,
+    T::Output: Into<PayableCall<T::Call>>,
 {
     type Output = PayableCall<T::Call>;
 
     fn convert(&self, sdk_tx: &SdkTx) -> Result<PayableCall<T::Call>> {
-        let paid_call = self.inner.convert(sdk_tx)?;
-        Ok(PayableCall::Paid(paid_call))
+        Ok(self.inner.convert(sdk_tx)?.into())
     }
 }
 
@@ -417,6 +637,12 @@ where
     }
 }
 
+// raw calls and queries are passed through to the inner client
+crate::pass_through_client! {
+    impl[T: Client<UnpaidAdapter<T, U>>, U: Clone + Send]
+    PayableClient<T, U> => inner: T::Client
+}
+
 #[allow(clippy::non_send_fields_in_send_ty)]
 unsafe impl<T: Client<UnpaidAdapter<T, U>>, U: Clone + Send> Send for PayableClient<T, U> {}
 
@@ -482,3 +708,69 @@ mod abci {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::Map;
+    use serial_test::serial;
+
+    /// Transfers from account 0 to account 1 in paid calls.
+    #[derive(State, Encode, Decode, Default)]
+    struct Bank {
+        balances: Map<u8, u64>,
+    }
+
+    impl Call for Bank {
+        type Call = u64;
+
+        fn call(&mut self, amount: Self::Call) -> Result<()> {
+            if self.context::<Paid>().unwrap().running_payer {
+                return Ok(());
+            }
+
+            let from = *self.balances.get_or_default(0)?;
+            if from < amount {
+                return Err(Error::App("Insufficient balance".into()));
+            }
+            let to = *self.balances.get_or_default(1)?;
+            self.balances.insert(0, from - amount)?;
+            self.balances.insert(1, to + amount)
+        }
+    }
+
+    fn transfer(amount: u64) -> PaidCall<u64> {
+        PaidCall {
+            payer: 0,
+            paid: amount,
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn failed_batch_keeps_no_changes() -> Result<()> {
+        let store = Store::default();
+        let mut plugin: PayablePlugin<Bank> = Default::default();
+        plugin.attach(store.clone())?;
+        plugin.inner.balances.insert(0, 100)?;
+
+        plugin.call(PayableCall::PaidBatch(vec![transfer(10), transfer(20)]))?;
+        assert_eq!(*plugin.balances.get_or_default(1)?, 30);
+
+        // the second transfer fails, so the first one is not kept either
+        let batch = vec![transfer(10), transfer(100)];
+        assert!(plugin.call(PayableCall::PaidBatch(batch)).is_err());
+        assert_eq!(*plugin.balances.get_or_default(0)?, 70);
+        assert_eq!(*plugin.balances.get_or_default(1)?, 30);
+
+        let mut bytes = vec![];
+        plugin.flush(&mut bytes)?;
+        let plugin: PayablePlugin<Bank> = State::load(store, &mut bytes.as_slice())?;
+        assert_eq!(*plugin.balances.get_or_default(0)?, 70);
+        assert_eq!(*plugin.balances.get_or_default(1)?, 30);
+
+        Context::remove::<Paid>();
+
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
+use super::CheckTxInfo;
 use crate::call::Call as CallTrait;
 use crate::client::{AsyncCall, AsyncQuery, Client};
 use crate::coins::{Address, Symbol};
+use crate::context::Context;
 use crate::encoding::{Decode, Encode};
 use crate::migrate::{MigrateFrom, MigrateInto};
 use crate::query::Query;
 use crate::state::State;
 use crate::{Error, Result};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
@@ -238,6 +241,48 @@ pub mod sdk {
             Ok(pubkey_arr)
         }
 
+        pub fn memo(&self) -> &str {
+            match self {
+                Tx::Amino(tx) => tx.memo.as_str(),
+                Tx::Protobuf(tx) => tx.body.memo.as_str(),
+            }
+        }
+
+        /// The last block height at which the transaction is valid, if any.
+        /// Amino transactions have no timeout.
+        pub fn timeout_height(&self) -> Option<u64> {
+            match self {
+                Tx::Amino(_) => None,
+                Tx::Protobuf(tx) => {
+                    let body: cosmrs::proto::cosmos::tx::v1beta1::TxBody = tx.body.clone().into();
+                    (body.timeout_height > 0).then_some(body.timeout_height)
+                }
+            }
+        }
+
+        /// The fee of the transaction, in the Amino JSON format for both
+        /// encodings.
+        pub fn fee(&self) -> Fee {
+            match self {
+                Tx::Amino(tx) => tx.fee.clone(),
+                Tx::Protobuf(tx) => {
+                    let fee: cosmrs::proto::cosmos::tx::v1beta1::Fee =
+                        tx.auth_info.fee.clone().into();
+                    Fee {
+                        amount: fee
+                            .amount
+                            .into_iter()
+                            .map(|coin| Coin {
+                                amount: coin.amount,
+                                denom: coin.denom,
+                            })
+                            .collect(),
+                        gas: fee.gas_limit.to_string(),
+                    }
+                }
+            }
+        }
+
         pub fn sender_address(&self) -> Result<Address> {
             let signer_call = super::super::signer::sdk_to_signercall(self)?;
             signer_call.address()
@@ -261,8 +306,9 @@ pub mod sdk {
                     .clone(),
             };
 
-            let mut sig_arr = [0; 64];
-            sig_arr.copy_from_slice(&sig_vec);
+            let sig_arr = sig_vec
+                .try_into()
+                .map_err(|_| Error::App("Invalid signature length".to_string()))?;
 
             Ok(sig_arr)
         }
@@ -360,6 +406,128 @@ pub trait ConvertSdkTx {
     fn convert(&self, msg: &sdk::Tx) -> Result<Self::Output>;
 }
 
+type AminoDecoder<T> = Box<dyn Fn(&serde_json::Value) -> Result<T>>;
+type ProtobufDecoder<T> = Box<dyn Fn(&[u8]) -> Result<T>>;
+
+/// Decoders from SDK messages into native calls, registered by protobuf `Any`
+/// type URL and Amino type name.
+///
+/// Apps can build a registry in their [ConvertSdkTx] implementation rather
+/// than matching on message types themselves, e.g.:
+///
+/// ```ignore
+/// MsgRegistry::new()
+///     .amino("cosmos-sdk/MsgSend", |msg: sdk::MsgSend| transfer(msg))
+///     .protobuf("/cosmos.bank.v1beta1.MsgSend", |msg: bank::MsgSend| transfer(msg.into()))
+///     .decode(tx)
+/// ```
+pub struct MsgRegistry<T> {
+    amino: HashMap<String, AminoDecoder<T>>,
+    protobuf: HashMap<String, ProtobufDecoder<T>>,
+}
+
+impl<T> Default for MsgRegistry<T> {
+    fn default() -> Self {
+        Self {
+            amino: HashMap::new(),
+            protobuf: HashMap::new(),
+        }
+    }
+}
+
+impl<T> MsgRegistry<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a decoder for Amino JSON messages with the given type name.
+    pub fn amino<M, F>(mut self, type_name: &str, decode: F) -> Self
+    where
+        M: serde::de::DeserializeOwned,
+        F: Fn(M) -> Result<T> + 'static,
+    {
+        let name = type_name.to_string();
+        let decoder = move |value: &serde_json::Value| {
+            let msg = serde_json::from_value(value.clone())
+                .map_err(|e| Error::App(format!("Invalid {} message: {}", name, e)))?;
+            decode(msg)
+        };
+        self.amino.insert(type_name.to_string(), Box::new(decoder));
+        self
+    }
+
+    /// Registers a decoder for protobuf messages with the given `Any` type
+    /// URL.
+    pub fn protobuf<M, F>(mut self, type_url: &str, decode: F) -> Self
+    where
+        M: prost::Message + Default,
+        F: Fn(M) -> Result<T> + 'static,
+    {
+        let url = type_url.to_string();
+        let decoder = move |bytes: &[u8]| {
+            let msg = M::decode(bytes)
+                .map_err(|e| Error::App(format!("Invalid {} message: {}", url, e)))?;
+            decode(msg)
+        };
+        self.protobuf
+            .insert(type_url.to_string(), Box::new(decoder));
+        self
+    }
+
+    /// Decodes every message of the transaction, in order. Fails if the
+    /// transaction has no messages or any message type is not registered.
+    pub fn decode(&self, tx: &sdk::Tx) -> Result<Vec<T>> {
+        let calls = match tx {
+            sdk::Tx::Amino(tx) => tx
+                .msg
+                .iter()
+                .map(|msg| {
+                    let decode = self.amino.get(&msg.type_).ok_or_else(|| {
+                        Error::App(format!("Unsupported message type '{}'", msg.type_))
+                    })?;
+                    decode(&msg.value)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            sdk::Tx::Protobuf(tx) => tx
+                .body
+                .messages
+                .iter()
+                .map(|msg| {
+                    let decode = self.protobuf.get(&msg.type_url).ok_or_else(|| {
+                        Error::App(format!("Unsupported message type '{}'", msg.type_url))
+                    })?;
+                    decode(msg.value.as_slice())
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        if calls.is_empty() {
+            return Err(Error::App("Transaction has no messages".into()));
+        }
+
+        Ok(calls)
+    }
+}
+
+/// The fields of the SDK transaction being processed which are not part of its
+/// messages, available in the context while its calls run.
+#[derive(Debug, Clone)]
+pub struct SdkTxInfo {
+    pub memo: String,
+    pub timeout_height: Option<u64>,
+    pub fee: sdk::Fee,
+}
+
+impl From<&sdk::Tx> for SdkTxInfo {
+    fn from(tx: &sdk::Tx) -> Self {
+        SdkTxInfo {
+            memo: tx.memo().to_string(),
+            timeout_height: tx.timeout_height(),
+            fee: tx.fee(),
+        }
+    }
+}
+
 impl<S: Symbol, T> CallTrait for SdkCompatPlugin<S, T>
 where
     T: CallTrait + State + ConvertSdkTx<Output = T::Call>,
@@ -368,9 +536,15 @@ where
     type Call = Call<T::Call>;
 
     fn call(&mut self, call: Self::Call) -> Result<()> {
-        let call = match call {
-            Call::Native(call) => call,
-            Call::Sdk(tx) => self.inner.convert(&tx)?,
+        let (call, _tx_info) = match call {
+            Call::Native(call) => (call, None),
+            Call::Sdk(tx) => {
+                if let Some(info) = Context::resolve::<CheckTxInfo>() {
+                    info.gas_wanted = tx.fee().gas.parse().ok();
+                }
+                let tx_info = Context::scope(SdkTxInfo::from(&tx));
+                (self.inner.convert(&tx)?, Some(tx_info))
+            }
         };
 
         self.inner.call(call)
@@ -459,6 +633,12 @@ impl<T: Client<SdkCompatAdapter<T, U, S>>, U: Clone, S> DerefMut for SdkCompatCl
     }
 }
 
+// raw calls and queries are passed through to the inner client
+crate::pass_through_client! {
+    impl[T: Client<SdkCompatAdapter<T, U, S>>, U: Clone, S]
+    SdkCompatClient<T, U, S> => inner: T::Client
+}
+
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsValue;
 
@@ -550,3 +730,56 @@ mod abci {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::sdk::{AminoTx, Fee, Msg, MsgSend, Tx};
+    use super::*;
+    use serde_json::json;
+
+    fn amino_tx(msgs: Vec<Msg>) -> Tx {
+        Tx::Amino(AminoTx {
+            msg: msgs,
+            fee: Fee {
+                amount: vec![],
+                gas: "10000".to_string(),
+            },
+            memo: "hello".to_string(),
+            signatures: vec![],
+        })
+    }
+
+    fn send(to: &str) -> Msg {
+        Msg {
+            type_: "cosmos-sdk/MsgSend".to_string(),
+            value: json!({
+                "from_address": "a",
+                "to_address": to,
+                "amount": [],
+            }),
+        }
+    }
+
+    #[test]
+    fn msg_registry() {
+        let registry =
+            MsgRegistry::new().amino("cosmos-sdk/MsgSend", |msg: MsgSend| Ok(msg.to_address));
+
+        let tx = amino_tx(vec![send("b"), send("c")]);
+        assert_eq!(registry.decode(&tx).unwrap(), vec!["b", "c"]);
+
+        let info = SdkTxInfo::from(&tx);
+        assert_eq!(info.memo, "hello");
+        assert_eq!(info.timeout_height, None);
+        assert_eq!(info.fee.gas, "10000");
+
+        let unknown = Msg {
+            type_: "cosmos-sdk/MsgDelegate".to_string(),
+            value: json!({}),
+        };
+        assert!(registry
+            .decode(&amino_tx(vec![send("b"), unknown]))
+            .is_err());
+        assert!(registry.decode(&amino_tx(vec![])).is_err());
+    }
+}
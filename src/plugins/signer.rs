@@ -1,11 +1,12 @@
 use super::{
+    eip712::TypedData,
     sdk_compat::{self, sdk::Tx as SdkTx, ConvertSdkTx},
-    ChainId, GetNonce,
+    ChainId, CheckTxInfo, GetNonce, MultisigSignatures, Simulation,
 };
 use crate::client::{AsyncCall, AsyncQuery, Client};
 use crate::coins::{Address, Symbol};
 use crate::context::{Context, GetContext};
-use crate::encoding::{Decode, Encode};
+use crate::encoding::{Decode, Encode, LengthVec};
 use crate::migrate::MigrateFrom;
 use crate::query::Query;
 use crate::state::State;
@@ -13,6 +14,7 @@ use crate::{call::Call, migrate::MigrateInto};
 use crate::{Error, Result};
 use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::ops::Deref;
 
 #[derive(Default, Encode, Decode, State)]
@@ -54,32 +56,148 @@ pub struct SignerCall {
 
 impl SignerCall {
     pub fn address(&self) -> Result<Address> {
-        let pubkey_bytes = self
-            .pubkey
-            .ok_or_else(|| Error::Signer("No pubkey specified".to_string()))?;
+        let pubkey_bytes = match &self.sigtype {
+            SigType::Multisig(sigs) => return Ok(sigs.multisig.address()),
+            SigType::Ed25519(pubkey) => return Ok(Address::from_pubkey_ed25519(*pubkey)),
+            _ => self
+                .pubkey
+                .ok_or_else(|| Error::Signer("No pubkey specified".to_string()))?,
+        };
         match &self.sigtype {
-            SigType::EthPersonalSign(_) => {
+            SigType::Secp256r1 | SigType::WebAuthn(_) => {
+                Ok(Address::from_pubkey_secp256r1(pubkey_bytes))
+            }
+            SigType::EthPersonalSign(_) | SigType::Eip712 | SigType::Eip712Sdk(_) => {
                 let pubkey = PublicKey::from_slice(pubkey_bytes.as_slice())?;
-                let pubkey_bytes = pubkey.serialize_uncompressed();
-                let mut eth_pubkey = [0; 64];
-                eth_pubkey.copy_from_slice(&pubkey_bytes[1..]);
-                Ok(Address::from_pubkey_eth(eth_pubkey))
+                Ok(eth_address(&pubkey))
             }
             _ => Ok(Address::from_pubkey(pubkey_bytes)),
         }
     }
+
+    /// Signs call bytes with an ed25519 key.
+    pub fn sign_ed25519(keypair: &ed25519_dalek::Keypair, call_bytes: Vec<u8>) -> Self {
+        use ed25519_dalek::Signer as _;
+        let signature = keypair.sign(call_bytes.as_slice());
+
+        SignerCall {
+            signature: Some(signature.to_bytes()),
+            pubkey: None,
+            sigtype: SigType::Ed25519(keypair.public.to_bytes()),
+            call_bytes,
+        }
+    }
+
+    /// Signs call bytes with a secp256r1 key.
+    pub fn sign_secp256r1(key: &p256::ecdsa::SigningKey, call_bytes: Vec<u8>) -> Self {
+        use p256::ecdsa::{signature::Signer as _, Signature};
+        let signature: Signature = key.sign(call_bytes.as_slice());
+        let mut signature_bytes = [0; 64];
+        signature_bytes.copy_from_slice(signature.as_ref());
+        let mut pubkey = [0; 33];
+        pubkey.copy_from_slice(key.verifying_key().to_encoded_point(true).as_bytes());
+
+        SignerCall {
+            signature: Some(signature_bytes),
+            pubkey: Some(pubkey),
+            sigtype: SigType::Secp256r1,
+            call_bytes,
+        }
+    }
 }
 
 #[derive(Debug, Encode, Decode)]
 pub enum SigType {
     Native,
     Adr36,
+    Multisig(MultisigSignatures),
+    /// An ed25519 signature of the call bytes by the given public key, e.g. a
+    /// validator's consensus key. The call's `pubkey` field must be empty.
+    Ed25519([u8; 32]),
+    /// A secp256r1 (P-256) signature of the SHA-256 hash of the call bytes,
+    /// with the compressed public key in the call's `pubkey` field.
+    Secp256r1,
+    /// A secp256r1 signature from a WebAuthn authenticator (e.g. a passkey),
+    /// whose challenge is the SHA-256 hash of the call bytes.
+    WebAuthn(WebAuthnAssertion),
+    /// A secp256k1 signature of the EIP-712 typed data for the call, from an
+    /// Ethereum wallet. The typed data renders the call as fields (see
+    /// [Eip712Call](super::eip712::Eip712Call)) along with the call bytes.
+    Eip712,
     #[skip]
     Sdk(Box<sdk_compat::sdk::Tx>),
     #[skip]
     Adr36WrappedSdk(Box<sdk_compat::sdk::Tx>),
     #[skip]
     EthPersonalSign(Box<sdk_compat::sdk::Tx>),
+    #[skip]
+    Eip712Sdk(Box<sdk_compat::sdk::Tx>),
+}
+
+/// The authenticator output needed to reconstruct the message signed by a
+/// WebAuthn authenticator.
+#[derive(Debug, Encode, Decode)]
+pub struct WebAuthnAssertion {
+    pub authenticator_data: LengthVec<u16, u8>,
+    pub client_data_json: LengthVec<u16, u8>,
+}
+
+impl WebAuthnAssertion {
+    /// Checks that the assertion is for the given call bytes and returns the
+    /// bytes signed by the authenticator.
+    fn signed_bytes(&self, call_bytes: &[u8]) -> Result<Vec<u8>> {
+        #[derive(serde::Deserialize)]
+        struct ClientData {
+            #[serde(rename = "type")]
+            type_: String,
+            challenge: String,
+        }
+
+        let client_data: ClientData = serde_json::from_slice(&self.client_data_json)?;
+        if client_data.type_ != "webauthn.get" {
+            return Err(Error::Signer("Invalid WebAuthn client data type".into()));
+        }
+        let challenge = base64::encode_config(Sha256::digest(call_bytes), base64::URL_SAFE_NO_PAD);
+        if client_data.challenge.trim_end_matches('=') != challenge {
+            return Err(Error::Signer(
+                "WebAuthn challenge does not match call".into(),
+            ));
+        }
+
+        let mut bytes = self.authenticator_data.to_vec();
+        bytes.extend_from_slice(&Sha256::digest(self.client_data_json.as_slice()));
+        Ok(bytes)
+    }
+}
+
+fn eth_address(pubkey: &PublicKey) -> Address {
+    let pubkey_bytes = pubkey.serialize_uncompressed();
+    let mut eth_pubkey = [0; 64];
+    eth_pubkey.copy_from_slice(&pubkey_bytes[1..]);
+    Address::from_pubkey_eth(eth_pubkey)
+}
+
+fn verify_ed25519(pubkey: &[u8; 32], msg: &[u8], signature: &[u8; 64]) -> Result<Address> {
+    use ed25519_dalek::Verifier;
+    let key = ed25519_dalek::PublicKey::from_bytes(pubkey)?;
+    let signature = ed25519_dalek::Signature::from_bytes(signature)?;
+    #[cfg(not(fuzzing))]
+    key.verify(msg, &signature)?;
+
+    Ok(Address::from_pubkey_ed25519(*pubkey))
+}
+
+fn verify_secp256r1(pubkey: &[u8; 33], msg: &[u8], signature: &[u8; 64]) -> Result<Address> {
+    use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+    let key = VerifyingKey::from_sec1_bytes(pubkey)
+        .map_err(|_| Error::Signer("Invalid secp256r1 public key".into()))?;
+    let signature = Signature::try_from(signature.as_slice())
+        .map_err(|_| Error::Signer("Invalid secp256r1 signature".into()))?;
+    #[cfg(not(fuzzing))]
+    key.verify(msg, &signature)
+        .map_err(|_| Error::Signer("Invalid secp256r1 signature".into()))?;
+
+    Ok(Address::from_pubkey_secp256r1(*pubkey))
 }
 
 #[derive(Serialize)]
@@ -138,17 +256,49 @@ where
     T: Deref<Target = U>,
     U: GetNonce,
 {
-    fn sdk_sign_bytes(&mut self, tx: &SdkTx, address: Address) -> Result<Vec<u8>> {
-        let nonce = self.inner.nonce(address)? + 1;
-        let chain_id = self
+    fn chain_id(&mut self) -> Result<String> {
+        Ok(self
             .context::<ChainId>()
             .ok_or_else(|| Error::App("Chain ID not found".to_string()))?
             .deref()
-            .to_string();
+            .to_string())
+    }
+
+    fn sdk_sign_bytes(&mut self, tx: &SdkTx, address: Address) -> Result<Vec<u8>> {
+        let nonce = self.inner.nonce(address)? + 1;
+        let chain_id = self.chain_id()?;
         tx.sign_bytes(chain_id, nonce)
     }
 
     fn verify(&mut self, call: &SignerCall) -> Result<Option<Address>> {
+        let malformed = || Error::Signer("Malformed transaction".into());
+        match (&call.sigtype, call.pubkey.as_ref(), call.signature.as_ref()) {
+            (SigType::Multisig(sigs), None, None) => {
+                let addr = sigs
+                    .multisig
+                    .verify(call.call_bytes.as_slice(), sigs.signatures.as_slice())?;
+                return Ok(Some(addr));
+            }
+            (SigType::Ed25519(pubkey), None, Some(signature)) => {
+                let addr = verify_ed25519(pubkey, call.call_bytes.as_slice(), signature)?;
+                return Ok(Some(addr));
+            }
+            (SigType::Secp256r1, Some(pubkey), Some(signature)) => {
+                let addr = verify_secp256r1(pubkey, call.call_bytes.as_slice(), signature)?;
+                return Ok(Some(addr));
+            }
+            (SigType::WebAuthn(assertion), Some(pubkey), Some(signature)) => {
+                let bytes = assertion.signed_bytes(call.call_bytes.as_slice())?;
+                let addr = verify_secp256r1(pubkey, bytes.as_slice(), signature)?;
+                return Ok(Some(addr));
+            }
+            (SigType::Multisig(_), _, _)
+            | (SigType::Ed25519(_), _, _)
+            | (SigType::Secp256r1, _, _)
+            | (SigType::WebAuthn(_), _, _) => return Err(malformed()),
+            _ => {}
+        }
+
         match (call.pubkey.as_ref(), call.signature) {
             (Some(pubkey_bytes), Some(signature)) => {
                 use secp256k1::hashes::sha256;
@@ -167,6 +317,10 @@ where
                         let msg = Message::from_hashed_data::<sha256::Hash>(bytes.as_slice());
                         (msg, addr)
                     }
+                    SigType::Multisig(_)
+                    | SigType::Ed25519(_)
+                    | SigType::Secp256r1
+                    | SigType::WebAuthn(_) => unreachable!(),
                     SigType::Sdk(tx) => {
                         let addr = Address::from_pubkey(*pubkey_bytes);
                         let bytes = self.sdk_sign_bytes(tx, addr)?;
@@ -181,10 +335,7 @@ where
                         (msg, addr)
                     }
                     SigType::EthPersonalSign(tx) => {
-                        let pubkey_bytes = pubkey.serialize_uncompressed();
-                        let mut eth_pubkey = [0; 64];
-                        eth_pubkey.copy_from_slice(&pubkey_bytes[1..]);
-                        let addr = Address::from_pubkey_eth(eth_pubkey);
+                        let addr = eth_address(&pubkey);
 
                         let prefix = b"\x19Ethereum Signed Message:\n";
                         let mut sdk_bytes = self.sdk_sign_bytes(tx, addr)?;
@@ -203,6 +354,20 @@ where
 
                         (msg, addr)
                     }
+                    SigType::Eip712 => {
+                        let addr = eth_address(&pubkey);
+                        let chain_id = self.chain_id()?;
+                        let data =
+                            TypedData::native(&chain_id, &self.inner, call.call_bytes.as_slice())?;
+                        (Message::from_slice(&data.hash()?)?, addr)
+                    }
+                    SigType::Eip712Sdk(tx) => {
+                        let addr = eth_address(&pubkey);
+                        let nonce = self.inner.nonce(addr)? + 1;
+                        let chain_id = self.chain_id()?;
+                        let data = TypedData::sdk(tx, &chain_id, nonce)?;
+                        (Message::from_slice(&data.hash()?)?, addr)
+                    }
                 };
 
                 let signature = Signature::from_compact(&signature)?;
@@ -212,7 +377,7 @@ where
                 Ok(Some(addr))
             }
             (None, None) => Ok(None),
-            _ => Err(Error::Signer("Malformed transaction".into())),
+            _ => Err(malformed()),
         }
     }
 }
@@ -226,9 +391,20 @@ where
 
     fn call(&mut self, call: Self::Call) -> Result<()> {
         Context::remove::<Signer>();
-        let signer_ctx = Signer {
-            signer: self.verify(&call)?,
+        let skip_signatures = self
+            .context::<Simulation>()
+            .map_or(false, |sim| sim.skip_signatures);
+        let signer = if skip_signatures {
+            // Simulated calls may be unsigned, so the signer is only derived
+            // from the public key
+            call.address().ok()
+        } else {
+            self.verify(&call)?
         };
+        if let Some(info) = Context::resolve::<CheckTxInfo>() {
+            info.sender = info.sender.or(signer);
+        }
+        let signer_ctx = Signer { signer };
         Context::add(signer_ctx);
 
         let inner_call = Decode::decode(call.call_bytes.as_slice())?;
@@ -254,6 +430,7 @@ pub(crate) fn sdk_to_signercall(sdk_tx: &SdkTx) -> Result<SignerCall> {
         None | Some("sdk") => SigType::Sdk(sdk_tx),
         Some("adr36") => SigType::Adr36WrappedSdk(sdk_tx),
         Some("eth") => SigType::EthPersonalSign(sdk_tx),
+        Some("eip712") => SigType::Eip712Sdk(sdk_tx),
         Some(_) => return Err(Error::App("Unknown signature type".to_string())),
     };
 
@@ -582,14 +759,19 @@ pub mod keplr {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub fn load_privkey() -> Result<SecretKey> {
+pub fn privkey_path() -> Result<std::path::PathBuf> {
     // Ensure orga home directory exists
     let orga_home = home::home_dir()
         .expect("No home directory set")
         .join(".orga-wallet");
 
     std::fs::create_dir_all(&orga_home)?;
-    let keypair_path = orga_home.join("privkey");
+    Ok(orga_home.join("privkey"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_privkey() -> Result<SecretKey> {
+    let keypair_path = privkey_path()?;
     if keypair_path.exists() {
         // Load existing key
         let bytes = std::fs::read(&keypair_path)?;
@@ -739,7 +921,7 @@ mod tests {
             },
         };
 
-        Context::add(ChainId("testchain"));
+        Context::add(ChainId("testchain".to_string()));
 
         // sign bytes: {"account_number":"0","chain_id":"testchain","fee":{"amount":[{"amount":"0","denom":"unom"}],"gas":"10000"},"memo":"","msgs":[{"type":"x","value":{}}],"sequence":"1"}
         // signature and pubkey taken from keplr
@@ -774,7 +956,7 @@ mod tests {
             },
         };
 
-        Context::add(ChainId("testchain"));
+        Context::add(ChainId("testchain".to_string()));
 
         // sign bytes: {"account_number":"0","chain_id":"testchain","fee":{"amount":[{"amount":"0","denom":"unom"}],"gas":"10000"},"memo":"","msgs":[{"type":"x","value":{}}],"sequence":"1"}
         // signature and pubkey taken from metamask
@@ -794,4 +976,60 @@ mod tests {
         );
         Context::remove::<ChainId>();
     }
+
+    fn counter() -> SignerPlugin<Counter> {
+        SignerPlugin {
+            inner: Counter {
+                count: 0,
+                last_signer: Address::NULL,
+                nonce: NonceNoop(()),
+            },
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn ed25519() {
+        let mut state = counter();
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[1; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let keypair = ed25519_dalek::Keypair { secret, public };
+
+        let call_bytes = <Counter as Call>::Call::MethodIncrement(vec![])
+            .encode()
+            .unwrap();
+        let call = SignerCall::sign_ed25519(&keypair, call_bytes);
+        let addr = call.address().unwrap();
+        assert_eq!(addr, Address::from_pubkey_ed25519(public.to_bytes()));
+
+        state.call(call).unwrap();
+        assert_eq!(state.count, 1);
+        assert_eq!(state.last_signer, addr);
+
+        let mut call = SignerCall::sign_ed25519(&keypair, vec![0]);
+        call.call_bytes = vec![1];
+        assert!(state.call(call).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn secp256r1() {
+        let mut state = counter();
+        let key = p256::ecdsa::SigningKey::from_bytes(&[1; 32]).unwrap();
+
+        let call_bytes = <Counter as Call>::Call::MethodIncrement(vec![])
+            .encode()
+            .unwrap();
+        let call = SignerCall::sign_secp256r1(&key, call_bytes.clone());
+        let addr = call.address().unwrap();
+        assert_eq!(addr, Address::from_pubkey_secp256r1(call.pubkey.unwrap()));
+
+        state.call(call).unwrap();
+        assert_eq!(state.count, 1);
+        assert_eq!(state.last_signer, addr);
+
+        let mut call = SignerCall::sign_secp256r1(&key, call_bytes);
+        call.pubkey = None;
+        assert!(state.call(call).is_err());
+    }
 }
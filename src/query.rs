@@ -1,5 +1,6 @@
 use crate::encoding::{Decode, Encode};
 use crate::{Error, Result};
+use std::convert::TryInto;
 use std::error::Error as StdError;
 use std::result::Result as StdResult;
 
@@ -11,6 +12,62 @@ pub trait Query {
     fn query(&self, query: Self::Query) -> Result<()>;
 }
 
+/// A list of queries which are resolved together against the same state, so
+/// that a single proof covers all of them.
+#[derive(Debug)]
+pub struct BatchQuery<T>(pub Vec<T>);
+
+impl<T: Encode> Encode for BatchQuery<T> {
+    fn encoding_length(&self) -> ed::Result<usize> {
+        let mut len = 2;
+        for query in self.0.iter() {
+            len += 2 + query.encoding_length()?;
+        }
+        Ok(len)
+    }
+
+    fn encode_into<W: std::io::Write>(&self, dest: &mut W) -> ed::Result<()> {
+        let count: u16 = self
+            .0
+            .len()
+            .try_into()
+            .map_err(|_| too_long("Batch has too many queries"))?;
+        dest.write_all(&count.encode()?)?;
+
+        for query in self.0.iter() {
+            let query_bytes = query.encode()?;
+            let query_len: u16 = query_bytes
+                .len()
+                .try_into()
+                .map_err(|_| too_long("Batched query is too long"))?;
+            dest.write_all(&query_len.encode()?)?;
+            dest.write_all(&query_bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Batch counts and query lengths are encoded as `u16`s.
+fn too_long(msg: &str) -> ed::Error {
+    ed::Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg))
+}
+
+impl<T: Decode> Decode for BatchQuery<T> {
+    fn decode<R: std::io::Read>(mut reader: R) -> ed::Result<Self> {
+        let count = u16::decode(&mut reader)?;
+        let mut queries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let query_len = u16::decode(&mut reader)?;
+            let mut query_bytes = vec![0u8; query_len as usize];
+            reader.read_exact(&mut query_bytes)?;
+            queries.push(T::decode(query_bytes.as_slice())?);
+        }
+
+        Ok(Self(queries))
+    }
+}
+
 impl<T: Query> Query for &T {
     type Query = T::Query;
 
@@ -186,3 +243,33 @@ impl<T: Query, const N: usize> Query for [T; N] {
         self[index].query(subquery)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_query_encoding() {
+        let batch = BatchQuery(vec![1u32, 2, 3]);
+        let bytes = batch.encode().unwrap();
+        assert_eq!(bytes.len(), batch.encoding_length().unwrap());
+        assert_eq!(
+            bytes,
+            vec![0, 3, 0, 4, 0, 0, 0, 1, 0, 4, 0, 0, 0, 2, 0, 4, 0, 0, 0, 3]
+        );
+
+        let decoded: BatchQuery<u32> = Decode::decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.0, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn batch_query_too_long() {
+        let batch = BatchQuery(vec![0u8; u16::MAX as usize + 1]);
+        match batch.encode() {
+            Err(ed::Error::IOError(err)) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput)
+            }
+            _ => panic!("expected batch too long error"),
+        }
+    }
+}
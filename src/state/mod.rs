@@ -21,25 +21,28 @@ pub trait State: Sized {
     fn load(store: Store, bytes: &mut &[u8]) -> Result<Self>;
 }
 
+/// Implements [State] for a type by its encoding, for types with no child
+/// state (e.g. primitives and simple enums).
 macro_rules! state_impl {
     ($type:ty) => {
-        impl State for $type {
+        impl crate::state::State for $type {
             #[inline]
-            fn attach(&mut self, _: Store) -> Result<()> {
+            fn attach(&mut self, _: crate::store::Store) -> crate::Result<()> {
                 Ok(())
             }
 
             #[inline]
-            fn flush<W: std::io::Write>(self, out: &mut W) -> Result<()> {
-                Ok(self.encode_into(out)?)
+            fn flush<W: std::io::Write>(self, out: &mut W) -> crate::Result<()> {
+                Ok(crate::encoding::Encode::encode_into(&self, out)?)
             }
 
-            fn load(_store: Store, bytes: &mut &[u8]) -> Result<Self> {
-                Ok(Self::decode(bytes)?)
+            fn load(_store: crate::store::Store, bytes: &mut &[u8]) -> crate::Result<Self> {
+                Ok(<Self as crate::encoding::Decode>::decode(bytes)?)
             }
         }
     };
 }
+pub(crate) use state_impl;
 
 state_impl!(u8);
 state_impl!(u16);
@@ -62,6 +62,13 @@ impl<S> BufStore<S> {
         self.map
     }
 
+    /// Returns the in-memory buffer of key/value entries, where `None` marks a
+    /// deleted key.
+    #[inline]
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
     #[inline]
     pub fn store(&self) -> &S {
         &self.store
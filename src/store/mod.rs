@@ -12,7 +12,7 @@ pub use bufstore::{BufStore, Map as BufStoreMap, MapStore};
 pub use iter::Iter;
 pub use nullstore::NullStore;
 pub use share::Shared;
-pub use store::{DefaultBackingStore, Store};
+pub use store::{DefaultBackingStore, Stage, Store};
 
 // TODO: Key type (for cheaper concat, enum over ref or owned slice, etc)
 
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::ops::RangeBounds;
 
-use super::{Iter, Read, Shared, Write, KV};
+use super::{Iter, MapStore, NullStore, Read, Shared, Write, KV};
 use crate::encoding::{Decode, Encode, Terminated};
 use crate::migrate::MigrateFrom;
 use crate::state::State;
@@ -126,6 +126,56 @@ impl<S> Store<S> {
     }
 }
 
+/// Backing stores which can create a staged copy of themselves, whose writes
+/// are buffered until they are committed back. See [Store::stage].
+pub trait Stage: Sized {
+    /// Creates a store which reads through to this store, but buffers its
+    /// writes rather than applying them to this store.
+    fn stage(&self) -> Self;
+
+    /// Applies the writes buffered in a store created by [Stage::stage].
+    fn commit(&mut self, staged: &Self) -> Result<()>;
+}
+
+impl Stage for Shared<MapStore> {
+    fn stage(&self) -> Self {
+        // map stores are fully in memory, so the staged store is a copy
+        Shared::new(MapStore::wrap_with_map(
+            NullStore,
+            self.borrow().map().clone(),
+        ))
+    }
+
+    fn commit(&mut self, staged: &Self) -> Result<()> {
+        let map = staged.borrow().map().clone();
+        *self.borrow_mut() = MapStore::wrap_with_map(NullStore, map);
+
+        Ok(())
+    }
+}
+
+impl<S: Stage> Store<S> {
+    /// Creates a store with the same prefix whose writes are buffered rather
+    /// than written to this store's backing store, e.g. to run operations
+    /// which must either succeed as a whole or change nothing.
+    ///
+    /// The buffered writes are applied by passing the staged store to
+    /// [Store::commit], or discarded by dropping it.
+    pub fn stage(&self) -> Self {
+        Store {
+            prefix: self.prefix.clone(),
+            store: Shared::new(self.store.borrow().stage()),
+        }
+    }
+
+    /// Applies the writes buffered in a store created by [Store::stage] to
+    /// this store's backing store.
+    pub fn commit(&self, staged: Self) -> Result<()> {
+        let staged = staged.store.borrow();
+        self.store.clone().borrow_mut().commit(&staged)
+    }
+}
+
 impl State for Store {
     fn attach(&mut self, store: Store) -> Result<()> {
         self.prefix = store.prefix;
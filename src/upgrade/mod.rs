@@ -2,6 +2,7 @@ use crate::coins::{Address, Amount, Decimal};
 use crate::collections::Map;
 use crate::context::GetContext;
 use crate::encoding::LengthVec;
+use crate::events::{emit, Event};
 use crate::orga;
 use crate::plugins::{Signer, Time, ValidatorEntry, Validators};
 use crate::{Error as OrgaError, Result};
@@ -46,6 +47,15 @@ impl Default for Upgrade {
     }
 }
 
+/// Emitted when a validator signals readiness for a version, with the
+/// consensus key and version hex-encoded.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[event(name = "upgrade_signal")]
+pub struct SignalEvent {
+    pub cons_key: String,
+    pub version: String,
+}
+
 impl Upgrade {
     #[call]
     pub fn signal(&mut self, version: Version) -> Result<()> {
@@ -70,7 +80,13 @@ impl Upgrade {
             }
         }
 
-        self.signals.insert(cons_key, signal)
+        let event = SignalEvent {
+            cons_key: hex::encode(cons_key),
+            version: hex::encode(signal.version.as_slice()),
+        };
+        self.signals.insert(cons_key, signal)?;
+
+        emit(event)
     }
 
     pub fn step(&mut self, version: &Version) -> Result<()> {
@@ -104,11 +120,11 @@ impl Upgrade {
                 }
             }
         }
-        let vp_threshold = (self.threshold * Amount::new(total_vp))?;
+        let vp_threshold = (self.threshold * Amount::from(total_vp))?;
 
         Ok(signal_vps
             .into_iter()
-            .find(|(_, vp)| Amount::new(*vp) > vp_threshold)
+            .find(|(_, vp)| Amount::from(*vp) > vp_threshold)
             .map(|(version, _)| version))
     }
 